@@ -392,6 +392,28 @@ do
 	module.gt = gt
 end
 
+do
+	local count = {}
+
+	local math_max = math.max
+
+	-- Counting loops pass the counter as it is first tested, after the
+	-- first pass, and get how many passes the loop runs in all
+	function count.lt_i32(num, bound)
+		return 1 + math_max(0, bound - num)
+	end
+
+	function count.lt_u32(num, bound)
+		return 1 + math_max(0, to_number(u32(bound)) - to_number(u32(num)))
+	end
+
+	function count.ne_i32(num, bound)
+		return 1 + (bound - num) % 0x100000000
+	end
+
+	module.count = count
+end
+
 do
 	local wrap = {}
 	local truncate = {}
@@ -444,7 +466,9 @@ do
 	truncate.f64 = truncate_f64
 
 	function saturate.i32_f32(num)
-		if num <= -0x80000000 then
+		if num ~= num then
+			return 0
+		elseif num <= -0x80000000 then
 			return -0x80000000
 		elseif num >= 0x7FFFFFFF then
 			return 0x7FFFFFFF
@@ -456,7 +480,7 @@ do
 	saturate.i32_f64 = saturate.i32_f32
 
 	function saturate.u32_f32(num)
-		if num <= 0 then
+		if num <= 0 or num ~= num then
 			return 0
 		elseif num >= 0xFFFFFFFF then
 			return -1
@@ -493,6 +517,23 @@ do
 
 	saturate.u64_f64 = saturate.u64_f32
 
+	-- References to `i31` are the low 31 bits of the number they were made from
+	function wrap.i31_i32(num)
+		return bit_and(num, 0x7FFFFFFF)
+	end
+
+	function extend.i32_i31(num)
+		if num >= 0x40000000 then
+			return num - 0x80000000
+		else
+			return num
+		end
+	end
+
+	function extend.u32_i31(num)
+		return num
+	end
+
 	function extend.i32_n8(num)
 		num = bit_and(num, 0xFF)
 
@@ -568,11 +609,17 @@ do
 	convert.f64_u32 = convert.f32_u32
 	convert.f64_u64 = convert.f32_u64
 
+	-- Storing to a `float` rounds to nearest with ties to even, while every
+	-- `float` is exact as a double so promotion has nothing to do
 	function demote.f32_f64(num)
-		return num
+		RE_INSTANCE.f32 = num
+
+		return RE_INSTANCE.f32
 	end
 
-	promote.f64_f32 = demote.f32_f64
+	function promote.f64_f32(num)
+		return num
+	end
 
 	function reinterpret.i32_f32(num)
 		RE_INSTANCE.f32 = num
@@ -636,7 +683,6 @@ do
 	};
 
 	void *calloc(size_t num, size_t size);
-	void *realloc(void *ptr, size_t size);
 	void free(void *ptr);
 	]])
 
@@ -773,11 +819,9 @@ do
 		ffi.C.free(memory.data)
 	end
 
+	-- The buffer is allocated for `max` pages up front, so growing only clears
+	-- the new pages and never reallocates or copies the ones before them
 	local function grow_unchecked(memory, old, new)
-		memory.data = ffi.C.realloc(memory.data, new)
-
-		assert(memory.data ~= nil, "failed to reallocate")
-
 		ffi.fill(by_offset(memory.data, old), new - old, 0)
 	end
 
@@ -814,4 +858,209 @@ do
 	module.allocator = allocator
 end
 
+do
+	local table = {}
+
+	local raw_get = rawget
+	local raw_set = rawset
+
+	-- Indices are checked against `min` since tables are never grown
+	local function check_bounds(tab, index)
+		if index < 0 or index >= tab.min then
+			error("out of bounds table access", 3)
+		end
+	end
+
+	function table.get(tab, index)
+		check_bounds(tab, index)
+
+		return tab.data[index]
+	end
+
+	function table.set(tab, index, value)
+		check_bounds(tab, index)
+
+		tab.data[index] = value
+	end
+
+	function table.raw_get(tab, index)
+		check_bounds(tab, index)
+
+		return raw_get(tab.data, index)
+	end
+
+	function table.raw_set(tab, index, value)
+		check_bounds(tab, index)
+
+		raw_set(tab.data, index, value)
+	end
+
+	module.table = table
+end
+
+do
+	local guard = {}
+
+	local math_fmod = math.fmod
+
+	local NUM_NEGATIVE_ONE = i64(-1)
+
+	local function constant(value)
+		return function()
+			return value
+		end
+	end
+
+	local function dividend(lhs)
+		return lhs
+	end
+
+	-- Divisors of zero never reach the operation, which would trap
+	local function guarded(operation, on_zero)
+		return function(lhs, rhs)
+			if rhs == 0 then
+				return on_zero(lhs)
+			end
+
+			return operation(lhs, rhs)
+		end
+	end
+
+	local operation_list = {
+		div_i32 = { module.div.i32, 0, -1 },
+		div_u32 = { module.div.u32, 0, -1 },
+		div_i64 = {
+			function(lhs, rhs)
+				return lhs / rhs
+			end,
+			NUM_ZERO,
+			NUM_NEGATIVE_ONE,
+		},
+		div_u64 = { module.div.u64, NUM_ZERO, NUM_NEGATIVE_ONE },
+		rem_i32 = { math_fmod, 0 },
+		rem_u32 = { module.rem.u32, 0 },
+		rem_i64 = {
+			function(lhs, rhs)
+				return lhs % rhs
+			end,
+			NUM_ZERO,
+		},
+		rem_u64 = { module.rem.u64, NUM_ZERO },
+	}
+
+	-- Wrapped results follow RISC-V, where a quotient by zero has all bits
+	-- set and a remainder by zero is the dividend
+	for name, data in pairs(operation_list) do
+		local operation, zero, wrapped = data[1], data[2], data[3]
+
+		guard["zero_" .. name] = guarded(operation, constant(zero))
+		guard["wrapped_" .. name] = guarded(operation, wrapped and constant(wrapped) or dividend)
+	end
+
+	module.guard = guard
+end
+
+do
+	local debug = {}
+
+	local ffi_istype = ffi.istype
+
+	local function check_i32(num, name)
+		if type(num) ~= "number" or to_signed(num) ~= num then
+			error(name .. " is not an i32: " .. tostring(num), 3)
+		end
+	end
+
+	local function check_i64(num, name)
+		if not ffi_istype(i64, num) then
+			error(name .. " is not an i64: " .. tostring(num), 3)
+		end
+	end
+
+	-- Operands and results are checked against their representation so a
+	-- miscompiled operation is reported where it happens
+	local function checked(operation, check)
+		return function(lhs, rhs)
+			check(lhs, "left operand")
+			check(rhs, "right operand")
+
+			local result = operation(lhs, rhs)
+
+			check(result, "result")
+
+			return result
+		end
+	end
+
+	debug.checked_add_i32 = checked(module.add.i32, check_i32)
+	debug.checked_sub_i32 = checked(module.sub.i32, check_i32)
+	debug.checked_mul_i32 = checked(module.mul.i32, check_i32)
+
+	debug.checked_add_i64 = checked(function(lhs, rhs)
+		return lhs + rhs
+	end, check_i64)
+
+	debug.checked_sub_i64 = checked(function(lhs, rhs)
+		return lhs - rhs
+	end, check_i64)
+
+	debug.checked_mul_i64 = checked(function(lhs, rhs)
+		return lhs * rhs
+	end, check_i64)
+
+	module.debug = debug
+end
+
+do
+	local extern = {}
+
+	local select = select
+
+	-- Hosts replace `release` to free handles once `externref` locals stop
+	-- holding them. It does nothing by default
+	function extern.release(_) end
+
+	-- The values the other `externref` locals hold follow `new`, and are
+	-- kept if one of them is `old`
+	function extern.replace(old, new, ...)
+		if old == nil or old == new then
+			return new
+		end
+
+		for i = 1, select("#", ...) do
+			if select(i, ...) == old then
+				return new
+			end
+		end
+
+		extern.release(old)
+
+		return new
+	end
+
+	-- Each value held by the locals at a return is released once, unless the
+	-- function is also returning it
+	function extern.leave(list, ...)
+		local seen = {}
+
+		for i = 1, select("#", ...) do
+			local value = select(i, ...)
+
+			if value ~= nil and value == value then
+				seen[value] = true
+			end
+		end
+
+		for _, old in pairs(list) do
+			if not seen[old] then
+				seen[old] = true
+
+				extern.release(old)
+			end
+		end
+	end
+
+	module.extern = extern
+end
+
 return module
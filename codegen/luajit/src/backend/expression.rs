@@ -4,27 +4,17 @@ use std::{
 };
 
 use wasm_ast::node::{
-	BinOp, CmpOp, Expression, GetGlobal, LoadAt, Local, MemorySize, Select, Temporary, UnOp, Value,
+	BinOp, BinOpType, CmpOp, Expression, GetGlobal, LoadAt, Local, MemorySize, Select, Temporary,
+	UnOp, Value,
 };
 
-use crate::analyzer::as_symbol::AsSymbol;
+use crate::{
+	analyzer::{as_symbol::AsSymbol, identity},
+	options::DivisionPolicy,
+};
 
 use super::manager::{write_separated, Driver, Manager};
 
-macro_rules! impl_write_number {
-	($name:tt, $numeric:ty) => {
-		fn $name(number: $numeric, w: &mut dyn Write) -> Result<()> {
-			match (number.classify(), number.is_sign_negative()) {
-				(FpCategory::Nan, true) => write!(w, "(0.0 / 0.0)"),
-				(FpCategory::Nan, false) => write!(w, "-(0.0 / 0.0)"),
-				(FpCategory::Infinite, true) => write!(w, "-math.huge"),
-				(FpCategory::Infinite, false) => write!(w, "math.huge"),
-				_ => write!(w, "{number:e}"),
-			}
-		}
-	};
-}
-
 impl Driver for Select {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
 		write!(w, "(")?;
@@ -39,7 +29,7 @@ impl Driver for Select {
 
 impl Driver for Temporary {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
-		let var = self.var();
+		let var = mng.get_register(self.var());
 
 		if let Some(var) = var.checked_sub(mng.num_temp()) {
 			write!(w, "reg_spill[{}]", var + 1)
@@ -49,15 +39,17 @@ impl Driver for Temporary {
 	}
 }
 
+pub fn write_local(var: usize, mng: &Manager, w: &mut dyn Write) -> Result<()> {
+	if let Some(var) = var.checked_sub(mng.num_local()) {
+		write!(w, "loc_spill[{}]", var + 1)
+	} else {
+		write!(w, "loc_{var}")
+	}
+}
+
 impl Driver for Local {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
-		let var = self.var();
-
-		if let Some(var) = var.checked_sub(mng.num_local()) {
-			write!(w, "loc_spill[{}]", var + 1)
-		} else {
-			write!(w, "loc_{var}")
-		}
+		write_local(self.var(), mng, w)
 	}
 }
 
@@ -67,30 +59,82 @@ impl Driver for GetGlobal {
 	}
 }
 
+pub fn write_load_at(load: &LoadAt, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	mng.stats_mut().loads += 1;
+
+	let name = load.load_type().as_name();
+	let memory = load.memory();
+
+	write!(w, "load_{name}(memory_at_{memory}, ")?;
+	write_address(load.pointer(), load.offset(), mng, w)?;
+	write!(w, ")")
+}
+
 impl Driver for LoadAt {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
-		let name = self.load_type().as_name();
-		let memory = self.memory();
+		match mng.get_hoisted(self) {
+			Some(slot) => write!(w, "hoist_{slot}"),
+			None => write_load_at(self, mng, w),
+		}
+	}
+}
 
-		write!(w, "load_{name}(memory_at_{memory}, ")?;
-		self.pointer().write(mng, w)?;
+impl Driver for MemorySize {
+	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		let memory = self.memory();
 
-		if self.offset() != 0 {
-			write!(w, " + {}", self.offset())?;
+		if mng.is_memory64(memory) {
+			write!(w, "(memory_at_{memory}.min + 0LL)")
+		} else {
+			write!(w, "memory_at_{memory}.min")
 		}
-
-		write!(w, ")")
 	}
 }
 
-impl Driver for MemorySize {
-	fn write(&self, _mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
-		write!(w, "memory_at_{}.min", self.memory())
+// Lua numbers are doubles, so `f32` constants are widened first; formatting
+// the narrow value would yield its shortest decimal, which parses back as
+// a slightly different double. Rust prints the shortest decimal that
+// round-trips, so the finite values, subnormals included, come back exact.
+// Division by zero yields a NaN with the sign bit set on common hosts, so
+// only the canonical NaNs are written that way and the rest are rebuilt
+// from their bits. Canonical ones still take the sign the host gives them.
+fn write_f64(number: f64, w: &mut dyn Write) -> Result<()> {
+	if number.is_nan() && number.to_bits() << 1 != 0xFFF0_0000_0000_0000 {
+		return write_f64_bits(number.to_bits(), w);
 	}
+
+	match (number.classify(), number.is_sign_negative()) {
+		(FpCategory::Nan, true) => write!(w, "(0.0 / 0.0)"),
+		(FpCategory::Nan, false) => write!(w, "-(0.0 / 0.0)"),
+		(FpCategory::Infinite, true) => write!(w, "-math.huge"),
+		(FpCategory::Infinite, false) => write!(w, "math.huge"),
+		_ => write!(w, "{number:e}"),
+	}
+}
+
+fn write_f32_bits(bits: u32, w: &mut dyn Write) -> Result<()> {
+	write!(
+		w,
+		"rt.reinterpret.f32_i32({})",
+		i32::from_ne_bytes(bits.to_ne_bytes())
+	)
 }
 
-impl_write_number!(write_f32, f32);
-impl_write_number!(write_f64, f64);
+fn write_f64_bits(bits: u64, w: &mut dyn Write) -> Result<()> {
+	write!(
+		w,
+		"rt.reinterpret.f64_i64({}LL)",
+		i64::from_ne_bytes(bits.to_ne_bytes())
+	)
+}
+
+fn write_f32(number: f32, w: &mut dyn Write) -> Result<()> {
+	if number.is_nan() && number.to_bits() << 1 != 0xFF80_0000 {
+		return write_f32_bits(number.to_bits(), w);
+	}
+
+	write_f64(f64::from(number), w)
+}
 
 impl Driver for Value {
 	fn write(&self, _mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
@@ -113,9 +157,83 @@ impl Driver for UnOp {
 	}
 }
 
+const fn is_checked(op_type: BinOpType) -> bool {
+	matches!(
+		op_type,
+		BinOpType::Add_I32
+			| BinOpType::Sub_I32
+			| BinOpType::Mul_I32
+			| BinOpType::Add_I64
+			| BinOpType::Sub_I64
+			| BinOpType::Mul_I64
+	)
+}
+
+// Only a zero divisor is guarded, so quotients that overflow still trap
+const fn as_guard_prefix(policy: DivisionPolicy, op_type: BinOpType) -> Option<&'static str> {
+	let is_division = matches!(
+		op_type,
+		BinOpType::DivS_I32
+			| BinOpType::DivU_I32
+			| BinOpType::RemS_I32
+			| BinOpType::RemU_I32
+			| BinOpType::DivS_I64
+			| BinOpType::DivU_I64
+			| BinOpType::RemS_I64
+			| BinOpType::RemU_I64
+	);
+
+	match policy {
+		_ if !is_division => None,
+		DivisionPolicy::Trap => None,
+		DivisionPolicy::ReturnZero => Some("zero"),
+		DivisionPolicy::ReturnWrapped => Some("wrapped"),
+	}
+}
+
+// Constant shift and rotate counts are reduced modulo the operand width as
+// WebAssembly requires, so the emitted call only ever sees an in-range count
+fn write_bin_op_rhs(bin: &BinOp, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let is_count = matches!(
+		bin.op_type(),
+		BinOpType::Shl_I32
+			| BinOpType::ShrS_I32
+			| BinOpType::ShrU_I32
+			| BinOpType::Rotl_I32
+			| BinOpType::Rotr_I32
+			| BinOpType::Shl_I64
+			| BinOpType::ShrS_I64
+			| BinOpType::ShrU_I64
+			| BinOpType::Rotl_I64
+			| BinOpType::Rotr_I64
+	);
+
+	match bin.rhs() {
+		Expression::Value(Value::I32(count)) if is_count => Value::I32(count & 31).write(mng, w),
+		Expression::Value(Value::I64(count)) if is_count => Value::I64(count & 63).write(mng, w),
+		rhs => rhs.write(mng, w),
+	}
+}
+
 impl Driver for BinOp {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
-		if let Some(symbol) = self.op_type().as_symbol() {
+		if let Some(operand) = identity::as_identity(self).filter(|_| mng.has_fold_identities()) {
+			return operand.write(mng, w);
+		}
+
+		if mng.has_checked_arithmetic() && is_checked(self.op_type()) {
+			let (head, tail) = self.op_type().as_name();
+
+			write!(w, "rt.debug.checked_{head}_{tail}(")?;
+			self.lhs().write(mng, w)?;
+			write!(w, ", ")?;
+		} else if let Some(prefix) = as_guard_prefix(mng.division_policy(), self.op_type()) {
+			let (head, tail) = self.op_type().as_name();
+
+			write!(w, "rt.guard.{prefix}_{head}_{tail}(")?;
+			self.lhs().write(mng, w)?;
+			write!(w, ", ")?;
+		} else if let Some(symbol) = self.op_type().as_symbol() {
 			write!(w, "(")?;
 			self.lhs().write(mng, w)?;
 			write!(w, " {symbol} ")?;
@@ -127,11 +245,25 @@ impl Driver for BinOp {
 			write!(w, ", ")?;
 		}
 
-		self.rhs().write(mng, w)?;
+		write_bin_op_rhs(self, mng, w)?;
 		write!(w, ")")
 	}
 }
 
+// Largest magnitude below which every integer is exact as a double
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+
+// Comparisons convert a number to `int64_t` when the other side is one, so
+// small constants can skip the cdata literal and the boxing that comes with it
+fn write_cmp_operand(operand: &Expression, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	match operand {
+		Expression::Value(Value::I64(i)) if i.unsigned_abs() <= MAX_SAFE_INTEGER => {
+			write!(w, "{i}")
+		}
+		_ => operand.write(mng, w),
+	}
+}
+
 struct CmpOpBoolean<'a>(&'a CmpOp);
 
 impl Driver for CmpOpBoolean<'_> {
@@ -139,9 +271,9 @@ impl Driver for CmpOpBoolean<'_> {
 		let cmp = self.0;
 
 		if let Some(symbol) = cmp.op_type().as_symbol() {
-			cmp.lhs().write(mng, w)?;
+			write_cmp_operand(cmp.lhs(), mng, w)?;
 			write!(w, " {symbol} ")?;
-			cmp.rhs().write(mng, w)
+			write_cmp_operand(cmp.rhs(), mng, w)
 		} else {
 			let (head, tail) = cmp.op_type().as_name();
 
@@ -162,6 +294,25 @@ impl Driver for CmpOp {
 	}
 }
 
+pub fn write_address(
+	pointer: &Expression,
+	offset: u32,
+	mng: &mut Manager,
+	w: &mut dyn Write,
+) -> Result<()> {
+	if let Some(index) = mng.get_address(pointer) {
+		write!(w, "addr_{index}")?;
+	} else {
+		pointer.write(mng, w)?;
+	}
+
+	if offset != 0 {
+		write!(w, " + {offset}")?;
+	}
+
+	Ok(())
+}
+
 pub struct Condition<'a>(pub &'a Expression);
 
 impl Driver for Condition<'_> {
@@ -1,9 +1,14 @@
-use std::io::{Result, Write};
+use std::{
+	io::{Error, Result, Write},
+	process::{Command, Stdio},
+};
 
 use wasm_ast::module::Module;
 
 fn load_arg_source() -> Result<Vec<u8>> {
-	let name = std::env::args().nth(1).expect("usage: wasm2luajit <file>");
+	let name = std::env::args()
+		.nth(1)
+		.expect("usage: wasm2luajit <file> [bytecode output]");
 
 	std::fs::read(name)
 }
@@ -16,12 +21,36 @@ fn do_runtime(lock: &mut dyn Write) -> Result<()> {
 	writeln!(lock, "end)()")
 }
 
+// Source is piped through `luajit -b` so hosts can skip parsing at load time
+fn do_bytecode(source: &[u8], output: &str) -> Result<()> {
+	let mut child = Command::new("luajit")
+		.args(["-b", "-", output])
+		.stdin(Stdio::piped())
+		.spawn()?;
+
+	child.stdin.take().unwrap().write_all(source)?;
+
+	if child.wait()?.success() {
+		Ok(())
+	} else {
+		Err(Error::other("luajit failed to compile the source"))
+	}
+}
+
 fn main() -> Result<()> {
 	let data = load_arg_source()?;
 	let wasm = Module::try_from_data(&data).unwrap();
 
-	let lock = &mut std::io::stdout().lock();
+	let Some(output) = std::env::args().nth(2) else {
+		let lock = &mut std::io::stdout().lock();
+
+		do_runtime(lock)?;
+		return codegen_luajit::from_module_untyped(&wasm, lock);
+	};
+
+	let mut source = Vec::new();
 
-	do_runtime(lock)?;
-	codegen_luajit::from_module_untyped(&wasm, lock)
+	do_runtime(&mut source)?;
+	codegen_luajit::from_module_untyped(&wasm, &mut source)?;
+	do_bytecode(&source, &output)
 }
@@ -1,7 +1,18 @@
 pub static RUNTIME: &str = include_str!("../runtime/runtime.lua");
 
-pub use translator::{from_inst_list, from_module_typed, from_module_untyped};
+pub use options::{DivisionPolicy, Hook, Options};
+pub use translator::{
+	from_func_list, from_func_patch, from_inst_list, from_module_covered, from_module_list,
+	from_module_typed, from_module_untyped, from_pure_func, transpile, transpile_list,
+	transpile_with_hash,
+};
+pub use wasm_ast::{
+	coverage::{Branch, Outcome},
+	fingerprint::Fingerprint,
+	stats::FunctionStats,
+};
 
 mod analyzer;
 mod backend;
+mod options;
 mod translator;
@@ -0,0 +1,259 @@
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	io::{Result, Write},
+};
+
+/// Writes code for the function with the given index, as whole lines.
+pub type Hook = Box<dyn Fn(usize, &mut dyn Write) -> Result<()>>;
+
+/// What integer division and remainder give when the divisor is zero.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum DivisionPolicy {
+	/// Traps, as the specification requires.
+	#[default]
+	Trap,
+	/// Gives zero.
+	ReturnZero,
+	/// Gives all bits set for a quotient and the dividend for a remainder,
+	/// as RISC-V does.
+	ReturnWrapped,
+}
+
+/// Settings that alter how a module is translated. The defaults produce
+/// the same output as the plain `from_module_untyped` entry point.
+#[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Options {
+	/// Indices of functions that receive their parameters as `...` and
+	/// unpack them on entry, which eases writing generic host dispatchers.
+	pub vararg_set: BTreeSet<usize>,
+	/// Indices of functions that assert the types of their parameters on
+	/// entry, which catches host binding mistakes before they surface deep
+	/// inside arithmetic.
+	pub assert_set: BTreeSet<usize>,
+	/// Indices of functions that coerce their parameters on entry with
+	/// `tonumber`, so loosely typed hosts may pass strings. Values it does
+	/// not accept become zero.
+	pub coerce_set: BTreeSet<usize>,
+	/// Indices of functions whose indirect calls remember the last element
+	/// they called and skip the table lookup while the index repeats. This
+	/// assumes called elements are not replaced while those functions run.
+	pub inline_cache_set: BTreeSet<usize>,
+	/// Indices of functions that cache their results by the exact value of
+	/// their arguments, so repeated calls are only computed once. The cache
+	/// is never cleared, and functions that are not pure or take references
+	/// are left as is.
+	pub memoize_set: BTreeSet<usize>,
+	/// Indices of imported functions that return their results packed in a
+	/// single table, which is unpacked at each call. Hosts then need not
+	/// return several values from Lua.
+	pub packed_result_set: BTreeSet<usize>,
+	/// Indices of imported functions whose results default to zero when the
+	/// host returns fewer than declared. Lua already drops extra arguments
+	/// and results, so hosts may then bind functions of any arity.
+	pub flexible_result_set: BTreeSet<usize>,
+	/// Source locations, such as `src/lib.rs:42`, written as a comment above
+	/// the functions with the given indices. Callers can find them from the
+	/// DWARF line table of the module using the offset of each code body.
+	pub location_map: BTreeMap<usize, String>,
+	/// Writes exported functions first, ordered by name, followed by the rest
+	/// in index order. Output then stays stable when functions are reordered
+	/// in the module but otherwise unchanged.
+	pub sort_func_list: bool,
+	/// Starts every `reg_*` temporary at 0 rather than `nil`, so a register
+	/// read before it is written gives a wrong value instead of an error.
+	/// This is meant for debugging the code generator.
+	pub zero_temporaries: bool,
+	/// Calls `rt.extern.release` with each value an `externref` local stops
+	/// holding, when it is overwritten or the function returns, so hosts can
+	/// free their handles. A value another `externref` local still holds is
+	/// kept, but values in temporaries, globals, or tables are not tracked,
+	/// so a value stored in a global or table may still be released.
+	pub release_externs: bool,
+	/// Writes integer `add`, `sub`, and `mul` as calls to `rt.debug`, which
+	/// trap when an operand or the result is not a valid integer. This is
+	/// meant for finding miscompiles, at a large cost in speed.
+	pub checked_arithmetic: bool,
+	/// Chooses how integer division and remainder handle a zero divisor.
+	/// Other policies than `Trap` write them as calls to `rt.guard` so that
+	/// untrusted code cannot raise errors through them.
+	pub division_policy: DivisionPolicy,
+	/// Lets temporaries that are never live at once share a register, so
+	/// fewer `reg_*` locals are declared and fewer spill past the limit on
+	/// locals.
+	pub coalesce_registers: bool,
+	/// Writes integer operations that cannot change their operand, such as
+	/// `x + 0` or `x * 1`, as the operand alone.
+	pub fold_identities: bool,
+	/// Reads loads whose address is fixed within a loop that never writes
+	/// memory once before it is entered, rather than on every pass.
+	pub hoist_loads: bool,
+	/// Writes loops that count a local up to a fixed bound as numeric `for`
+	/// loops, with the number of passes worked out once on entry.
+	pub counting_loops: bool,
+	/// Writes an `if` that only sets a number local as one assignment picking
+	/// the new or old value with `and` and `or`, so no branch is written.
+	pub conditional_moves: bool,
+	/// Writes a comment naming the instruction above each statement it was
+	/// translated from, such as `-- i32.add`, to help follow the code back
+	/// to the module.
+	pub opcode_comments: bool,
+	/// Names labels after their kind, as in `loop_3` or `block_exit_5`, in
+	/// place of `continue_at_3`. This only makes the code easier to read.
+	pub readable_labels: bool,
+	/// Annotates `loc_*` parameters and locals with Ravi types, `integer`
+	/// for `i32` and `number` for `f32` and `f64`. Values of `i64` are
+	/// `int64_t` cdata here, so those and references are left as they are.
+	/// The output then only parses in Ravi, which must also provide the FFI.
+	pub ravi_annotations: bool,
+	/// Table the host creates for counting how often each branch goes each
+	/// way, keyed by index in the legend that `from_module_covered` gives.
+	/// Branches are then always written in full, so `counting_loops` and
+	/// similar rewrites that drop a test are left off.
+	pub coverage_table: Option<String>,
+	/// Deepest nesting of calls allowed before trapping, which keeps guest
+	/// recursion from overflowing the host's stack. Exported functions reset
+	/// the depth when a trap unwinds through them.
+	pub depth_limit: Option<usize>,
+	/// Variable holding the call depth when `depth_limit` is set. The host
+	/// must initialize it to 0, or it defaults to a module local.
+	pub depth_counter: Option<String>,
+	/// Bytes of code a single function may be written as before translation
+	/// fails, naming the function. This catches functions too long for Lua
+	/// to load; they are not split up.
+	pub function_size_limit: Option<usize>,
+	/// Bytes the whole output of a module may take before translation fails
+	/// with `ErrorKind::InvalidData`. Nothing past the limit is written, which
+	/// bounds what hosted services spend on pathological modules.
+	pub output_size_limit: Option<usize>,
+	/// Bytes of output gathered in memory before each write to the given
+	/// `Write`, or the standard library default when unset. Writers that
+	/// already buffer can set it to 0 to have every write passed through.
+	pub write_buffer_size: Option<usize>,
+	/// Table the `load_*` helpers are taken from in place of `rt.load`, for
+	/// memories that are only reachable through host accessors.
+	pub load_source: Option<String>,
+	/// Table the `store_*` helpers are taken from in place of `rt.store`.
+	/// Its `copy` and `fill` serve `memory.copy` and `memory.fill`, so every
+	/// write made by running code goes through it.
+	pub store_source: Option<String>,
+	/// Function called with the message when the code traps, in place of
+	/// `error`. Runtime helpers that trap still call `error` directly.
+	pub trap_function: Option<String>,
+	/// Table the host creates for recording traps while fuzzing. Exported
+	/// functions then catch any trap, add one to its `count`, set `trapped`
+	/// and `message`, and return no results instead of raising the error.
+	pub trap_counter: Option<String>,
+	/// Makes reading an imported global that the host left unbound trap
+	/// with its name, rather than fail later on indexing `nil`. This is
+	/// meant for debugging host bindings.
+	pub trap_unbound_globals: bool,
+	/// Keeps the functions of the table in a plain array indexed directly by
+	/// `call_indirect`. This only applies to a single table that is neither
+	/// imported nor exported, since the host cannot reach it.
+	pub flat_table: bool,
+	/// Writes the functions of element segments as a map that a table looks
+	/// up in `FUNC_LIST` on first access instead of copying them all during
+	/// instantiation. This suits large, sparsely used tables, though calls
+	/// through entries not yet resolved or empty go through a metatable.
+	pub lazy_elements: bool,
+	/// Reads and writes the functions of tables with `rawget` and `rawset`,
+	/// skipping metatables the host is known not to install. Memory is left
+	/// as is, and this is ignored with `lazy_elements`, which relies on them.
+	pub raw_access: bool,
+	/// Makes instantiation only bind the imports and return an `INIT`
+	/// function. Calling it runs the initializers and start function, then
+	/// returns the exports, so hosts choose when initialization happens.
+	/// Linked module lists reject it, since they pass exports along.
+	pub deferred_init: bool,
+	/// Adds a `signature_list` to the exports giving the parameter and result
+	/// counts and types of each exported function, for generic hosts that
+	/// marshal calls.
+	pub export_signatures: bool,
+	/// Adds an `internal_list` to the exports holding the function, table,
+	/// memory, and global lists of the instance, which chunks written by
+	/// `from_func_patch` are called with.
+	pub internal_lists: bool,
+	/// Argument given to `require` to bind `rt` at the top of the chunk,
+	/// written as is so it may be a quoted name or an expression. The chunk
+	/// is then a module that can be required on its own, and otherwise `rt`
+	/// must already be in scope.
+	pub runtime_module: Option<String>,
+	/// Writes code at the start of every function, after its locals are set
+	/// up, for tracing or profiling without changing the code generator.
+	pub entry_hook: Option<Hook>,
+	/// Writes code at the end of every function, before it returns. Every
+	/// normal return passes through it, while traps skip it.
+	pub exit_hook: Option<Hook>,
+	/// Starts the chunk with a comment naming the version, backend, and the
+	/// options changed from their defaults, recording how it was built.
+	pub banner: bool,
+}
+
+impl Options {
+	pub(crate) fn depth_counter(&self) -> &str {
+		self.depth_counter.as_deref().unwrap_or("CALL_DEPTH")
+	}
+
+	pub(crate) fn store_source(&self) -> &str {
+		self.store_source.as_deref().unwrap_or("rt.store")
+	}
+
+	pub(crate) fn trap_function(&self) -> &str {
+		self.trap_function.as_deref().unwrap_or("error")
+	}
+
+	/// Names of the options changed from their defaults, in declaration order.
+	pub(crate) fn changed_list(&self) -> Vec<&'static str> {
+		let list = [
+			("vararg_set", !self.vararg_set.is_empty()),
+			("assert_set", !self.assert_set.is_empty()),
+			("coerce_set", !self.coerce_set.is_empty()),
+			("inline_cache_set", !self.inline_cache_set.is_empty()),
+			("memoize_set", !self.memoize_set.is_empty()),
+			("packed_result_set", !self.packed_result_set.is_empty()),
+			("flexible_result_set", !self.flexible_result_set.is_empty()),
+			("location_map", !self.location_map.is_empty()),
+			("sort_func_list", self.sort_func_list),
+			("zero_temporaries", self.zero_temporaries),
+			("release_externs", self.release_externs),
+			("checked_arithmetic", self.checked_arithmetic),
+			(
+				"division_policy",
+				self.division_policy != DivisionPolicy::Trap,
+			),
+			("coalesce_registers", self.coalesce_registers),
+			("fold_identities", self.fold_identities),
+			("hoist_loads", self.hoist_loads),
+			("counting_loops", self.counting_loops),
+			("conditional_moves", self.conditional_moves),
+			("opcode_comments", self.opcode_comments),
+			("readable_labels", self.readable_labels),
+			("ravi_annotations", self.ravi_annotations),
+			("coverage_table", self.coverage_table.is_some()),
+			("depth_limit", self.depth_limit.is_some()),
+			("depth_counter", self.depth_counter.is_some()),
+			("function_size_limit", self.function_size_limit.is_some()),
+			("output_size_limit", self.output_size_limit.is_some()),
+			("write_buffer_size", self.write_buffer_size.is_some()),
+			("load_source", self.load_source.is_some()),
+			("store_source", self.store_source.is_some()),
+			("trap_function", self.trap_function.is_some()),
+			("trap_counter", self.trap_counter.is_some()),
+			("trap_unbound_globals", self.trap_unbound_globals),
+			("flat_table", self.flat_table),
+			("lazy_elements", self.lazy_elements),
+			("raw_access", self.raw_access),
+			("deferred_init", self.deferred_init),
+			("export_signatures", self.export_signatures),
+			("internal_lists", self.internal_lists),
+			("runtime_module", self.runtime_module.is_some()),
+			("entry_hook", self.entry_hook.is_some()),
+			("exit_hook", self.exit_hook.is_some()),
+		];
+
+		list.into_iter()
+			.filter_map(|(name, is_changed)| is_changed.then_some(name))
+			.collect()
+	}
+}
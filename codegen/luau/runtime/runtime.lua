@@ -85,6 +85,7 @@ do
 		return to_u32((math_modf(lhs / rhs)))
 	end
 
+	-- Values are kept in `0..2^32`, so the unsigned forms need no correction
 	function div.u32(lhs, rhs)
 		assert(rhs ~= 0, "division by zero")
 
@@ -303,6 +304,28 @@ do
 	module.gt = gt
 end
 
+do
+	local count = {}
+
+	local math_max = math.max
+
+	-- Counting loops pass the counter as it is first tested, after the
+	-- first pass, and get how many passes the loop runs in all
+	function count.lt_i32(num, bound)
+		return 1 + math_max(0, to_i32(bound) - to_i32(num))
+	end
+
+	function count.lt_u32(num, bound)
+		return 1 + math_max(0, bound - num)
+	end
+
+	function count.ne_i32(num, bound)
+		return 1 + (bound - num) % 0x100000000
+	end
+
+	module.count = count
+end
+
 do
 	local band = {}
 	local bor = {}
@@ -437,7 +460,12 @@ do
 	truncate.f32 = truncate_f64
 	truncate.f64 = truncate_f64
 
+	-- Comparisons with NaN are all false, so it is caught before clamping
 	function saturate.i32_f32(num)
+		if num ~= num then
+			return 0
+		end
+
 		local temp = math_clamp(truncate_f64(num), -0x80000000, 0x7FFFFFFF)
 
 		return to_u32(temp)
@@ -446,6 +474,10 @@ do
 	saturate.i32_f64 = saturate.i32_f32
 
 	function saturate.u32_f32(num)
+		if num ~= num then
+			return 0
+		end
+
 		local temp = math_clamp(truncate_f64(num), 0, 0xFFFFFFFF)
 
 		return to_u32(temp)
@@ -460,6 +492,8 @@ do
 			return NUM_MAX_I64
 		elseif num <= -2 ^ 63 then
 			return NUM_MIN_I64
+		elseif num ~= num then
+			return NUM_ZERO
 		else
 			return truncate_i64_f64(num)
 		end
@@ -470,7 +504,7 @@ do
 	function saturate.u64_f32(num)
 		if num >= 2 ^ 64 then
 			return NUM_MAX_U64
-		elseif num <= 0 then
+		elseif num <= 0 or num ~= num then
 			return NUM_ZERO
 		else
 			return truncate_i64_f64(num)
@@ -479,6 +513,23 @@ do
 
 	saturate.u64_f64 = saturate.u64_f32
 
+	-- References to `i31` are the low 31 bits of the number they were made from
+	function wrap.i31_i32(num)
+		return bit_and(num, 0x7FFFFFFF)
+	end
+
+	function extend.i32_i31(num)
+		if num >= 0x40000000 then
+			return to_u32(num - 0x80000000)
+		else
+			return num
+		end
+	end
+
+	function extend.u32_i31(num)
+		return num
+	end
+
 	function extend.i32_n8(num)
 		num = bit_and(num, 0xFF)
 
@@ -572,7 +623,11 @@ do
 	convert.f64_i64 = convert.f32_i64
 	convert.f64_u64 = num_into_u64
 
-	demote.f32_f64 = no_op
+	-- Packing as a `float` rounds to nearest with ties to even, while every
+	-- `float` is exact as a double so promotion has nothing to do
+	function demote.f32_f64(num)
+		return (string_unpack("f", string_pack("f", num)))
+	end
 
 	promote.f64_f32 = no_op
 
@@ -956,6 +1011,44 @@ do
 		end
 	end
 
+	-- Missing words read as zero, so whole words are cleared by removing them
+	-- and only the bytes at either end are stored one at a time
+	function store.zero(memory, addr, len)
+		local data = memory.data
+
+		if addr == 0 and len >= memory.min * 65536 then
+			table.clear(data)
+
+			return
+		end
+
+		local last = addr + len
+		local first_word = math_floor((addr + 3) / 4)
+		local last_word = math_floor(last / 4)
+
+		if first_word >= last_word then
+			for i = addr, last - 1 do
+				store_byte(data, i, 0)
+			end
+
+			return
+		end
+
+		for i = addr, first_word * 4 - 1 do
+			store_byte(data, i, 0)
+		end
+
+		for i = first_word, last_word - 1 do
+			data[i] = nil
+		end
+
+		for i = last_word * 4, last - 1 do
+			store_byte(data, i, 0)
+		end
+	end
+
+	-- Memory is a sparse table of 32-bit words where missing entries read as zero,
+	-- so growing only raises the page limit and never allocates or copies pages
 	function allocator.new(min, max)
 		return { min = min, max = max, data = {} }
 	end
@@ -978,5 +1071,203 @@ do
 	module.allocator = allocator
 end
 
+do
+	local table = {}
+
+	local raw_get = rawget
+	local raw_set = rawset
+
+	-- Indices are checked against `min` since tables are never grown
+	local function check_bounds(tab, index)
+		if index < 0 or index >= tab.min then
+			error("out of bounds table access", 3)
+		end
+	end
+
+	function table.get(tab, index)
+		check_bounds(tab, index)
+
+		return tab.data[index]
+	end
+
+	function table.set(tab, index, value)
+		check_bounds(tab, index)
+
+		tab.data[index] = value
+	end
+
+	function table.raw_get(tab, index)
+		check_bounds(tab, index)
+
+		return raw_get(tab.data, index)
+	end
+
+	function table.raw_set(tab, index, value)
+		check_bounds(tab, index)
+
+		raw_set(tab.data, index, value)
+	end
+
+	module.table = table
+end
+
+do
+	local guard = {}
+
+	local num_is_zero = Integer.is_zero
+
+	local NUM_ZERO = num_from_u32(0, 0)
+	local NUM_NEGATIVE_ONE = num_from_u32(0xFFFFFFFF, 0xFFFFFFFF)
+
+	local function constant(value)
+		return function()
+			return value
+		end
+	end
+
+	local function dividend(lhs)
+		return lhs
+	end
+
+	-- Divisors of zero never reach the operation, which would trap
+	local function guarded(operation, on_zero, is_zero)
+		return function(lhs, rhs)
+			if is_zero(rhs) then
+				return on_zero(lhs)
+			end
+
+			return operation(lhs, rhs)
+		end
+	end
+
+	local function is_zero_i32(num)
+		return num == 0
+	end
+
+	local operation_list = {
+		div_i32 = { module.div.i32, 0, 0xFFFFFFFF, is_zero_i32 },
+		div_u32 = { module.div.u32, 0, 0xFFFFFFFF, is_zero_i32 },
+		div_i64 = { module.div.i64, NUM_ZERO, NUM_NEGATIVE_ONE, num_is_zero },
+		div_u64 = { module.div.u64, NUM_ZERO, NUM_NEGATIVE_ONE, num_is_zero },
+		rem_i32 = { module.rem.i32, 0, nil, is_zero_i32 },
+		rem_u32 = {
+			function(lhs, rhs)
+				return lhs % rhs
+			end,
+			0,
+			nil,
+			is_zero_i32,
+		},
+		rem_i64 = { module.rem.i64, NUM_ZERO, nil, num_is_zero },
+		rem_u64 = { module.rem.u64, NUM_ZERO, nil, num_is_zero },
+	}
+
+	-- Wrapped results follow RISC-V, where a quotient by zero has all bits
+	-- set and a remainder by zero is the dividend
+	for name, data in pairs(operation_list) do
+		local operation, zero, wrapped, is_zero = data[1], data[2], data[3], data[4]
+		local on_wrapped = wrapped and constant(wrapped) or dividend
+
+		guard["zero_" .. name] = guarded(operation, constant(zero), is_zero)
+		guard["wrapped_" .. name] = guarded(operation, on_wrapped, is_zero)
+	end
+
+	module.guard = guard
+end
+
+do
+	local debug = {}
+
+	local I64_TYPE = typeof(num_from_u32(0, 0))
+
+	local function check_i32(num, name)
+		if type(num) ~= "number" or num ~= to_u32(num) then
+			error(name .. " is not an i32: " .. tostring(num), 3)
+		end
+	end
+
+	local function check_i64(num, name)
+		if typeof(num) ~= I64_TYPE then
+			error(name .. " is not an i64: " .. tostring(num), 3)
+		end
+	end
+
+	-- Operands and results are checked against their representation so a
+	-- miscompiled operation is reported where it happens
+	local function checked(operation, check)
+		return function(lhs, rhs)
+			check(lhs, "left operand")
+			check(rhs, "right operand")
+
+			local result = operation(lhs, rhs)
+
+			check(result, "result")
+
+			return result
+		end
+	end
+
+	debug.checked_add_i32 = checked(module.add.i32, check_i32)
+	debug.checked_sub_i32 = checked(module.sub.i32, check_i32)
+	debug.checked_mul_i32 = checked(module.mul.i32, check_i32)
+	debug.checked_add_i64 = checked(module.add.i64, check_i64)
+	debug.checked_sub_i64 = checked(module.sub.i64, check_i64)
+	debug.checked_mul_i64 = checked(module.mul.i64, check_i64)
+
+	module.debug = debug
+end
+
+do
+	local extern = {}
+
+	local select = select
+
+	-- Hosts replace `release` to free handles once `externref` locals stop
+	-- holding them. It does nothing by default
+	function extern.release(_) end
+
+	-- The values the other `externref` locals hold follow `new`, and are
+	-- kept if one of them is `old`
+	function extern.replace(old, new, ...)
+		if old == nil or old == new then
+			return new
+		end
+
+		for i = 1, select("#", ...) do
+			if select(i, ...) == old then
+				return new
+			end
+		end
+
+		extern.release(old)
+
+		return new
+	end
+
+	-- Each value held by the locals at a return is released once, unless the
+	-- function is also returning it
+	function extern.leave(list, ...)
+		local seen = {}
+
+		for i = 1, select("#", ...) do
+			local value = select(i, ...)
+
+			if value ~= nil and value == value then
+				seen[value] = true
+			end
+		end
+
+		for _, old in pairs(list) do
+			if not seen[old] then
+				seen[old] = true
+
+				extern.release(old)
+			end
+		end
+	end
+
+	module.extern = extern
+end
+
 return module
 
@@ -0,0 +1,77 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use wasm_ast::{
+	node::{BinOpType, Expression, FuncData, LoadAt, SetLocal, StoreAt, Value},
+	visit::{Driver, Visitor},
+};
+
+// Each shared address takes a register so only the most used are kept
+const MAX_ADDRESS_COUNT: usize = 8;
+
+/// Returns the parameter and constant of a `param + constant` address.
+pub fn as_address(pointer: &Expression) -> Option<(usize, i32)> {
+	let Expression::BinOp(op) = pointer else {
+		return None;
+	};
+
+	match (op.op_type(), op.lhs(), op.rhs()) {
+		(BinOpType::Add_I32, Expression::GetLocal(local), Expression::Value(Value::I32(k))) => {
+			Some((local.var(), *k))
+		}
+		_ => None,
+	}
+}
+
+struct Visit {
+	use_map: BTreeMap<(usize, i32), usize>,
+	set_list: BTreeSet<usize>,
+}
+
+impl Visit {
+	fn add_pointer(&mut self, pointer: &Expression) {
+		if let Some(address) = as_address(pointer) {
+			*self.use_map.entry(address).or_default() += 1;
+		}
+	}
+}
+
+impl Visitor for Visit {
+	fn visit_load_at(&mut self, load: &LoadAt) {
+		self.add_pointer(load.pointer());
+	}
+
+	fn visit_store_at(&mut self, store: &StoreAt) {
+		self.add_pointer(store.pointer());
+	}
+
+	fn visit_set_local(&mut self, set: &SetLocal) {
+		self.set_list.insert(set.var().var());
+	}
+}
+
+// Addresses built from parameters that are never assigned cannot change,
+// so those used more than once are computed a single time on entry
+pub fn visit(ast: &FuncData) -> Vec<(usize, i32)> {
+	let mut visit = Visit {
+		use_map: BTreeMap::new(),
+		set_list: BTreeSet::new(),
+	};
+
+	ast.accept(&mut visit);
+
+	let mut list: Vec<_> = visit
+		.use_map
+		.into_iter()
+		.filter(|&((var, _), uses)| {
+			uses > 1 && var < ast.num_param() && !visit.set_list.contains(&var)
+		})
+		.collect();
+
+	list.sort_by_key(|&(_, uses)| std::cmp::Reverse(uses));
+	list.truncate(MAX_ADDRESS_COUNT);
+
+	let mut list: Vec<_> = list.into_iter().map(|v| v.0).collect();
+
+	list.sort_unstable();
+	list
+}
@@ -19,6 +19,8 @@ impl AsSymbol for BinOpType {
 	}
 }
 
+// Lua compares NaN the same way WebAssembly does, so each float comparison
+// maps to its own operator and none may be rewritten as a negated other
 impl AsSymbol for CmpOpType {
 	fn as_symbol(&self) -> Option<&'static str> {
 		let result = match self {
@@ -1,3 +1,9 @@
+pub use wasm_ast::analyzer::{
+	assignment, bounds, call_indirect, coalesce, conditional_move, counting_loop, hoist, identity,
+	pure, repeat_loop,
+};
+
+pub mod address;
 pub mod as_symbol;
 pub mod br_target;
 pub mod localize;
@@ -1,11 +1,19 @@
 use std::{
-	collections::HashMap,
+	collections::{BTreeSet, HashMap},
 	io::{Result, Write},
 };
 
-use wasm_ast::node::{BrTable, FuncData, LabelType};
+use wasm_ast::{
+	coverage::{Branch, Outcome},
+	node::{BrTable, Expression, FuncData, LabelType, LoadAt},
+	stats::FunctionStats,
+};
+use wasmparser::{HeapType, ValType};
 
-use crate::analyzer::{br_target, localize};
+use crate::{
+	analyzer::{address, br_target, call_indirect, coalesce, hoist, localize},
+	options::{DivisionPolicy, Hook, Options},
+};
 
 #[macro_export]
 macro_rules! indentation {
@@ -32,6 +40,18 @@ macro_rules! line {
 	}};
 }
 
+fn get_extern_list(ast: &FuncData) -> Vec<usize> {
+	let is_extern =
+		|ty: &ValType| matches!(ty, ValType::Ref(v) if v.heap_type() == HeapType::Extern);
+
+	ast.param_data()
+		.iter()
+		.chain(ast.local_data())
+		.enumerate()
+		.filter_map(|(i, ty)| is_extern(ty).then_some(i))
+		.collect()
+}
+
 fn get_pinned_registers(
 	upvalues: usize,
 	params: usize,
@@ -50,61 +70,327 @@ fn get_pinned_registers(
 	(params + locals, temporaries)
 }
 
-pub struct Manager {
+pub struct Manager<'a> {
+	options: &'a Options,
+	index: Option<usize>,
+	stats: FunctionStats,
 	table_map: HashMap<usize, usize>,
+	address_list: Vec<(usize, i32)>,
+	hoist_map: HashMap<usize, usize>,
+	num_cache: usize,
+	num_cache_used: usize,
+	flat_table: Option<u32>,
+	memory64_set: BTreeSet<usize>,
+	flexible_result_map: HashMap<usize, Vec<ValType>>,
+	branch_list: Vec<Branch>,
+	branch_base: usize,
+	num_site: usize,
 	has_branch: bool,
+	register_map: Vec<usize>,
+	num_register: usize,
 	num_local: usize,
 	num_temp: usize,
+	extern_list: Vec<usize>,
 	label_list: Vec<Option<LabelType>>,
+	try_list: Vec<usize>,
 	indentation: usize,
 }
 
-impl Manager {
-	pub fn empty() -> Self {
+impl<'a> Manager<'a> {
+	pub fn empty(options: &'a Options) -> Self {
 		Self {
+			options,
+			index: None,
+			stats: FunctionStats::default(),
 			table_map: HashMap::new(),
+			address_list: Vec::new(),
+			hoist_map: HashMap::new(),
+			num_cache: 0,
+			num_cache_used: 0,
+			flat_table: None,
+			memory64_set: BTreeSet::new(),
+			flexible_result_map: HashMap::new(),
+			branch_list: Vec::new(),
+			branch_base: 0,
+			num_site: 0,
 			has_branch: false,
+			register_map: Vec::new(),
+			num_register: 0,
 			num_local: 0,
 			num_temp: usize::MAX,
+			extern_list: Vec::new(),
 			label_list: Vec::new(),
+			try_list: Vec::new(),
 			indentation: 0,
 		}
 	}
 
-	pub fn function(ast: &FuncData) -> Self {
+	pub fn function(ast: &FuncData, index: Option<usize>, options: &'a Options) -> Self {
 		let (upvalues, memories) = localize::visit(ast);
 		let (table_map, has_branch) = br_target::visit(ast);
+		let address_list = address::visit(ast);
+		let hoist_map = if options.hoist_loads {
+			hoist::visit(ast)
+		} else {
+			HashMap::new()
+		};
+		let num_cache = if index.is_some_and(|index| options.inline_cache_set.contains(&index)) {
+			call_indirect::visit(ast)
+		} else {
+			0
+		};
+		let (register_map, num_register) = if options.coalesce_registers {
+			coalesce::visit(ast)
+		} else {
+			(Vec::new(), ast.num_stack())
+		};
+		let extern_list = if options.release_externs {
+			get_extern_list(ast)
+		} else {
+			Vec::new()
+		};
 		let (num_local, num_temp) = get_pinned_registers(
-			upvalues.len() + memories.len(),
+			upvalues.len() + memories.len() + address_list.len() + hoist_map.len() + num_cache * 2,
 			ast.num_param(),
 			ast.local_data().len(),
-			ast.num_stack(),
+			num_register,
 		);
 
 		Self {
+			options,
+			index,
+			stats: FunctionStats {
+				index: index.unwrap_or_default(),
+				..FunctionStats::default()
+			},
 			table_map,
+			address_list,
+			hoist_map,
+			num_cache,
+			num_cache_used: 0,
+			flat_table: None,
+			memory64_set: BTreeSet::new(),
+			flexible_result_map: HashMap::new(),
+			branch_list: Vec::new(),
+			branch_base: 0,
+			num_site: 0,
 			has_branch,
+			register_map,
+			num_register,
 			num_local,
 			num_temp,
+			extern_list,
 			label_list: Vec::new(),
+			try_list: Vec::new(),
 			indentation: 0,
 		}
 	}
 
+	pub fn is_vararg(&self) -> bool {
+		self.index
+			.is_some_and(|index| self.options.vararg_set.contains(&index))
+	}
+
+	pub fn has_param_check(&self) -> bool {
+		self.index
+			.is_some_and(|index| self.options.assert_set.contains(&index))
+	}
+
+	pub fn has_param_coercion(&self) -> bool {
+		self.index
+			.is_some_and(|index| self.options.coerce_set.contains(&index))
+	}
+
+	pub fn has_packed_result(&self, function: usize) -> bool {
+		self.options.packed_result_set.contains(&function)
+	}
+
+	pub fn extern_list(&self) -> &[usize] {
+		&self.extern_list
+	}
+
+	pub fn is_extern(&self, var: usize) -> bool {
+		self.extern_list.binary_search(&var).is_ok()
+	}
+
+	pub const fn has_zero_temporaries(&self) -> bool {
+		self.options.zero_temporaries
+	}
+
+	pub const fn has_checked_arithmetic(&self) -> bool {
+		self.options.checked_arithmetic
+	}
+
+	pub const fn division_policy(&self) -> DivisionPolicy {
+		self.options.division_policy
+	}
+
+	pub const fn has_fold_identities(&self) -> bool {
+		self.options.fold_identities
+	}
+
+	pub const fn has_conditional_moves(&self) -> bool {
+		self.options.conditional_moves && self.options.coverage_table.is_none()
+	}
+
+	pub const fn has_counting_loops(&self) -> bool {
+		self.options.counting_loops && self.options.coverage_table.is_none()
+	}
+
+	pub const fn has_opcode_comments(&self) -> bool {
+		self.options.opcode_comments
+	}
+
+	pub const fn has_raw_access(&self) -> bool {
+		self.options.raw_access && !self.options.lazy_elements
+	}
+
+	pub fn depth_guard(&self) -> Option<(&'a str, usize)> {
+		let limit = self.options.depth_limit.filter(|_| self.index.is_some())?;
+
+		Some((self.options.depth_counter(), limit))
+	}
+
+	pub fn store_source(&self) -> &'a str {
+		self.options.store_source()
+	}
+
+	pub fn trap_function(&self) -> &'a str {
+		self.options.trap_function()
+	}
+
+	pub fn entry_hook(&self) -> Option<(&'a Hook, usize)> {
+		Some((self.options.entry_hook.as_ref()?, self.index?))
+	}
+
+	pub fn exit_hook(&self) -> Option<(&'a Hook, usize)> {
+		Some((self.options.exit_hook.as_ref()?, self.index?))
+	}
+
+	pub const fn stats(&self) -> FunctionStats {
+		self.stats
+	}
+
+	pub const fn stats_mut(&mut self) -> &mut FunctionStats {
+		&mut self.stats
+	}
+
 	pub fn get_table_index(&self, table: &BrTable) -> usize {
 		let id = table as *const _ as usize;
 
 		self.table_map[&id]
 	}
 
+	pub fn address_list(&self) -> &[(usize, i32)] {
+		&self.address_list
+	}
+
+	pub fn get_address(&self, pointer: &Expression) -> Option<usize> {
+		let address = address::as_address(pointer)?;
+
+		self.address_list.binary_search(&address).ok()
+	}
+
+	pub fn get_hoisted(&self, load: &LoadAt) -> Option<usize> {
+		let id = load as *const _ as usize;
+
+		self.hoist_map.get(&id).copied()
+	}
+
+	pub fn num_hoisted(&self) -> usize {
+		self.hoist_map.len()
+	}
+
+	pub const fn num_cache(&self) -> usize {
+		self.num_cache
+	}
+
+	pub fn push_cache(&mut self) -> Option<usize> {
+		if self.num_cache_used == self.num_cache {
+			return None;
+		}
+
+		self.num_cache_used += 1;
+
+		Some(self.num_cache_used - 1)
+	}
+
+	pub const fn flat_table(&self) -> Option<u32> {
+		self.flat_table
+	}
+
+	pub const fn set_flat_table(&mut self, min: Option<u32>) {
+		self.flat_table = min;
+	}
+
+	pub fn is_memory64(&self, memory: usize) -> bool {
+		self.memory64_set.contains(&memory)
+	}
+
+	pub fn set_memory64_set(&mut self, set: BTreeSet<usize>) {
+		self.memory64_set = set;
+	}
+
+	pub fn flexible_result(&self, function: usize) -> Option<&[ValType]> {
+		self.flexible_result_map.get(&function).map(Vec::as_slice)
+	}
+
+	pub fn set_flexible_result_map(&mut self, map: HashMap<usize, Vec<ValType>>) {
+		self.flexible_result_map = map;
+	}
+
+	pub fn coverage_table(&self) -> Option<&'a str> {
+		self.options.coverage_table.as_deref()
+	}
+
+	pub fn branch_list(&self) -> &[Branch] {
+		&self.branch_list
+	}
+
+	pub const fn set_branch_base(&mut self, base: usize) {
+		self.branch_base = base;
+	}
+
+	pub const fn push_site(&mut self) -> usize {
+		self.num_site += 1;
+		self.num_site - 1
+	}
+
+	// Counters are keyed by their place in the legend of the whole module
+	pub fn push_branch(&mut self, site: usize, outcome: Outcome) -> usize {
+		self.branch_list.push(Branch {
+			function: self.index.unwrap_or_default(),
+			site,
+			outcome,
+		});
+
+		self.branch_base + self.branch_list.len() - 1
+	}
+
 	pub fn has_table(&self) -> bool {
 		!self.table_map.is_empty()
 	}
 
+	pub fn num_table(&self) -> usize {
+		self.table_map.len()
+	}
+
+	pub fn is_presized(&self, len: usize) -> bool {
+		self.options.is_presized(len)
+	}
+
 	pub const fn has_branch(&self) -> bool {
 		self.has_branch
 	}
 
+	pub fn get_register(&self, var: usize) -> usize {
+		self.register_map.get(var).copied().unwrap_or(var)
+	}
+
+	pub const fn num_register(&self) -> usize {
+		self.num_register
+	}
+
 	pub const fn num_local(&self) -> usize {
 		self.num_local
 	}
@@ -125,6 +411,24 @@ impl Manager {
 		self.label_list.pop().unwrap();
 	}
 
+	pub fn push_try(&mut self) -> usize {
+		let level = self.label_list.len();
+
+		self.try_list.push(level);
+
+		level
+	}
+
+	pub fn pop_try(&mut self) {
+		self.try_list.pop().unwrap();
+	}
+
+	// Loops enclosing a `try` are past the closure its body runs in, so
+	// branches to them are only followed once `pcall` returns
+	pub fn is_try_body(&self) -> bool {
+		self.try_list.last() == Some(&self.label_list.len())
+	}
+
 	pub const fn indentation(&self) -> usize {
 		self.indentation
 	}
@@ -3,16 +3,26 @@ use std::{
 	ops::Range,
 };
 
-use wasm_ast::node::{
-	Block, Br, BrIf, BrTable, Call, CallIndirect, FuncData, If, LabelType, MemoryCopy, MemoryFill,
-	MemoryGrow, ResultList, SetGlobal, SetLocal, SetTemporary, Statement, StoreAt, Terminator,
+use wasm_ast::{
+	coverage::Outcome,
+	node::{
+		BinOpType, Block, Br, BrIf, BrTable, Call, CallIndirect, CmpOp, Expression, FuncData, If,
+		LabelType, MemoryCopy, MemoryFill, MemoryGrow, ResultList, SetGlobal, SetLocal,
+		SetTemporary, Statement, StoreAt, TableGet, TableSet, Terminator, Throw, Try, Value,
+	},
 };
 use wasmparser::ValType;
 
-use crate::{backend::manager::write_separated, indentation, indented, line};
+use crate::{
+	analyzer::{assignment, conditional_move, counting_loop, hoist, repeat_loop},
+	backend::manager::write_separated,
+	indentation, indented, line,
+};
 
 use super::{
-	expression::Condition,
+	expression::{
+		write_address, write_load_at, write_local, write_memory_operand, Condition, NotCondition,
+	},
 	manager::{Driver, Manager},
 };
 
@@ -24,6 +34,8 @@ impl Driver for ResultList {
 
 impl Driver for Br {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		mng.stats_mut().branches += 1;
+
 		if !self.align().is_aligned() {
 			indentation!(mng, w)?;
 			self.align().new_range().write(mng, w)?;
@@ -59,14 +71,35 @@ fn to_ordered_table(list: &[Br], default: Br) -> Vec<Br> {
 	data
 }
 
+fn write_coverage(
+	site: usize,
+	outcome: Outcome,
+	mng: &mut Manager,
+	w: &mut dyn Write,
+) -> Result<()> {
+	let Some(table) = mng.coverage_table() else {
+		return Ok(());
+	};
+
+	let id = mng.push_branch(site, outcome);
+
+	line!(mng, w, "{table}[{id}] = ({table}[{id}] or 0) + 1")
+}
+
+fn write_table_target(site: usize, br: Br, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	write_coverage(site, Outcome::Target(br.target()), mng, w)?;
+	br.write(mng, w)
+}
+
 fn write_search_layer(
 	range: Range<usize>,
 	list: &[Br],
+	site: usize,
 	mng: &mut Manager,
 	w: &mut dyn Write,
 ) -> Result<()> {
 	if range.len() == 1 {
-		return list[range.start].write(mng, w);
+		return write_table_target(site, list[range.start], mng, w);
 	}
 
 	let center = range.start + range.len() / 2;
@@ -75,7 +108,7 @@ fn write_search_layer(
 	if range.start != center {
 		line!(mng, w, "if temp < {} then", br.target())?;
 		mng.indent();
-		write_search_layer(range.start..center, list, mng, w)?;
+		write_search_layer(range.start..center, list, site, mng, w)?;
 		mng.dedent();
 		indented!(mng, w, "else")?;
 	}
@@ -83,14 +116,14 @@ fn write_search_layer(
 	if range.end != center + 1 {
 		writeln!(w, "if temp > {} then", br.target())?;
 		mng.indent();
-		write_search_layer(center + 1..range.end, list, mng, w)?;
+		write_search_layer(center + 1..range.end, list, site, mng, w)?;
 		mng.dedent();
 		indented!(mng, w, "else")?;
 	}
 
 	writeln!(w)?;
 	mng.indent();
-	br.write(mng, w)?;
+	write_table_target(site, br, mng, w)?;
 	mng.dedent();
 	line!(mng, w, "end")
 }
@@ -134,23 +167,70 @@ impl Driver for BrTable {
 		// and the appropriate jump is performed.
 		let list = to_ordered_table(self.data(), self.default());
 
+		let site = mng.push_site();
+
 		write_table_setup(self, mng, w)?;
-		write_search_layer(0..list.len(), &list, mng, w)
+		write_search_layer(0..list.len(), &list, site, mng, w)
+	}
+}
+
+impl Driver for Throw {
+	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		let tag = self.tag();
+
+		indented!(mng, w, "error({{ tag = TAG_LIST[{tag}]")?;
+
+		for value in self.value_list() {
+			write!(w, ", ")?;
+			value.write(mng, w)?;
+		}
+
+		writeln!(w, " }})")
 	}
 }
 
 impl Driver for Terminator {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		let name = match self {
+			Self::Unreachable => Some("unreachable"),
+			Self::Unsupported(_) => None,
+			Self::Br(_) => Some("br"),
+			Self::BrTable(_) => Some("br_table"),
+			Self::Throw(_) => Some("throw"),
+			Self::Rethrow(_) => Some("rethrow"),
+		};
+
+		write_opcode_comment(name, mng, w)?;
+
 		match self {
-			Self::Unreachable => line!(mng, w, r#"error("out of code bounds")"#),
+			Self::Unreachable => {
+				let trap = mng.trap_function();
+
+				line!(mng, w, r#"{trap}("out of code bounds")"#)
+			}
+			Self::Unsupported(s) => {
+				let trap = mng.trap_function();
+				let name = s.name();
+
+				line!(mng, w, r#"{trap}("unsupported: {name}")"#)
+			}
 			Self::Br(s) => s.write(mng, w),
 			Self::BrTable(s) => s.write(mng, w),
+			Self::Throw(s) => s.write(mng, w),
+			Self::Rethrow(s) => {
+				let level = mng.label_list().len() - 1 - s.target();
+
+				line!(mng, w, "error(exception_{level}, 0)")
+			}
 		}
 	}
 }
 
+// Luau has no `goto`, so every block is a loop that branches leave with
+// `break` or `continue`. Branches past several blocks set `desired` to the
+// target level and each enclosing loop checks it on the way out
 fn write_br_parent(mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
-	if !mng.has_branch() || mng.label_list().iter().all(Option::is_none) {
+	if !mng.has_branch() || mng.label_list().iter().all(Option::is_none) || mng.is_try_body() {
 		return Ok(());
 	}
 
@@ -178,14 +258,104 @@ fn write_br_parent(mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
 	line!(mng, w, "end")
 }
 
+fn write_repeat_loop(
+	block: &Block,
+	br_if: &BrIf,
+	mng: &mut Manager,
+	w: &mut dyn Write,
+) -> Result<()> {
+	let code = &block.code()[..block.code().len() - 1];
+
+	mng.push_label(block.label_type());
+	mng.stats_mut().branches += 1;
+
+	line!(mng, w, "repeat")?;
+	mng.indent();
+
+	write_statement_list(code, mng, w)?;
+
+	mng.dedent();
+	indented!(mng, w, "until ")?;
+	NotCondition(br_if.condition()).write(mng, w)?;
+	writeln!(w)?;
+
+	mng.pop_label();
+	write_br_parent(mng, w)
+}
+
+// The test is left out as the count of passes already stops the loop, and
+// nothing branches back to it so no label is needed
+fn write_counting_loop(
+	block: &Block,
+	next: &Expression,
+	condition: &CmpOp,
+	mng: &mut Manager,
+	w: &mut dyn Write,
+) -> Result<()> {
+	let code = &block.code()[..block.code().len() - 1];
+	let (head, tail) = condition.op_type().as_name();
+
+	mng.push_label(block.label_type());
+	mng.stats_mut().branches += 1;
+
+	indented!(mng, w, "for _ = 1, rt.count.{head}_{tail}(")?;
+	next.write(mng, w)?;
+	write!(w, ", ")?;
+	condition.rhs().write(mng, w)?;
+	writeln!(w, ") do")?;
+	mng.indent();
+
+	write_statement_list(code, mng, w)?;
+
+	mng.dedent();
+	line!(mng, w, "end")?;
+
+	mng.pop_label();
+	write_br_parent(mng, w)
+}
+
+// Hoisted loads are read just before the loop so that every pass through
+// it sees the value its first pass would have read
+fn write_hoisted_list(block: &Block, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	for load in hoist::hoisted_list(block) {
+		let Some(slot) = mng.get_hoisted(load) else {
+			continue;
+		};
+
+		indented!(mng, w, "hoist_{slot} = ")?;
+		write_load_at(load, mng, w)?;
+		writeln!(w)?;
+	}
+
+	Ok(())
+}
+
 impl Driver for Block {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		write_hoisted_list(self, mng, w)?;
+
+		let counter = if mng.has_counting_loops() {
+			counting_loop::visit(self)
+		} else {
+			None
+		};
+
+		if let Some((next, condition)) = counter {
+			return write_counting_loop(self, next, condition, mng, w);
+		}
+
+		let repeat = repeat_loop::visit(self).filter(|_| mng.coverage_table().is_none());
+
+		if let Some(br_if) = repeat {
+			return write_repeat_loop(self, br_if, mng, w);
+		}
+
 		mng.push_label(self.label_type());
 
 		line!(mng, w, "while true do")?;
 		mng.indent();
 
-		self.code().iter().try_for_each(|s| s.write(mng, w))?;
+		write_statement_list(self.code(), mng, w)?;
 
 		match self.last() {
 			Some(v) => v.write(mng, w)?,
@@ -202,62 +372,340 @@ impl Driver for Block {
 
 impl Driver for BrIf {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		let site = mng.push_site();
+
 		indented!(mng, w, "if ")?;
 		Condition(self.condition()).write(mng, w)?;
 		writeln!(w, " then")?;
 		mng.indent();
+		write_coverage(site, Outcome::Taken, mng, w)?;
 		self.target().write(mng, w)?;
 		mng.dedent();
-		line!(mng, w, "end")
+		line!(mng, w, "end")?;
+		write_coverage(site, Outcome::NotTaken, mng, w)
+	}
+}
+
+// An `else` holding only another `if` is written as `elseif`, with its
+// block still counted so that branch depths inside are unchanged. Branches
+// past a block rely on its loop to stop at each level, so those keep it
+fn as_else_if<'a>(block: &'a Block, mng: &Manager) -> Option<&'a If> {
+	if mng.has_branch() || block.label_type().is_some() || block.last().is_some() {
+		return None;
+	}
+
+	match block.code() {
+		[Statement::If(v)] => Some(v),
+		_ => None,
+	}
+}
+
+// Coverage counts which arm of each `if` ran, so chains are not joined into
+// `elseif` and a missing `else` is written to hold its counter
+fn write_if_chain(node: &If, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let site = mng.push_site();
+
+	Condition(node.condition()).write(mng, w)?;
+	writeln!(w, " then")?;
+
+	mng.indent();
+	write_coverage(site, Outcome::Then, mng, w)?;
+	node.on_true().write(mng, w)?;
+	mng.dedent();
+
+	let Some(on_false) = node.on_false() else {
+		if mng.coverage_table().is_some() {
+			line!(mng, w, "else")?;
+			mng.indent();
+			write_coverage(site, Outcome::Else, mng, w)?;
+			mng.dedent();
+		}
+
+		return Ok(());
+	};
+
+	let nested = as_else_if(on_false, mng).filter(|_| mng.coverage_table().is_none());
+
+	if let Some(nested) = nested {
+		mng.push_label(None);
+		indented!(mng, w, "elseif ")?;
+		write_if_chain(nested, mng, w)?;
+		mng.pop_label();
+	} else {
+		line!(mng, w, "else")?;
+		mng.indent();
+		write_coverage(site, Outcome::Else, mng, w)?;
+		on_false.write(mng, w)?;
+		mng.dedent();
 	}
+
+	Ok(())
+}
+
+fn write_conditional_move(
+	node: &If,
+	set: &SetLocal,
+	mng: &mut Manager,
+	w: &mut dyn Write,
+) -> Result<()> {
+	indentation!(mng, w)?;
+	set.var().write(mng, w)?;
+	write!(w, " = if ")?;
+	Condition(node.condition()).write(mng, w)?;
+	write!(w, " then ")?;
+	write_local_value(set, mng, w)?;
+	write!(w, " else ")?;
+	set.var().write(mng, w)?;
+	writeln!(w)
 }
 
 impl Driver for If {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		let set = conditional_move::visit(self).filter(|_| mng.has_conditional_moves());
+
+		if let Some(set) = set {
+			return write_conditional_move(self, set, mng, w);
+		}
+
 		indented!(mng, w, "if ")?;
-		Condition(self.condition()).write(mng, w)?;
-		writeln!(w, " then")?;
+		write_if_chain(self, mng, w)?;
+		line!(mng, w, "end")
+	}
+}
 
+// Exceptions are tables holding their tag and values. Anything else raised,
+// such as a trap, passes through every handler
+fn write_catch_list(node: &Try, level: usize, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let exception = format!("exception_{level}");
+	let mut keyword = "if";
+
+	for catch in node.catch_list() {
+		let tag = catch.tag();
+		let num_value = catch.result_list().iter().count();
+
+		indented!(mng, w, "{keyword} type({exception}) == ")?;
+		writeln!(w, r#""table" and {exception}.tag == TAG_LIST[{tag}] then"#)?;
 		mng.indent();
-		self.on_true().write(mng, w)?;
+
+		if num_value != 0 {
+			indentation!(mng, w)?;
+			catch.result_list().write(mng, w)?;
+			write!(w, " = ")?;
+			write_separated(1..=num_value, |i, w| write!(w, "{exception}[{i}]"), w)?;
+			writeln!(w)?;
+		}
+
+		catch.block().write(mng, w)?;
 		mng.dedent();
 
-		if let Some(v) = self.on_false() {
-			line!(mng, w, "else")?;
-			mng.indent();
-			v.write(mng, w)?;
-			mng.dedent();
+		keyword = "elseif";
+	}
+
+	if let Some(block) = node.catch_all() {
+		indented!(mng, w, "{keyword} type({exception}) == ")?;
+		writeln!(w, r#""table" and {exception}.tag ~= nil then"#)?;
+		mng.indent();
+		block.write(mng, w)?;
+		mng.dedent();
+
+		keyword = "elseif";
+	}
+
+	if keyword == "if" {
+		return line!(mng, w, "error({exception}, 0)");
+	}
+
+	line!(mng, w, "else")?;
+	mng.indent();
+	line!(mng, w, "error({exception}, 0)")?;
+	mng.dedent();
+	line!(mng, w, "end")
+}
+
+// The body runs in a closure given to `pcall`. Branches out of it set
+// `desired` and leave its loop, which is then checked once `pcall` returns
+impl Driver for Try {
+	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		let level = mng.push_try();
+
+		line!(mng, w, "do")?;
+		mng.indent();
+
+		// Callees that throw never reach their decrement, so the depth is put
+		// back to what it was before anything is caught
+		if let Some((counter, _)) = mng.depth_guard() {
+			line!(mng, w, "local depth_{level} = {counter}")?;
 		}
 
-		line!(mng, w, "end")
+		line!(
+			mng,
+			w,
+			"local ok_{level}, exception_{level} = pcall(function()"
+		)?;
+		mng.indent();
+		self.body().write(mng, w)?;
+		mng.dedent();
+		line!(mng, w, "end)")?;
+
+		mng.pop_try();
+
+		line!(mng, w, "if not ok_{level} then")?;
+		mng.indent();
+
+		if let Some((counter, _)) = mng.depth_guard() {
+			line!(mng, w, "{counter} = depth_{level}")?;
+		}
+
+		write_catch_list(self, level, mng, w)?;
+		mng.dedent();
+		line!(mng, w, "end")?;
+		mng.dedent();
+		line!(mng, w, "end")?;
+
+		write_br_parent(mng, w)
 	}
 }
 
 impl Driver for Call {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		mng.stats_mut().calls += 1;
+
+		let function = self.function();
+
 		if !self.result_list().is_empty() {
 			self.result_list().write(mng, w)?;
 			write!(w, " = ")?;
 		}
 
-		write!(w, "FUNC_LIST[{}](", self.function())?;
-		self.param_list().write(mng, w)?;
-		write!(w, ")")
+		if mng.has_packed_result(function) && !self.result_list().is_empty() {
+			write!(w, "table.unpack(FUNC_LIST[{function}](")?;
+			self.param_list().write(mng, w)?;
+			write!(w, "))")
+		} else {
+			write!(w, "FUNC_LIST[{function}](")?;
+			self.param_list().write(mng, w)?;
+			write!(w, ")")
+		}
+	}
+}
+
+// Results the host left out are `nil`, which would only fail once used
+fn write_result_defaults(call: &Call, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let Some(list) = mng.flexible_result(call.function()).map(<[_]>::to_vec) else {
+		return Ok(());
+	};
+
+	for (temporary, ty) in call.result_list().iter().zip(list) {
+		let zero = match ty {
+			ValType::I32 => Value::I32(0),
+			ValType::I64 => Value::I64(0),
+			ValType::F32 => Value::F32(0.0),
+			ValType::F64 => Value::F64(0.0),
+			ValType::V128 | ValType::Ref(_) => continue,
+		};
+
+		indentation!(mng, w)?;
+		temporary.write(mng, w)?;
+		write!(w, " = ")?;
+		temporary.write(mng, w)?;
+		write!(w, " or ")?;
+		zero.write(mng, w)?;
+		writeln!(w)?;
+	}
+
+	Ok(())
+}
+
+fn write_element(call: &CallIndirect, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let (list, offset) = if mng.flat_table().is_some() {
+		("TABLE".to_string(), " + 1")
+	} else {
+		(format!("TABLE_LIST[{}].data", call.table()), "")
+	};
+
+	if mng.has_raw_access() {
+		writeln!(w, "local call_func = rawget({list}, call_index{offset})")
+	} else {
+		writeln!(w, "local call_func = {list}[call_index{offset}]")
 	}
 }
 
+// Calling a missing element would otherwise fail with an unhelpful
+// "attempt to call a nil value" rather than the trap the spec expects
+fn write_element_check(call: &CallIndirect, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let min = mng.flat_table().map_or_else(
+		|| format!("TABLE_LIST[{}].min", call.table()),
+		|min| min.to_string(),
+	);
+
+	indentation!(mng, w)?;
+	write_element(call, mng, w)?;
+	line!(
+		mng,
+		w,
+		r#"if not call_func then {}(call_index >= 0 and call_index < {min} and "uninitialized element" or "undefined element") end"#,
+		mng.trap_function()
+	)
+}
+
+// The element is only looked up and checked when the index differs from the
+// last one called here
+fn write_cached_element(
+	call: &CallIndirect,
+	id: usize,
+	mng: &mut Manager,
+	w: &mut dyn Write,
+) -> Result<()> {
+	line!(mng, w, "if cache_index_{id} ~= call_index then")?;
+	mng.indent();
+	write_element_check(call, mng, w)?;
+	line!(
+		mng,
+		w,
+		"cache_index_{id}, cache_func_{id} = call_index, call_func"
+	)?;
+	mng.dedent();
+	line!(mng, w, "end")
+}
+
+// The index and element are bound once so neither is evaluated again by the
+// check and the call
 impl Driver for CallIndirect {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		mng.stats_mut().calls += 1;
+
+		let cache = mng.push_cache();
+
+		writeln!(w, "do")?;
+		mng.indent();
+		indented!(mng, w, "local call_index = ")?;
+		self.index().write(mng, w)?;
+		writeln!(w)?;
+
+		if let Some(id) = cache {
+			write_cached_element(self, id, mng, w)?;
+		} else {
+			write_element_check(self, mng, w)?;
+		}
+
+		indentation!(mng, w)?;
+
 		if !self.result_list().is_empty() {
 			self.result_list().write(mng, w)?;
 			write!(w, " = ")?;
 		}
 
-		write!(w, "TABLE_LIST[{}].data[", self.table())?;
-		self.index().write(mng, w)?;
-		write!(w, "](")?;
+		if let Some(id) = cache {
+			write!(w, "cache_func_{id}")?;
+		} else {
+			write!(w, "call_func")?;
+		}
+
+		write!(w, "(")?;
 		self.param_list().write(mng, w)?;
-		write!(w, ")")
+		writeln!(w, ")")?;
+		mng.dedent();
+		indented!(mng, w, "end")
 	}
 }
 
@@ -273,10 +721,69 @@ impl Driver for SetLocal {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
 		self.var().write(mng, w)?;
 		write!(w, " = ")?;
-		self.value().write(mng, w)
+		write_local_value(self, mng, w)
 	}
 }
 
+// The value an `externref` local held is only released once the value that
+// replaces it is ready, as computing that may still read it. The other
+// `externref` locals are passed along so a value one of them holds is kept
+fn write_local_value(set: &SetLocal, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let var = set.var().var();
+
+	if !mng.is_extern(var) {
+		return set.value().write(mng, w);
+	}
+
+	write!(w, "rt.extern.replace(")?;
+	set.var().write(mng, w)?;
+	write!(w, ", ")?;
+	set.value().write(mng, w)?;
+
+	for other in mng.extern_list().to_vec() {
+		if other != var {
+			write!(w, ", ")?;
+			write_local(other, mng, w)?;
+		}
+	}
+
+	write!(w, ")")
+}
+
+// Assigning several `externref` locals at once would check each against the
+// values the others held before, so those are written in order instead
+fn has_extern_locals(list: &[Statement], mng: &Manager) -> bool {
+	let count = list
+		.iter()
+		.filter(|stat| matches!(stat, Statement::SetLocal(s) if mng.is_extern(s.var().var())))
+		.count();
+
+	count > 1
+}
+
+// Values still being returned are kept, so they are passed along too
+fn write_extern_leave(ast: &FuncData, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	if mng.extern_list().is_empty() {
+		return Ok(());
+	}
+
+	indented!(mng, w, "rt.extern.leave({{ ")?;
+
+	for var in mng.extern_list().to_vec() {
+		write_local(var, mng, w)?;
+		write!(w, ", ")?;
+	}
+
+	write!(w, "}}")?;
+
+	for temporary in ResultList::new(0, ast.num_result()).iter() {
+		write!(w, ", ")?;
+		temporary.write(mng, w)?;
+	}
+
+	writeln!(w, ")")
+}
+
 impl Driver for SetGlobal {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
 		write!(w, "GLOBAL_LIST[{}].value = ", self.var())?;
@@ -286,17 +793,13 @@ impl Driver for SetGlobal {
 
 impl Driver for StoreAt {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		mng.stats_mut().stores += 1;
+
 		let name = self.store_type().as_name();
 		let memory = self.memory();
 
 		write!(w, "store_{name}(memory_at_{memory}, ")?;
-
-		self.pointer().write(mng, w)?;
-
-		if self.offset() != 0 {
-			write!(w, " + {}", self.offset())?;
-		}
-
+		write_address(memory, self.pointer(), self.offset(), mng, w)?;
 		write!(w, ", ")?;
 		self.value().write(mng, w)?;
 		write!(w, ")")
@@ -308,9 +811,19 @@ impl Driver for MemoryGrow {
 		let memory = self.memory();
 
 		self.result().write(mng, w)?;
-		write!(w, " = rt.allocator.grow(memory_at_{memory}, ")?;
-		self.size().write(mng, w)?;
-		write!(w, ")")
+
+		if mng.is_memory64(memory) {
+			write!(
+				w,
+				" = rt.extend.i64_i32(rt.allocator.grow(memory_at_{memory}, "
+			)?;
+			write_memory_operand(self.size(), true, mng, w)?;
+			write!(w, "))")
+		} else {
+			write!(w, " = rt.allocator.grow(memory_at_{memory}, ")?;
+			self.size().write(mng, w)?;
+			write!(w, ")")
+		}
 	}
 }
 
@@ -319,12 +832,16 @@ impl Driver for MemoryCopy {
 		let memory_1 = self.destination().memory();
 		let memory_2 = self.source().memory();
 
-		write!(w, "rt.store.copy(memory_at_{memory_1}, ")?;
-		self.destination().pointer().write(mng, w)?;
+		let is_memory64_1 = mng.is_memory64(memory_1);
+		let is_memory64_2 = mng.is_memory64(memory_2);
+		let source = mng.store_source();
+
+		write!(w, "{source}.copy(memory_at_{memory_1}, ")?;
+		write_memory_operand(self.destination().pointer(), is_memory64_1, mng, w)?;
 		write!(w, ", memory_at_{memory_2}, ")?;
-		self.source().pointer().write(mng, w)?;
+		write_memory_operand(self.source().pointer(), is_memory64_2, mng, w)?;
 		write!(w, ", ")?;
-		self.size().write(mng, w)?;
+		write_memory_operand(self.size(), is_memory64_1 && is_memory64_2, mng, w)?;
 		write!(w, ")")
 	}
 }
@@ -333,29 +850,231 @@ impl Driver for MemoryFill {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
 		let memory = self.destination().memory();
 
-		write!(w, "rt.store.fill(memory_at_{memory}, ")?;
-		self.destination().pointer().write(mng, w)?;
-		write!(w, ", ")?;
-		self.size().write(mng, w)?;
+		let is_memory64 = mng.is_memory64(memory);
+		let source = mng.store_source();
+
+		// Only the low byte is stored, so any constant ending in a zero byte
+		// clears the range
+		let is_zero =
+			matches!(self.value(), Expression::Value(Value::I32(v)) if v.trailing_zeros() >= 8);
+		let name = if is_zero { "zero" } else { "fill" };
+
+		write!(w, "{source}.{name}(memory_at_{memory}, ")?;
+		write_memory_operand(self.destination().pointer(), is_memory64, mng, w)?;
 		write!(w, ", ")?;
-		self.value().write(mng, w)?;
+		write_memory_operand(self.size(), is_memory64, mng, w)?;
+
+		if !is_zero {
+			write!(w, ", ")?;
+			self.value().write(mng, w)?;
+		}
+
 		write!(w, ")")
 	}
 }
 
+// Flat tables never change size, so their bounds are checked inline with
+// the size known when translating. The index is bound once and the access
+// that follows reads it back through `table_index`
+fn write_table_check(index: &Expression, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let Some(min) = mng.flat_table() else {
+		return Ok(());
+	};
+
+	writeln!(w, "do")?;
+	mng.indent();
+	indented!(mng, w, "local table_index = ")?;
+	index.write(mng, w)?;
+	writeln!(w)?;
+	line!(
+		mng,
+		w,
+		r#"if table_index < 0 or table_index >= {min} then {}("out of bounds table access") end"#,
+		mng.trap_function()
+	)?;
+
+	indentation!(mng, w)
+}
+
+fn write_table_check_end(mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	if mng.flat_table().is_none() {
+		return Ok(());
+	}
+
+	writeln!(w)?;
+	mng.dedent();
+	indented!(mng, w, "end")
+}
+
+impl Driver for TableGet {
+	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		write_table_check(self.index(), mng, w)?;
+
+		self.result().write(mng, w)?;
+
+		match (mng.flat_table(), mng.has_raw_access()) {
+			(Some(_), false) => write!(w, " = TABLE[table_index + 1]")?,
+			(Some(_), true) => write!(w, " = rawget(TABLE, table_index + 1)")?,
+			(None, is_raw) => {
+				let name = if is_raw { "raw_get" } else { "get" };
+
+				write!(w, " = rt.table.{name}(TABLE_LIST[{}], ", self.table())?;
+				self.index().write(mng, w)?;
+				write!(w, ")")?;
+			}
+		}
+
+		write_table_check_end(mng, w)
+	}
+}
+
+impl Driver for TableSet {
+	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		write_table_check(self.index(), mng, w)?;
+
+		match (mng.flat_table(), mng.has_raw_access()) {
+			(Some(_), false) => {
+				write!(w, "TABLE[table_index + 1] = ")?;
+				self.value().write(mng, w)?;
+			}
+			(Some(_), true) => {
+				write!(w, "rawset(TABLE, table_index + 1, ")?;
+				self.value().write(mng, w)?;
+				write!(w, ")")?;
+			}
+			(None, is_raw) => {
+				let name = if is_raw { "raw_set" } else { "set" };
+
+				write!(w, "rt.table.{name}(TABLE_LIST[{}], ", self.table())?;
+				self.index().write(mng, w)?;
+				write!(w, ", ")?;
+				self.value().write(mng, w)?;
+				write!(w, ")")?;
+			}
+		}
+
+		write_table_check_end(mng, w)
+	}
+}
+
+const fn get_expression_opcode(value: &Expression) -> Option<&'static str> {
+	let name = match value {
+		Expression::Select(_) => "select",
+		Expression::GetTemporary(_) => return None,
+		Expression::GetLocal(_) => "local.get",
+		Expression::GetGlobal(_) => "global.get",
+		Expression::LoadAt(s) => s.load_type().as_opcode(),
+		Expression::MemorySize(_) => "memory.size",
+		Expression::Value(Value::I32(_)) => "i32.const",
+		Expression::Value(Value::I64(_)) => "i64.const",
+		Expression::Value(Value::F32(_)) => "f32.const",
+		Expression::Value(Value::F64(_)) => "f64.const",
+		Expression::UnOp(s) => s.op_type().as_opcode(),
+		Expression::BinOp(s) => s.op_type().as_opcode(),
+		Expression::CmpOp(s) => s.op_type().as_opcode(),
+	};
+
+	Some(name)
+}
+
+// Moves between temporaries and blocks without a label have no instruction
+// of their own, so they are left uncommented
+fn get_statement_opcode(stat: &Statement) -> Option<&'static str> {
+	let name = match stat {
+		Statement::Block(s) => match s.label_type() {
+			Some(LabelType::Forward) => "block",
+			Some(LabelType::Backward) => "loop",
+			None => return None,
+		},
+		Statement::BrIf(_) => "br_if",
+		Statement::If(_) => "if",
+		Statement::Try(_) => "try",
+		Statement::Call(_) => "call",
+		Statement::CallIndirect(_) => "call_indirect",
+		Statement::SetTemporary(s) => return get_expression_opcode(s.value()),
+		Statement::SetLocal(_) => "local.set",
+		Statement::SetGlobal(_) => "global.set",
+		Statement::StoreAt(s) => s.store_type().as_opcode(),
+		Statement::MemoryGrow(_) => "memory.grow",
+		Statement::MemoryCopy(_) => "memory.copy",
+		Statement::MemoryFill(_) => "memory.fill",
+		Statement::TableGet(_) => "table.get",
+		Statement::TableSet(_) => "table.set",
+	};
+
+	Some(name)
+}
+
+fn write_opcode_comment(name: Option<&str>, mng: &Manager, w: &mut dyn Write) -> Result<()> {
+	match name {
+		Some(name) if mng.has_opcode_comments() => line!(mng, w, "-- {name}"),
+		_ => Ok(()),
+	}
+}
+
 fn write_stat(stat: &dyn Driver, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
 	indentation!(mng, w)?;
 	stat.write(mng, w)?;
 	writeln!(w)
 }
 
+fn write_assignment_list(list: &[Statement], mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	for stat in list {
+		write_opcode_comment(get_statement_opcode(stat), mng, w)?;
+	}
+
+	indentation!(mng, w)?;
+	write_separated(
+		list.iter(),
+		|v, w| match v {
+			Statement::SetTemporary(s) => s.var().write(mng, w),
+			Statement::SetLocal(s) => s.var().write(mng, w),
+			_ => unreachable!("statement is not an assignment"),
+		},
+		w,
+	)?;
+	write!(w, " = ")?;
+	write_separated(
+		list.iter(),
+		|v, w| match v {
+			Statement::SetTemporary(s) => s.value().write(mng, w),
+			Statement::SetLocal(s) => write_local_value(s, mng, w),
+			_ => unreachable!("statement is not an assignment"),
+		},
+		w,
+	)?;
+	writeln!(w)
+}
+
+fn write_statement_list(code: &[Statement], mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	for list in assignment::visit(code, &|var| mng.get_register(var)) {
+		if let [stat] = list {
+			stat.write(mng, w)?;
+		} else if has_extern_locals(list, mng) {
+			for stat in list {
+				stat.write(mng, w)?;
+			}
+		} else {
+			write_assignment_list(list, mng, w)?;
+		}
+	}
+
+	Ok(())
+}
+
 impl Driver for Statement {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+		write_opcode_comment(get_statement_opcode(self), mng, w)?;
+
 		match self {
 			Self::Block(s) => s.write(mng, w),
 			Self::BrIf(s) => s.write(mng, w),
 			Self::If(s) => s.write(mng, w),
-			Self::Call(s) => write_stat(s, mng, w),
+			Self::Try(s) => s.write(mng, w),
+			Self::Call(s) => {
+				write_stat(s, mng, w)?;
+				write_result_defaults(s, mng, w)
+			}
 			Self::CallIndirect(s) => write_stat(s, mng, w),
 			Self::SetTemporary(s) => write_stat(s, mng, w),
 			Self::SetLocal(s) => write_stat(s, mng, w),
@@ -364,11 +1083,25 @@ impl Driver for Statement {
 			Self::MemoryGrow(s) => write_stat(s, mng, w),
 			Self::MemoryCopy(s) => write_stat(s, mng, w),
 			Self::MemoryFill(s) => write_stat(s, mng, w),
+			Self::TableGet(s) => write_stat(s, mng, w),
+			Self::TableSet(s) => write_stat(s, mng, w),
 		}
 	}
 }
 
-fn write_parameter_list(ast: &FuncData, w: &mut dyn Write) -> Result<()> {
+fn write_parameter_list(ast: &FuncData, mng: &Manager, w: &mut dyn Write) -> Result<()> {
+	if mng.is_vararg() {
+		writeln!(w, "function(...)")?;
+
+		if ast.num_param() != 0 {
+			indented!(mng, w, "local ")?;
+			write_separated(0..ast.num_param(), |i, w| write!(w, "loc_{i}"), w)?;
+			writeln!(w, " = ...")?;
+		}
+
+		return Ok(());
+	}
+
 	write!(w, "function(")?;
 	write_separated(0..ast.num_param(), |i, w| write!(w, "loc_{i}"), w)?;
 	writeln!(w, ")")
@@ -377,11 +1110,85 @@ fn write_parameter_list(ast: &FuncData, w: &mut dyn Write) -> Result<()> {
 const fn type_to_zero(typ: ValType) -> &'static str {
 	match typ {
 		ValType::F32 | ValType::F64 => "0.0",
+		ValType::Ref(_) => "nil",
 		ValType::I64 => "i64_ZERO",
 		_ => "0",
 	}
 }
 
+// Coercion runs before any check so that checks see the coerced values
+fn write_parameter_coercion(ast: &FuncData, mng: &Manager, w: &mut dyn Write) -> Result<()> {
+	if !mng.has_param_coercion() {
+		return Ok(());
+	}
+
+	for (i, typ) in ast.param_data().iter().enumerate() {
+		let name = match typ {
+			ValType::I32 | ValType::F32 | ValType::F64 => {
+				line!(mng, w, "loc_{i} = tonumber(loc_{i}) or 0")?;
+
+				continue;
+			}
+			ValType::I64 => {
+				if cfg!(feature = "vector") {
+					"vector"
+				} else {
+					"table"
+				}
+			}
+			_ => continue,
+		};
+
+		line!(
+			mng,
+			w,
+			r#"if type(loc_{i}) ~= "{name}" then loc_{i} = rt.truncate.i64_f64(tonumber(loc_{i}) or 0) end"#
+		)?;
+	}
+
+	Ok(())
+}
+
+fn write_parameter_check(ast: &FuncData, mng: &Manager, w: &mut dyn Write) -> Result<()> {
+	if !mng.has_param_check() {
+		return Ok(());
+	}
+
+	for (i, typ) in ast.param_data().iter().enumerate() {
+		let name = match typ {
+			ValType::I32 | ValType::F32 | ValType::F64 => "number",
+			ValType::I64 => {
+				if cfg!(feature = "vector") {
+					"vector"
+				} else {
+					"table"
+				}
+			}
+			_ => continue,
+		};
+
+		line!(
+			mng,
+			w,
+			r#"assert(type(loc_{i}) == "{name}", "parameter {i} must be a {name}")"#
+		)?;
+	}
+
+	Ok(())
+}
+
+fn write_address_list(mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
+	let (head, tail) = BinOpType::Add_I32.as_name();
+
+	for (i, &(var, k)) in mng.address_list().to_vec().iter().enumerate() {
+		indented!(mng, w, "local addr_{i} = {head}_{tail}(loc_{var}, ")?;
+		Value::I32(k).write(mng, w)?;
+		writeln!(w, ")")?;
+	}
+
+	Ok(())
+}
+
 fn write_variable_list(ast: &FuncData, mng: &Manager, w: &mut dyn Write) -> Result<()> {
 	let mut locals = ast.local_data().iter().copied();
 	let num_local = mng.num_local() - ast.num_param();
@@ -405,16 +1212,24 @@ fn write_variable_list(ast: &FuncData, mng: &Manager, w: &mut dyn Write) -> Resu
 		writeln!(w, "}}")?;
 	}
 
-	let mut temporaries = 0..ast.num_stack();
+	let mut temporaries = 0..mng.num_register();
 
 	for i in temporaries.by_ref().take(mng.num_temp()) {
-		line!(mng, w, "local reg_{i}")?;
+		if mng.has_zero_temporaries() {
+			line!(mng, w, "local reg_{i} = 0")?;
+		} else {
+			line!(mng, w, "local reg_{i}")?;
+		}
 	}
 
 	if !temporaries.is_empty() {
 		let len = temporaries.len();
 
 		line!(mng, w, "local reg_spill = table.create({len})")?;
+
+		if mng.has_zero_temporaries() {
+			line!(mng, w, "for i = 1, {len} do reg_spill[i] = 0 end")?;
+		}
 	}
 
 	Ok(())
@@ -424,19 +1239,59 @@ impl Driver for FuncData {
 	fn write(&self, mng: &mut Manager, w: &mut dyn Write) -> Result<()> {
 		mng.indent();
 
-		write_parameter_list(self, w)?;
+		write_parameter_list(self, mng, w)?;
+		write_parameter_coercion(self, mng, w)?;
+		write_parameter_check(self, mng, w)?;
 		write_variable_list(self, mng, w)?;
+		write_address_list(mng, w)?;
+
+		for i in 0..mng.num_hoisted() {
+			line!(mng, w, "local hoist_{i}")?;
+		}
+
+		for i in 0..mng.num_cache() {
+			line!(mng, w, "local cache_index_{i}, cache_func_{i}")?;
+		}
 
 		if mng.has_branch() {
 			line!(mng, w, "local desired")?;
 		}
 
 		if mng.has_table() {
-			line!(mng, w, "local br_map = {{}}")?;
+			if mng.is_presized(mng.num_table()) {
+				line!(mng, w, "local br_map = table.create({})", mng.num_table())?;
+			} else {
+				line!(mng, w, "local br_map = {{}}")?;
+			}
+		}
+
+		if let Some((counter, limit)) = mng.depth_guard() {
+			let trap = mng.trap_function();
+
+			line!(mng, w, "if {counter} >= {limit} then")?;
+			mng.indent();
+			line!(mng, w, r#"{trap}("call stack exhausted")"#)?;
+			mng.dedent();
+			line!(mng, w, "end")?;
+			line!(mng, w, "{counter} = {counter} + 1")?;
+		}
+
+		if let Some((hook, index)) = mng.entry_hook() {
+			hook(index, w)?;
 		}
 
 		self.code().write(mng, w)?;
 
+		if let Some((hook, index)) = mng.exit_hook() {
+			hook(index, w)?;
+		}
+
+		if let Some((counter, _)) = mng.depth_guard() {
+			line!(mng, w, "{counter} = {counter} - 1")?;
+		}
+
+		write_extern_leave(self, mng, w)?;
+
 		if self.num_result() != 0 {
 			indented!(mng, w, "return ")?;
 
@@ -1,21 +1,26 @@
 use std::{
-	collections::BTreeSet,
-	io::{Result, Write},
+	collections::{BTreeSet, HashMap},
+	io::{BufWriter, Error, ErrorKind, Result, Write},
 };
 
 use wasm_ast::{
+	coverage::Branch,
 	factory::Factory,
+	fingerprint::Fingerprint,
 	module::{External, Module, TypeInfo},
 	node::{FuncData, Statement},
+	size_limit::SizeLimit,
+	stats::FunctionStats,
 };
 use wasmparser::{
 	ConstExpr, Data, DataKind, Element, ElementItems, ElementKind, Export, Import, Operator,
-	OperatorsReader, ValType,
+	OperatorsReader, Type, TypeRef, ValType,
 };
 
 use crate::{
-	analyzer::localize,
+	analyzer::{bounds, localize, pure},
 	backend::manager::{Driver, Manager},
+	options::Options,
 };
 
 trait AsIEName {
@@ -29,7 +34,7 @@ impl AsIEName for External {
 			Self::Table => "table_list",
 			Self::Memory => "memory_list",
 			Self::Global => "global_list",
-			Self::Tag => unimplemented!(),
+			Self::Tag => "tag_list",
 		}
 	}
 }
@@ -41,17 +46,34 @@ fn reader_to_code(reader: OperatorsReader) -> Vec<Operator> {
 }
 
 fn write_named_array(name: &str, len: usize, w: &mut dyn Write) -> Result<()> {
-	let Some(len) = len.checked_sub(1) else { return Ok(()) };
+	let Some(len) = len.checked_sub(1) else {
+		return Ok(());
+	};
 
 	writeln!(w, "local {name} = table.create({len})")
 }
 
-fn write_constant(init: &ConstExpr, type_info: &TypeInfo, w: &mut dyn Write) -> Result<()> {
+fn write_constant(
+	init: &ConstExpr,
+	type_info: &TypeInfo,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<()> {
 	let code = reader_to_code(init.get_operators_reader());
+
+	// References are not values in the syntax tree so they are lowered here
+	match code.as_slice() {
+		[Operator::RefFunc { function_index }, Operator::End] => {
+			return write!(w, "FUNC_LIST[{function_index}]");
+		}
+		[Operator::RefNull { .. }, Operator::End] => return write!(w, "nil"),
+		_ => {}
+	}
+
 	let func = Factory::from_type_info(type_info).create_anonymous(&code);
 
 	if let Some(Statement::SetTemporary(stat)) = func.code().code().last() {
-		stat.value().write(&mut Manager::empty(), w)
+		stat.value().write(&mut Manager::empty(options), w)
 	} else {
 		writeln!(w, r#"error("Valueless constant")"#)
 	}
@@ -73,7 +95,12 @@ fn write_import_of(list: &[Import], wanted: External, w: &mut dyn Write) -> Resu
 	Ok(())
 }
 
-fn write_export_of(list: &[Export], wanted: External, w: &mut dyn Write) -> Result<()> {
+fn write_export_of(
+	list: &[Export],
+	wanted: External,
+	wrapper_list: &[&str],
+	w: &mut dyn Write,
+) -> Result<()> {
 	let lower = wanted.as_ie_name();
 	let upper = lower.to_uppercase();
 
@@ -81,7 +108,19 @@ fn write_export_of(list: &[Export], wanted: External, w: &mut dyn Write) -> Resu
 
 	for Export { name, index, .. } in list.iter().filter(|v| External::from(v.kind) == wanted) {
 		write!(w, "\t\t\t")?;
-		writeln!(w, r#"["{name}"] = {upper}[{index}],"#)?;
+		write!(w, r#"["{name}"] = "#)?;
+
+		for wrapper in wrapper_list {
+			write!(w, "{wrapper}(")?;
+		}
+
+		write!(w, "{upper}[{index}]")?;
+
+		for _ in wrapper_list {
+			write!(w, ")")?;
+		}
+
+		writeln!(w, ",")?;
 	}
 
 	writeln!(w, "\t\t}},")
@@ -91,17 +130,169 @@ fn write_import_list(list: &[Import], w: &mut dyn Write) -> Result<()> {
 	write_import_of(list, External::Func, w)?;
 	write_import_of(list, External::Table, w)?;
 	write_import_of(list, External::Memory, w)?;
-	write_import_of(list, External::Global, w)
+	write_import_of(list, External::Global, w)?;
+	write_import_of(list, External::Tag, w)
 }
 
-fn write_export_list(list: &[Export], w: &mut dyn Write) -> Result<()> {
-	write_export_of(list, External::Func, w)?;
-	write_export_of(list, External::Table, w)?;
-	write_export_of(list, External::Memory, w)?;
-	write_export_of(list, External::Global, w)
+const fn as_type_name(ty: ValType) -> &'static str {
+	match ty {
+		ValType::I32 => "i32",
+		ValType::I64 => "i64",
+		ValType::F32 => "f32",
+		ValType::F64 => "f64",
+		ValType::V128 => "v128",
+		ValType::Ref(_) => "ref",
+	}
 }
 
-fn write_table_list(wasm: &Module, w: &mut dyn Write) -> Result<()> {
+fn write_type_list(name: &str, list: &[ValType], w: &mut dyn Write) -> Result<()> {
+	write!(w, "{name} = {{ ")?;
+
+	for &ty in list {
+		write!(w, r#""{}", "#, as_type_name(ty))?;
+	}
+
+	write!(w, "}}")
+}
+
+fn write_signature_list(list: &[Export], type_info: &TypeInfo, w: &mut dyn Write) -> Result<()> {
+	writeln!(w, "\t\tsignature_list = {{")?;
+
+	let func_list = list
+		.iter()
+		.filter(|v| External::from(v.kind) == External::Func);
+
+	for Export { name, index, .. } in func_list {
+		let ty = type_info.func_type((*index).try_into().unwrap());
+		let params = ty.params();
+		let results = ty.results();
+
+		write!(w, "\t\t\t")?;
+		write!(w, r#"["{name}"] = {{ "#)?;
+		write!(w, "params = {}, ", params.len())?;
+		write!(w, "results = {}, ", results.len())?;
+		write_type_list("param_types", params, w)?;
+		write!(w, ", ")?;
+		write_type_list("result_types", results, w)?;
+		writeln!(w, " }},")?;
+	}
+
+	writeln!(w, "\t\t}},")
+}
+
+fn write_export_list(
+	list: &[Export],
+	type_info: &TypeInfo,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<()> {
+	let wrapper_list: Vec<_> = [
+		options.trap_counter.as_ref().map(|_| "trap_guard"),
+		options.depth_limit.map(|_| "depth_guard"),
+	]
+	.into_iter()
+	.flatten()
+	.collect();
+
+	write_export_of(list, External::Func, &wrapper_list, w)?;
+	write_export_of(list, External::Table, &[], w)?;
+	write_export_of(list, External::Memory, &[], w)?;
+	write_export_of(list, External::Global, &[], w)?;
+
+	if list.iter().any(|v| External::from(v.kind) == External::Tag) {
+		write_export_of(list, External::Tag, &[], w)?;
+	}
+
+	if options.export_signatures {
+		write_signature_list(list, type_info, w)?;
+	}
+
+	Ok(())
+}
+
+// Traps unwind past the decrements at function exits, so exported functions
+// restore the depth they were entered with before passing the error along
+fn write_depth_guard(options: &Options, w: &mut dyn Write) -> Result<()> {
+	if options.depth_limit.is_none() {
+		return Ok(());
+	}
+
+	let counter = options.depth_counter();
+
+	if options.depth_counter.is_none() {
+		writeln!(w, "local {counter} = 0")?;
+	}
+
+	writeln!(w, "local function depth_restore(depth, ok, ...)")?;
+	writeln!(w, "\t{counter} = depth")?;
+	writeln!(w, "\tif not ok then")?;
+	writeln!(w, "\t\terror((...), 0)")?;
+	writeln!(w, "\tend")?;
+	writeln!(w, "\treturn ...")?;
+	writeln!(w, "end")?;
+	writeln!(w, "local function depth_guard(func)")?;
+	writeln!(w, "\treturn function(...)")?;
+	writeln!(w, "\t\treturn depth_restore({counter}, pcall(func, ...))")?;
+	writeln!(w, "\tend")?;
+	writeln!(w, "end")
+}
+
+// Traps, whether raised by the code or the runtime, are caught as they leave
+// an exported function so that a fuzzing harness can keep running and only
+// compare whether one happened
+fn write_trap_guard(options: &Options, w: &mut dyn Write) -> Result<()> {
+	let Some(counter) = &options.trap_counter else {
+		return Ok(());
+	};
+
+	writeln!(w, "local function trap_restore(ok, ...)")?;
+	writeln!(w, "\tif not ok then")?;
+	writeln!(w, "\t\t{counter}.count = ({counter}.count or 0) + 1")?;
+	writeln!(w, "\t\t{counter}.trapped = true")?;
+	writeln!(w, "\t\t{counter}.message = (...)")?;
+	writeln!(w, "\t\treturn")?;
+	writeln!(w, "\tend")?;
+	writeln!(w, "\treturn ...")?;
+	writeln!(w, "end")?;
+	writeln!(w, "local function trap_guard(func)")?;
+	writeln!(w, "\treturn function(...)")?;
+	writeln!(w, "\t\treturn trap_restore(pcall(func, ...))")?;
+	writeln!(w, "\tend")?;
+	writeln!(w, "end")
+}
+
+// Only imported globals can be missing, since the rest are set up before
+// any code reads them
+fn write_global_trap(wasm: &Module, options: &Options, w: &mut dyn Write) -> Result<()> {
+	if !options.trap_unbound_globals || wasm.import_count(External::Global) == 0 {
+		return Ok(());
+	}
+
+	let trap = options.trap_function();
+
+	writeln!(w, "setmetatable(GLOBAL_LIST, {{")?;
+	writeln!(w, "\t__index = function(_, index)")?;
+	write!(w, "\t\tlocal name = ({{ ")?;
+
+	for (i, Import { name, module, .. }) in wasm
+		.import_section()
+		.iter()
+		.filter(|v| External::from(v.ty) == External::Global)
+		.enumerate()
+	{
+		write!(w, r#"[{i}] = "{module}.{name}", "#)?;
+	}
+
+	writeln!(w, "}})[index]")?;
+	writeln!(
+		w,
+		r#"		{trap}("unbound import global: " .. tostring(name or index))"#
+	)?;
+	writeln!(w, "\tend,")?;
+	writeln!(w, "}})")
+}
+
+fn write_table_list(wasm: &Module, options: &Options, w: &mut dyn Write) -> Result<()> {
 	let offset = wasm.import_count(External::Table);
 	let table = wasm.table_section();
 
@@ -109,10 +300,15 @@ fn write_table_list(wasm: &Module, w: &mut dyn Write) -> Result<()> {
 		let index = offset + i;
 		let min = table.ty.initial;
 		let max = table.ty.maximum.unwrap_or(0xFFFF);
+		let data = if options.is_presized(min.try_into().unwrap()) {
+			format!("table.create({min})")
+		} else {
+			"{}".to_string()
+		};
 
 		writeln!(
 			w,
-			"\tTABLE_LIST[{index}] = {{ min = {min}, max = {max}, data = {{}} }}"
+			"\tTABLE_LIST[{index}] = {{ min = {min}, max = {max}, data = {data} }}"
 		)?;
 	}
 
@@ -134,7 +330,12 @@ fn write_memory_list(wasm: &Module, w: &mut dyn Write) -> Result<()> {
 	Ok(())
 }
 
-fn write_global_list(wasm: &Module, type_info: &TypeInfo, w: &mut dyn Write) -> Result<()> {
+fn write_global_list(
+	wasm: &Module,
+	type_info: &TypeInfo,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<()> {
 	let offset = wasm.import_count(External::Global);
 	let global = wasm.global_section();
 
@@ -142,31 +343,129 @@ fn write_global_list(wasm: &Module, type_info: &TypeInfo, w: &mut dyn Write) ->
 		let index = offset + i;
 
 		write!(w, "\tGLOBAL_LIST[{index}] = {{ value = ")?;
-		write_constant(&global.init_expr, type_info, w)?;
+		write_constant(&global.init_expr, type_info, options, w)?;
 		writeln!(w, " }}")?;
 	}
 
 	Ok(())
 }
 
-fn write_element_list(list: &[Element], type_info: &TypeInfo, w: &mut dyn Write) -> Result<()> {
+// Tags are only told apart by identity, so each defined one is a new table
+fn write_tag_list(wasm: &Module, w: &mut dyn Write) -> Result<()> {
+	for index in wasm.import_count(External::Tag)..wasm.tag_space() {
+		writeln!(w, "\tTAG_LIST[{index}] = {{}}")?;
+	}
+
+	Ok(())
+}
+
+// The table can only be flattened when nothing outside the module sees it
+// Lists the module never uses are not declared, so only the rest are named
+fn internal_list_names(wasm: &Module, options: &Options) -> Vec<&'static str> {
+	let table = if flat_table_min(wasm, options).is_some() {
+		"TABLE"
+	} else {
+		"TABLE_LIST"
+	};
+
+	[
+		("FUNC_LIST", wasm.function_space()),
+		(table, wasm.table_space()),
+		("MEMORY_LIST", wasm.memory_space()),
+		("GLOBAL_LIST", wasm.global_space()),
+		("TAG_LIST", wasm.tag_space()),
+	]
+	.into_iter()
+	.filter(|v| v.1 != 0)
+	.map(|v| v.0)
+	.collect()
+}
+
+fn flat_table_min(wasm: &Module, options: &Options) -> Option<u32> {
+	let is_hidden = wasm.import_count(External::Table) == 0
+		&& !wasm
+			.export_section()
+			.iter()
+			.any(|v| External::from(v.kind) == External::Table);
+
+	match wasm.table_section() {
+		[table] if options.flat_table && is_hidden => Some(table.ty.initial),
+		_ => None,
+	}
+}
+
+fn memory64_set(wasm: &Module) -> BTreeSet<usize> {
+	let imported = wasm.import_section().iter().filter_map(|v| match v.ty {
+		TypeRef::Memory(ty) => Some(ty),
+		_ => None,
+	});
+
+	imported
+		.chain(wasm.memory_section().iter().copied())
+		.enumerate()
+		.filter_map(|(i, ty)| ty.memory64.then_some(i))
+		.collect()
+}
+
+fn flexible_result_map(wasm: &Module, options: &Options) -> HashMap<usize, Vec<ValType>> {
+	let imported = wasm.import_section().iter().filter_map(|v| match v.ty {
+		TypeRef::Func(ty) => Some(usize::try_from(ty).unwrap()),
+		_ => None,
+	});
+
+	imported
+		.enumerate()
+		.filter(|(i, _)| options.flexible_result_set.contains(i))
+		.map(|(i, ty)| {
+			let Type::Func(ty) = &wasm.type_section()[ty] else {
+				unreachable!("type at func index must be a func type");
+			};
+
+			(i, ty.results().to_vec())
+		})
+		.collect()
+}
+
+fn write_element_list(
+	list: &[Element],
+	type_info: &TypeInfo,
+	is_flat: bool,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<()> {
 	for element in list {
-		let ElementKind::Active { table_index: index, offset_expr: init } = element.kind else {
+		let ElementKind::Active {
+			table_index: index,
+			offset_expr: init,
+		} = element.kind
+		else {
 			unimplemented!("passive elements not supported")
 		};
 
 		let index = index.unwrap_or(0);
 
 		writeln!(w, "\tdo")?;
-		writeln!(w, "\t\tlocal target = TABLE_LIST[{index}].data")?;
-		write!(w, "\t\tlocal offset = ")?;
 
-		write_constant(&init, type_info, w)?;
+		if is_flat {
+			writeln!(w, "\t\tlocal target = TABLE")?;
+			write!(w, "\t\tlocal offset = 1 + ")?;
+		} else {
+			writeln!(w, "\t\tlocal target = TABLE_LIST[{index}].data")?;
+			write!(w, "\t\tlocal offset = ")?;
+		}
+
+		write_constant(&init, type_info, options, w)?;
 
 		writeln!(w)?;
 		write!(w, "\t\tlocal data = {{ ")?;
 
 		match element.items.clone() {
+			ElementItems::Functions(functions) if options.lazy_elements => {
+				for index in functions {
+					let index = index.unwrap();
+					write!(w, "{index},")?;
+				}
+			}
 			ElementItems::Functions(functions) => {
 				for index in functions {
 					let index = index.unwrap();
@@ -176,20 +475,93 @@ fn write_element_list(list: &[Element], type_info: &TypeInfo, w: &mut dyn Write)
 			ElementItems::Expressions(expressions) => {
 				for init in expressions {
 					let init = init.unwrap();
-					write_constant(&init, type_info, w)?;
+					write_constant(&init, type_info, options, w)?;
+					write!(w, ",")?;
 				}
 			}
 		}
 
 		writeln!(w, " }}")?;
-		writeln!(w, "\t\ttable.move(data, 1, #data, offset, target)")?;
+
+		if options.lazy_elements && matches!(element.items, ElementItems::Functions(_)) {
+			writeln!(w, "\t\tset_lazy_elements(target, offset, data)")?;
+		} else {
+			writeln!(w, "\t\ttable.move(data, 1, #data, offset, target)")?;
+		}
+
 		writeln!(w, "\tend")?;
 	}
 
 	Ok(())
 }
 
-fn write_data_list(list: &[Data], type_info: &TypeInfo, w: &mut dyn Write) -> Result<()> {
+// Entries are resolved at most once and then stored in the table itself,
+// while writes to entries not yet resolved drop them from the map so they
+// cannot come back after being replaced
+fn write_lazy_elements(options: &Options, w: &mut dyn Write) -> Result<()> {
+	if !options.lazy_elements {
+		return Ok(());
+	}
+
+	writeln!(w, "local function set_lazy_elements(target, offset, data)")?;
+	writeln!(w, "\tlocal meta = getmetatable(target)")?;
+	writeln!(w, "\tif meta == nil then")?;
+	writeln!(w, "\t\tlocal map = {{}}")?;
+	writeln!(w, "\t\tmeta = {{")?;
+	writeln!(w, "\t\t\tmap = map,")?;
+	writeln!(w, "\t\t\t__index = function(list, index)")?;
+	writeln!(w, "\t\t\t\tlocal func = FUNC_LIST[map[index]]")?;
+	writeln!(w, "\t\t\t\tmap[index] = nil")?;
+	writeln!(w, "\t\t\t\trawset(list, index, func)")?;
+	writeln!(w, "\t\t\t\treturn func")?;
+	writeln!(w, "\t\t\tend,")?;
+	writeln!(w, "\t\t\t__newindex = function(list, index, value)")?;
+	writeln!(w, "\t\t\t\tmap[index] = nil")?;
+	writeln!(w, "\t\t\t\trawset(list, index, value)")?;
+	writeln!(w, "\t\t\tend,")?;
+	writeln!(w, "\t\t}}")?;
+	writeln!(w, "\t\tsetmetatable(target, meta)")?;
+	writeln!(w, "\tend")?;
+	writeln!(w, "\tfor i, index in ipairs(data) do")?;
+	writeln!(w, "\t\trawset(target, offset + i - 1, nil)")?;
+	writeln!(w, "\t\tmeta.map[offset + i - 1] = index")?;
+	writeln!(w, "\tend")?;
+	writeln!(w, "end")
+}
+
+// Runs of a repeated byte at least this long are written as a fill
+const MIN_FILL_LEN: usize = 32;
+
+fn write_data_offset(
+	init: &ConstExpr,
+	position: usize,
+	is_memory64: bool,
+	type_info: &TypeInfo,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<()> {
+	if is_memory64 {
+		write!(w, "rt.convert.f64_u64(")?;
+		write_constant(init, type_info, options, w)?;
+		write!(w, ")")?;
+	} else {
+		write_constant(init, type_info, options, w)?;
+	}
+
+	if position != 0 {
+		write!(w, " + {position}")?;
+	}
+
+	Ok(())
+}
+
+fn write_data_list(
+	list: &[Data],
+	memory64_set: &BTreeSet<usize>,
+	type_info: &TypeInfo,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<()> {
 	for data in list {
 		let (index, init) = match data.kind {
 			DataKind::Passive => unimplemented!("passive data not supported"),
@@ -199,9 +571,52 @@ fn write_data_list(list: &[Data], type_info: &TypeInfo, w: &mut dyn Write) -> Re
 			} => (memory_index, offset_expr),
 		};
 
-		write!(w, "\trt.store.string(MEMORY_LIST[{index}], ")?;
-		write_constant(&init, type_info, w)?;
-		writeln!(w, r#","{}")"#, data.data.escape_ascii())?;
+		let is_memory64 = memory64_set.contains(&index.try_into().unwrap());
+		let mut start = 0;
+		let mut position = 0;
+
+		for run in data.data.chunk_by(|a, b| a == b) {
+			if run.len() >= MIN_FILL_LEN {
+				let text = &data.data[start..position];
+
+				if !text.is_empty() {
+					write!(w, "\trt.store.string(MEMORY_LIST[{index}], ")?;
+					write_data_offset(&init, start, is_memory64, type_info, options, w)?;
+					writeln!(w, r#","{}")"#, text.escape_ascii())?;
+				}
+
+				write!(w, "\trt.store.fill(MEMORY_LIST[{index}], ")?;
+				write_data_offset(&init, position, is_memory64, type_info, options, w)?;
+				writeln!(w, ", {}, {})", run.len(), run[0])?;
+
+				start = position + run.len();
+			}
+
+			position += run.len();
+		}
+
+		let text = &data.data[start..];
+
+		if !text.is_empty() || start == 0 {
+			write!(w, "\trt.store.string(MEMORY_LIST[{index}], ")?;
+			write_data_offset(&init, start, is_memory64, type_info, options, w)?;
+			writeln!(w, r#","{}")"#, text.escape_ascii())?;
+		}
+	}
+
+	Ok(())
+}
+
+fn check_func_list(wasm: &Module, func_list: &[FuncData]) -> Result<()> {
+	let offset = wasm.import_count(External::Func);
+
+	for (i, ast) in func_list.iter().enumerate() {
+		if let Some(error) = bounds::visit(ast, wasm) {
+			let index = offset + i;
+			let error = format!("function {index}: {error}");
+
+			return Err(Error::new(ErrorKind::InvalidData, error));
+		}
 	}
 
 	Ok(())
@@ -218,7 +633,12 @@ fn build_func_list(wasm: &Module, type_info: &TypeInfo) -> Vec<FuncData> {
 		.collect()
 }
 
-fn write_local_operation(head: &str, tail: &str, w: &mut dyn Write) -> Result<()> {
+fn write_local_operation(
+	head: &str,
+	tail: &str,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<()> {
 	write!(w, "local {head}_{tail} = ")?;
 
 	match (head, tail) {
@@ -226,6 +646,14 @@ fn write_local_operation(head: &str, tail: &str, w: &mut dyn Write) -> Result<()
 		("band" | "bor" | "bxor" | "bnot", "i32") => write!(w, "bit32.{head}"),
 		("clz", "i32") => write!(w, "bit32.countlz"),
 		("ctz", "i32") => write!(w, "bit32.countrz"),
+		("load", _) => {
+			let source = options.load_source.as_deref().unwrap_or("rt.load");
+
+			write!(w, "{source}.{tail}")
+		}
+		("store", _) => {
+			write!(w, "{}.{tail}", options.store_source())
+		}
 		_ => write!(w, "rt.{head}.{tail}"),
 	}?;
 
@@ -235,6 +663,7 @@ fn write_local_operation(head: &str, tail: &str, w: &mut dyn Write) -> Result<()
 fn write_localize_used(
 	wasm: &Module,
 	func_list: &[FuncData],
+	options: &Options,
 	w: &mut dyn Write,
 ) -> Result<BTreeSet<usize>> {
 	let mut loc_set = BTreeSet::new();
@@ -251,13 +680,18 @@ fn write_localize_used(
 		loc_set.insert(("i64", "from_u32"));
 	}
 
+	// Defaults for flexible results are written outside the syntax tree
+	if !options.flexible_result_set.is_empty() {
+		loc_set.insert(("i64", "ZERO"));
+	}
+
 	for (loc, mem) in func_list.iter().map(localize::visit) {
 		loc_set.extend(loc);
 		mem_set.extend(mem);
 	}
 
 	for loc in loc_set {
-		write_local_operation(loc.0, loc.1, w)?;
+		write_local_operation(loc.0, loc.1, options, w)?;
 	}
 
 	for mem in &mem_set {
@@ -267,7 +701,11 @@ fn write_localize_used(
 	Ok(mem_set)
 }
 
-fn write_func_start(wasm: &Module, index: u32, w: &mut dyn Write) -> Result<()> {
+fn write_func_start(wasm: &Module, index: u32, options: &Options, w: &mut dyn Write) -> Result<()> {
+	if let Some(location) = options.location_map.get(&index.try_into().unwrap()) {
+		writeln!(w, "-- {location}")?;
+	}
+
 	write!(w, "FUNC_LIST[{index}] = ")?;
 
 	wasm.name_section()
@@ -275,34 +713,204 @@ fn write_func_start(wasm: &Module, index: u32, w: &mut dyn Write) -> Result<()>
 		.map_or_else(|| Ok(()), |name| write!(w, "--[[ {name} ]] "))
 }
 
-fn write_func_list(wasm: &Module, func_list: &[FuncData], w: &mut dyn Write) -> Result<()> {
+fn sort_func_order(wasm: &Module, order: &mut [usize]) {
+	let offset = wasm.import_count(External::Func);
+	let mut name_map = HashMap::new();
+
+	for Export { name, index, .. } in wasm
+		.export_section()
+		.iter()
+		.filter(|v| External::from(v.kind) == External::Func)
+	{
+		let index = usize::try_from(*index).unwrap();
+		let entry = name_map.entry(index).or_insert(*name);
+
+		*entry = (*entry).min(*name);
+	}
+
+	order.sort_by_key(|&i| {
+		let name = name_map.get(&(offset + i)).copied();
+
+		(name.is_none(), name, i)
+	});
+}
+
+// Lua fails to load functions past its limits on jumps and constants with
+// little to say where. These are only known once compiled, so the length of
+// the code stands in for them and a clear error is given early instead
+fn write_func_body(
+	func: &FuncData,
+	mng: &mut Manager,
+	index: usize,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<()> {
+	let Some(limit) = options.function_size_limit else {
+		return func.write(mng, w);
+	};
+
+	let mut data = Vec::new();
+
+	func.write(mng, &mut data)?;
+
+	if data.len() > limit {
+		let len = data.len();
+		let error = format!("function {index} is {len} bytes long, past the limit of {limit}");
+
+		return Err(Error::new(ErrorKind::InvalidData, error));
+	}
+
+	w.write_all(&data)
+}
+
+// Integers are tables or vectors and floats are printed in decimal, so both
+// are keyed by their exact bits instead
+fn write_memo_key(ty: ValType, var: &str) -> Option<String> {
+	match ty {
+		ValType::I32 => Some(format!("tostring({var})")),
+		ValType::I64 => Some(format!(r#"string.pack("I4I4", rt.i64.into_u32({var}))"#)),
+		ValType::F32 | ValType::F64 => Some(format!(r#"string.pack("d", {var})"#)),
+		ValType::V128 | ValType::Ref(_) => None,
+	}
+}
+
+// Only pure functions are memoized since any other may give a different
+// result for the same arguments. References are printed as addresses that
+// may be reused once collected, so functions taking them are left alone
+fn write_memoized(func: &FuncData, index: usize, w: &mut dyn Write) -> Result<()> {
+	let (_, mem_set) = localize::visit(func);
+
+	if !mem_set.is_empty() || !pure::visit(func) {
+		return Ok(());
+	}
+
+	let param_list: Vec<_> = (0..func.num_param()).map(|i| format!("loc_{i}")).collect();
+	let Some(key_list) = func
+		.param_data()
+		.iter()
+		.zip(&param_list)
+		.map(|(&ty, var)| write_memo_key(ty, var))
+		.collect::<Option<Vec<_>>>()
+	else {
+		return Ok(());
+	};
+	let param_list = param_list.join(", ");
+	let key_list = if key_list.is_empty() {
+		r#""""#.to_string()
+	} else {
+		key_list.join(r#" .. "," .. "#)
+	};
+
+	writeln!(w, "do")?;
+	writeln!(
+		w,
+		"\tlocal memo_func, memo_cache = FUNC_LIST[{index}], {{}}"
+	)?;
+	writeln!(w, "\tFUNC_LIST[{index}] = function({param_list})")?;
+	writeln!(w, "\t\tlocal key = {key_list}")?;
+	writeln!(w, "\t\tlocal result = memo_cache[key]")?;
+	writeln!(w, "\t\tif result == nil then")?;
+	writeln!(w, "\t\t\tresult = {{ memo_func({param_list}) }}")?;
+	writeln!(w, "\t\t\tmemo_cache[key] = result")?;
+	writeln!(w, "\t\tend")?;
+	writeln!(w, "\t\treturn table.unpack(result)")?;
+	writeln!(w, "\tend")?;
+	writeln!(w, "end")
+}
+
+fn write_func(
+	wasm: &Module,
+	func: &FuncData,
+	index: usize,
+	options: &Options,
+	legend: &mut Vec<Branch>,
+	w: &mut dyn Write,
+) -> Result<FunctionStats> {
+	let mut mng = Manager::function(func, Some(index), options);
+
+	mng.set_flat_table(flat_table_min(wasm, options));
+	mng.set_memory64_set(memory64_set(wasm));
+	mng.set_flexible_result_map(flexible_result_map(wasm, options));
+	mng.set_branch_base(legend.len());
+
+	write_func_start(wasm, index.try_into().unwrap(), options, w)?;
+	write_func_body(func, &mut mng, index, options, w)?;
+
+	if options.memoize_set.contains(&index) {
+		write_memoized(func, index, w)?;
+	}
+
+	legend.extend_from_slice(mng.branch_list());
+
+	Ok(mng.stats())
+}
+
+fn write_func_list(
+	wasm: &Module,
+	func_list: &[FuncData],
+	options: &Options,
+	legend: &mut Vec<Branch>,
+	w: &mut dyn Write,
+) -> Result<Vec<FunctionStats>> {
 	let offset = wasm.import_count(External::Func);
+	let mut order: Vec<_> = (0..func_list.len()).collect();
 
-	func_list.iter().enumerate().try_for_each(|(i, v)| {
-		let index = (offset + i).try_into().unwrap();
+	if options.sort_func_list {
+		sort_func_order(wasm, &mut order);
+	}
 
-		write_func_start(wasm, index, w)?;
+	let mut stats = order
+		.into_iter()
+		.map(|i| write_func(wasm, &func_list[i], offset + i, options, legend, w))
+		.collect::<Result<Vec<_>>>()?;
 
-		v.write(&mut Manager::function(v), w)
-	})
+	stats.sort_by_key(|v| v.index);
+
+	Ok(stats)
 }
 
 fn write_module_start(
 	wasm: &Module,
 	type_info: &TypeInfo,
 	mem_set: &BTreeSet<usize>,
+	options: &Options,
 	w: &mut dyn Write,
 ) -> Result<()> {
+	let flat_table = flat_table_min(wasm, options);
+
+	write_lazy_elements(options, w)?;
 	writeln!(w, "local function run_init_code()")?;
-	write_table_list(wasm, w)?;
+
+	if flat_table.is_none() {
+		write_table_list(wasm, options, w)?;
+	}
+
 	write_memory_list(wasm, w)?;
-	write_global_list(wasm, type_info, w)?;
-	write_element_list(wasm.element_section(), type_info, w)?;
-	write_data_list(wasm.data_section(), type_info, w)?;
+	write_global_list(wasm, type_info, options, w)?;
+	write_tag_list(wasm, w)?;
+	write_element_list(
+		wasm.element_section(),
+		type_info,
+		flat_table.is_some(),
+		options,
+		w,
+	)?;
+	write_data_list(
+		wasm.data_section(),
+		&memory64_set(wasm),
+		type_info,
+		options,
+		w,
+	)?;
 	writeln!(w, "end")?;
 
-	writeln!(w, "return function(wasm)")?;
-	write_import_list(wasm.import_section(), w)?;
+	if options.deferred_init {
+		writeln!(w, "local function INIT()")?;
+	} else {
+		writeln!(w, "return function(wasm)")?;
+		write_import_list(wasm.import_section(), w)?;
+	}
+
 	writeln!(w, "\trun_init_code()")?;
 
 	for mem in mem_set {
@@ -314,9 +922,29 @@ fn write_module_start(
 	}
 
 	writeln!(w, "\treturn {{")?;
-	write_export_list(wasm.export_section(), w)?;
+	write_export_list(wasm.export_section(), type_info, options, w)?;
+
+	if options.internal_lists {
+		write!(w, "\t\tinternal_list = {{ ")?;
+
+		for name in internal_list_names(wasm, options) {
+			write!(w, "{name} = {name}, ")?;
+		}
+
+		writeln!(w, "}},")?;
+	}
+
 	writeln!(w, "\t}}")?;
-	writeln!(w, "end")
+	writeln!(w, "end")?;
+
+	if options.deferred_init {
+		writeln!(w, "return function(wasm)")?;
+		write_import_list(wasm.import_section(), w)?;
+		writeln!(w, "\treturn INIT")?;
+		writeln!(w, "end")?;
+	}
+
+	Ok(())
 }
 
 /// # Errors
@@ -324,22 +952,225 @@ fn write_module_start(
 pub fn from_inst_list(code: &[Operator], type_info: &TypeInfo, w: &mut dyn Write) -> Result<()> {
 	let ast = Factory::from_type_info(type_info).create_anonymous(code);
 
-	ast.write(&mut Manager::function(&ast), w)
+	ast.write(&mut Manager::function(&ast, None, &Options::default()), w)
 }
 
+/// Writes a function that uses no memory, globals, or calls as a chunk.
+///
+/// The chunk declares the runtime helpers it needs and returns the function,
+/// so it can be lifted out of the module and only depends on `rt`. Nothing is
+/// written and `false` is returned when the function is not pure.
+///
 /// # Errors
 /// Returns `Err` if writing to `Write` failed.
-pub fn from_module_typed(wasm: &Module, type_info: &TypeInfo, w: &mut dyn Write) -> Result<()> {
-	let func_list = build_func_list(wasm, type_info);
-	let mem_set = write_localize_used(wasm, &func_list, w)?;
+pub fn from_pure_func(ast: &FuncData, w: &mut dyn Write) -> Result<bool> {
+	let (loc_set, mem_set) = localize::visit(ast);
+
+	if !mem_set.is_empty() || !pure::visit(ast) {
+		return Ok(false);
+	}
+
+	let options = Options::default();
+
+	for loc in loc_set {
+		write_local_operation(loc.0, loc.1, &options, w)?;
+	}
+
+	write!(w, "return ")?;
+	ast.write(&mut Manager::function(ast, None, &options), w)?;
+
+	Ok(true)
+}
+
+/// Writes the function at `index` in `FUNC_LIST` as a chunk that replaces it
+/// in a running instance, for reloading code while developing.
+///
+/// The chunk is called with the lists an instance built with `internal_lists`
+/// gives as `internal_list`, declares the runtime helpers it needs, and then
+/// assigns the function at its index. The options should match those the
+/// instance was built with.
+///
+/// # Errors
+/// Returns `Err` with `ErrorKind::InvalidInput` if the module defines no
+/// function at `index` or the call depth is kept in a module local, and with
+/// `ErrorKind::InvalidData` if the function names an item the module does
+/// not declare. Also returns `Err` if writing to `Write` failed.
+pub fn from_func_patch(
+	wasm: &Module,
+	type_info: &TypeInfo,
+	index: usize,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<FunctionStats> {
+	let offset = wasm.import_count(External::Func);
+	let Some(body) = index
+		.checked_sub(offset)
+		.and_then(|i| wasm.code_section().get(i))
+	else {
+		let error = format!("function {index} is not defined by the module");
+
+		return Err(Error::new(ErrorKind::InvalidInput, error));
+	};
+
+	if options.depth_limit.is_some() && options.depth_counter.is_none() {
+		let error = "patched functions cannot reach a module local `depth_counter`";
+
+		return Err(Error::new(ErrorKind::InvalidInput, error));
+	}
+
+	let func = Factory::from_type_info(type_info)
+		.create_indexed(index, body)
+		.map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+
+	if let Some(error) = bounds::visit(&func, wasm) {
+		let error = format!("function {index}: {error}");
+
+		return Err(Error::new(ErrorKind::InvalidData, error));
+	}
+
+	writeln!(w, "local internal_list = ...")?;
+
+	for name in internal_list_names(wasm, options) {
+		writeln!(w, "local {name} = internal_list.{name}")?;
+	}
+
+	if let Some(runtime) = &options.runtime_module {
+		writeln!(w, "local rt = require({runtime})")?;
+	}
+
+	let mem_set = write_localize_used(wasm, std::slice::from_ref(&func), options, w)?;
+
+	for mem in &mem_set {
+		writeln!(w, "memory_at_{mem} = MEMORY_LIST[{mem}]")?;
+	}
+
+	write_func(wasm, &func, index, options, &mut Vec::new(), w)
+}
+
+// The drivers emit many small pieces, which are gathered here so that a
+// writer such as a file is not called once for each of them
+fn write_module(
+	wasm: &Module,
+	type_info: &TypeInfo,
+	func_list: &[FuncData],
+	options: &Options,
+	legend: &mut Vec<Branch>,
+	w: &mut dyn Write,
+) -> Result<Vec<FunctionStats>> {
+	let mut buffered = match options.write_buffer_size {
+		Some(capacity) => BufWriter::with_capacity(capacity, w),
+		None => BufWriter::new(w),
+	};
+	let stats =
+		write_module_unbuffered(wasm, type_info, func_list, options, legend, &mut buffered)?;
+
+	buffered.flush()?;
+
+	Ok(stats)
+}
+
+fn write_module_unbuffered(
+	wasm: &Module,
+	type_info: &TypeInfo,
+	func_list: &[FuncData],
+	options: &Options,
+	legend: &mut Vec<Branch>,
+	w: &mut dyn Write,
+) -> Result<Vec<FunctionStats>> {
+	let limit = options.output_size_limit.unwrap_or(usize::MAX);
+	let w = &mut SizeLimit::new(w, limit);
+
+	check_func_list(wasm, func_list)?;
+
+	if let Some(directive) = &options.analyzer_directive {
+		writeln!(w, "--!{directive}")?;
+	}
+
+	if options.banner {
+		write_banner(options, w)?;
+	}
+
+	if let Some(runtime) = &options.runtime_module {
+		writeln!(w, "local rt = require({runtime})")?;
+	}
+
+	let mem_set = write_localize_used(wasm, func_list, options, w)?;
 
 	write_named_array("FUNC_LIST", wasm.function_space(), w)?;
-	write_named_array("TABLE_LIST", wasm.table_space(), w)?;
+
+	if flat_table_min(wasm, options).is_some() {
+		writeln!(w, "local TABLE = {{}}")?;
+	} else {
+		write_named_array("TABLE_LIST", wasm.table_space(), w)?;
+	}
+
 	write_named_array("MEMORY_LIST", wasm.memory_space(), w)?;
 	write_named_array("GLOBAL_LIST", wasm.global_space(), w)?;
+	write_named_array("TAG_LIST", wasm.tag_space(), w)?;
+	write_global_trap(wasm, options, w)?;
+	write_depth_guard(options, w)?;
+	write_trap_guard(options, w)?;
+
+	let stats = write_func_list(wasm, func_list, options, legend, w)?;
+
+	write_module_start(wasm, type_info, &mem_set, options, w)?;
 
-	write_func_list(wasm, &func_list, w)?;
-	write_module_start(wasm, type_info, &mem_set, w)
+	Ok(stats)
+}
+
+/// Translates the module using function bodies that were already built.
+///
+/// This lets callers run their own passes over the syntax trees first. The
+/// list holds one entry per function defined, not imported, by the module.
+/// Statistics on the code written for each function are returned.
+///
+/// # Errors
+/// Returns `Err` with `ErrorKind::InvalidData` if a function names a memory,
+/// table, function, or global the module does not declare, or if writing
+/// to `Write` failed.
+pub fn from_func_list(
+	wasm: &Module,
+	type_info: &TypeInfo,
+	func_list: &[FuncData],
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<Vec<FunctionStats>> {
+	write_module(wasm, type_info, func_list, options, &mut Vec::new(), w)
+}
+
+/// Translates the module and returns statistics on the code written for
+/// each of its functions.
+///
+/// # Errors
+/// Returns `Err` if writing to `Write` failed.
+pub fn from_module_typed(
+	wasm: &Module,
+	type_info: &TypeInfo,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<Vec<FunctionStats>> {
+	let func_list = build_func_list(wasm, type_info);
+
+	from_func_list(wasm, type_info, &func_list, options, w)
+}
+
+/// Translates the module and returns the legend of the counters kept in
+/// `coverage_table`, which is empty when that is not set.
+///
+/// # Errors
+/// Returns `Err` if writing to `Write` failed.
+pub fn from_module_covered(
+	wasm: &Module,
+	type_info: &TypeInfo,
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<Vec<Branch>> {
+	let func_list = build_func_list(wasm, type_info);
+	let mut legend = Vec::new();
+
+	write_module(wasm, type_info, &func_list, options, &mut legend, w)?;
+
+	Ok(legend)
 }
 
 /// # Errors
@@ -347,5 +1178,155 @@ pub fn from_module_typed(wasm: &Module, type_info: &TypeInfo, w: &mut dyn Write)
 pub fn from_module_untyped(wasm: &Module, w: &mut dyn Write) -> Result<()> {
 	let type_info = TypeInfo::from_module(wasm);
 
-	from_module_typed(wasm, &type_info, w)
+	from_module_typed(wasm, &type_info, &Options::default(), w)?;
+
+	Ok(())
+}
+
+/// Translates several modules into one chunk where they share a single `rt`.
+///
+/// Each module keeps its own functions, tables, memories, and globals. The
+/// chunk returns a function taking the host imports that instantiates the
+/// modules in order, giving each the exports of those before it under their
+/// names, and returns the exports of every module by name.
+///
+/// # Errors
+/// Returns `Err` with `ErrorKind::InvalidInput` if `deferred_init` is set,
+/// as each module must hand its exports to those after it rather than
+/// `INIT`, and with `ErrorKind::InvalidData` if a function names an item
+/// its module does not declare. Also returns `Err` if writing to `Write`
+/// failed.
+pub fn from_module_list(
+	list: &[(&str, &Module)],
+	options: &Options,
+	w: &mut dyn Write,
+) -> Result<Vec<Vec<FunctionStats>>> {
+	if options.deferred_init {
+		let error = "linked modules cannot defer their initialization";
+
+		return Err(Error::new(ErrorKind::InvalidInput, error));
+	}
+
+	writeln!(w, "local MODULE_LIST = {{}}")?;
+
+	let stats = list
+		.iter()
+		.map(|&(name, wasm)| {
+			let type_info = TypeInfo::from_module(wasm);
+
+			writeln!(w, r#"MODULE_LIST["{name}"] = (function()"#)?;
+
+			let stats = from_module_typed(wasm, &type_info, options, w)?;
+
+			writeln!(w, "end)()")?;
+
+			Ok(stats)
+		})
+		.collect::<Result<Vec<_>>>()?;
+
+	writeln!(w, "return function(wasm)")?;
+	writeln!(
+		w,
+		"\tlocal linked = setmetatable({{}}, {{ __index = wasm }})"
+	)?;
+
+	for (name, _) in list {
+		write!(w, "\t")?;
+		writeln!(w, r#"linked["{name}"] = MODULE_LIST["{name}"](linked)"#)?;
+	}
+
+	writeln!(w, "\treturn setmetatable(linked, nil)")?;
+	writeln!(w, "end")?;
+
+	Ok(stats)
+}
+
+fn write_banner(options: &Options, w: &mut dyn Write) -> Result<()> {
+	let version = env!("CARGO_PKG_VERSION");
+	let list = options.changed_list();
+
+	writeln!(w, "-- Generated by Wasynth {version} for Luau")?;
+
+	if list.is_empty() {
+		writeln!(w, "-- Options: default")
+	} else {
+		writeln!(w, "-- Options: {}", list.join(", "))
+	}
+}
+
+fn write_prelude(w: &mut dyn Write) -> Result<()> {
+	writeln!(w, "local Integer = (function()")?;
+	writeln!(w, "{}", crate::NUMERIC)?;
+	writeln!(w, "end)()")?;
+	writeln!(w, "local rt = (function()")?;
+	writeln!(w, "{}", crate::RUNTIME)?;
+	writeln!(w, "end)()")
+}
+
+/// Translates WebAssembly bytes into a complete chunk of source.
+///
+/// The chunk embeds the runtime and returns the function that instantiates
+/// the module, so it can be loaded without any other files.
+///
+/// ```no_run
+/// let data = std::fs::read("module.wasm")?;
+/// let source = codegen_luau::transpile(&data)?;
+///
+/// std::fs::write("module.lua", source)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// # Errors
+/// Returns `Err` if the bytes are not a valid module.
+pub fn transpile(data: &[u8]) -> Result<String> {
+	transpile_with_hash(data).map(|v| v.0)
+}
+
+/// Translates WebAssembly bytes like [`transpile`] and also returns a 64-bit
+/// FNV-1a hash of the chunk, computed as it is written.
+///
+/// The same bytes always give the same chunk, so the hash can tell build
+/// systems when the output has not changed.
+///
+/// # Errors
+/// Returns `Err` if the bytes are not a valid module.
+pub fn transpile_with_hash(data: &[u8]) -> Result<(String, u64)> {
+	let wasm = Module::try_from_data(data).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+	let mut w = Fingerprint::new(Vec::new());
+
+	write_banner(&Options::default(), &mut w)?;
+	write_prelude(&mut w)?;
+
+	from_module_untyped(&wasm, &mut w)?;
+
+	let hash = w.hash();
+	let source =
+		String::from_utf8(w.into_inner()).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+	Ok((source, hash))
+}
+
+/// Translates named WebAssembly modules into a complete chunk of source
+/// that embeds the runtime once and links them with [`from_module_list`].
+///
+/// # Errors
+/// Returns `Err` if any of the bytes are not a valid module.
+pub fn transpile_list(list: &[(&str, &[u8])]) -> Result<String> {
+	let parsed = list
+		.iter()
+		.map(|&(name, data)| {
+			Module::try_from_data(data)
+				.map(|wasm| (name, wasm))
+				.map_err(|e| Error::new(ErrorKind::InvalidData, e))
+		})
+		.collect::<Result<Vec<_>>>()?;
+
+	let module_list: Vec<_> = parsed.iter().map(|(name, wasm)| (*name, wasm)).collect();
+	let mut w = Vec::new();
+
+	write_banner(&Options::default(), &mut w)?;
+	write_prelude(&mut w)?;
+	from_module_list(&module_list, &Options::default(), &mut w)?;
+
+	String::from_utf8(w).map_err(|e| Error::new(ErrorKind::InvalidData, e))
 }
@@ -3,6 +3,7 @@ use std::{
 	path::PathBuf,
 };
 
+use codegen_luau::Options;
 use wasm_ast::module::{Module, TypeInfo};
 use wast::{
 	core::{WastArgCore, WastRetCore},
@@ -163,7 +164,7 @@ impl Target for Luau {
 		let type_info = TypeInfo::from_module(data);
 
 		writeln!(w, r#"loaded["temp"] = (function()"#)?;
-		codegen_luau::from_module_typed(data, &type_info, w)?;
+		codegen_luau::from_module_typed(data, &type_info, &Options::default(), w)?;
 		writeln!(w, "end)()(linked)")?;
 
 		if let Some(name) = name {
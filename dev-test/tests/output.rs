@@ -0,0 +1,4215 @@
+use wasm_ast::{
+	analyzer,
+	call_graph::CallGraph,
+	factory::Factory,
+	manifest::{ImportType, Limits, Manifest},
+	module::{Module, TypeInfo},
+	node::{BinOp, BinOpType, Expression, Local, Statement, StoreAt, StoreType, Value},
+	stack_depth,
+};
+use wasmparser::ValType;
+use wast::{parser::ParseBuffer, Wat};
+
+fn encode(source: &str) -> Vec<u8> {
+	let lexed = ParseBuffer::new(source).expect("Failed to tokenize");
+	let mut parsed: Wat = wast::parser::parse(&lexed).unwrap();
+
+	parsed.encode().unwrap()
+}
+
+fn luajit_with(source: &str, options: &codegen_luajit::Options) -> String {
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut output = Vec::new();
+
+	codegen_luajit::from_module_typed(&wasm, &type_info, options, &mut output).unwrap();
+
+	String::from_utf8(output).unwrap()
+}
+
+fn luau_with(source: &str, options: &codegen_luau::Options) -> String {
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut output = Vec::new();
+
+	codegen_luau::from_module_typed(&wasm, &type_info, options, &mut output).unwrap();
+
+	String::from_utf8(output).unwrap()
+}
+
+fn luajit(source: &str) -> String {
+	luajit_with(source, &codegen_luajit::Options::default())
+}
+
+fn luau(source: &str) -> String {
+	luau_with(source, &codegen_luau::Options::default())
+}
+
+#[test]
+fn empty_function_bodies() {
+	let source = r#"
+		(module
+			(func (export "void"))
+			(func (export "trap") (result i32) unreachable)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		let (void, trap) = output.split_once("FUNC_LIST[1]").unwrap();
+
+		assert!(!void.contains("return reg"));
+		assert!(trap.contains("local reg_0\n"));
+		assert!(trap.contains("return reg_0\n"));
+	}
+}
+
+#[test]
+fn vararg_parameter_list() {
+	let source = r#"
+		(module
+			(func (export "named") (param i32 i32))
+			(func (export "vararg") (param i32 i32))
+		)
+	"#;
+
+	let output = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			vararg_set: [1].into(),
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains("FUNC_LIST[0] = function(loc_0, loc_1)\n"));
+	assert!(output.contains("FUNC_LIST[1] = function(...)\n\tlocal loc_0, loc_1 = ...\n"));
+
+	let output = luau_with(
+		source,
+		&codegen_luau::Options {
+			vararg_set: [1].into(),
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains("FUNC_LIST[0] = function(loc_0, loc_1)\n"));
+	assert!(output.contains("FUNC_LIST[1] = function(...)\n\tlocal loc_0, loc_1 = ...\n"));
+}
+
+#[test]
+fn float_constant_round_trip() {
+	let source = r#"
+		(module
+			(func (export "f32_tenth") (result f32) f32.const 0.1)
+			(func (export "f32_subnormal") (result f32) f32.const 0x1p-149)
+			(func (export "f64_subnormal") (result f64) f64.const 0x1p-1074)
+			(func (export "f64_inf") (result f64) f64.const inf)
+			(func (export "f64_neg_inf") (result f64) f64.const -inf)
+			(func (export "f64_nan") (result f64) f64.const nan)
+			(func (export "f64_neg_nan") (result f64) f64.const -nan)
+			(func (export "f64_payload") (result f64) f64.const nan:0x4)
+			(func (export "f32_payload") (result f32) f32.const nan:0x200000)
+		)
+	"#;
+
+	let exact = [
+		("1.0000000149011612e-1", f64::from(0.1_f32)),
+		("1.401298464324817e-45", f64::from(f32::from_bits(1))),
+		("5e-324", f64::from_bits(1)),
+	];
+
+	for output in [luajit(source), luau(source)] {
+		for (literal, number) in exact {
+			assert!(output.contains(&format!("reg_0 = {literal}\n")));
+			assert_eq!(literal.parse::<f64>().unwrap().to_bits(), number.to_bits());
+		}
+
+		assert!(output.contains("reg_0 = math.huge\n"));
+		assert!(output.contains("reg_0 = -math.huge\n"));
+		assert!(output.contains("reg_0 = -(0.0 / 0.0)\n"));
+		assert!(output.contains("reg_0 = (0.0 / 0.0)\n"));
+	}
+
+	// Division only gives the canonical NaN, and its sign is up to the host
+	let output = luajit(source);
+
+	assert!(output.contains("reg_0 = rt.reinterpret.f64_i64(9218868437227405316LL)\n"));
+	assert!(output.contains("reg_0 = rt.reinterpret.f32_i32(2141192192)\n"));
+
+	let output = luau(source);
+
+	assert!(output.contains("reg_0 = rt.reinterpret.f64_i64(i64_from_u32(4, 2146435072))\n"));
+	assert!(output.contains("reg_0 = rt.reinterpret.f32_i32(2141192192)\n"));
+}
+
+#[test]
+fn data_runs_as_fill() {
+	let source = r#"
+		(module
+			(memory 1)
+			(data (i32.const 8) "ab\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00cd")
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(output.contains("rt.store.string(MEMORY_LIST[0], 8,\"ab\")\n"));
+		assert!(output.contains("rt.store.fill(MEMORY_LIST[0], 8 + 2, 38, 0)\n"));
+		assert!(output.contains("rt.store.string(MEMORY_LIST[0], 8 + 40,\"cd\")\n"));
+	}
+}
+
+#[test]
+fn call_indirect_element_check() {
+	let source = r#"
+		(module
+			(type $void (func))
+			(table 2 funcref)
+			(func (export "call") (param i32)
+				local.get 0
+				call_indirect (type $void)
+			)
+		)
+	"#;
+
+	let check = concat!(
+		"if not call_func then error(call_index >= 0 and call_index < TABLE_LIST[0].min",
+		r#" and "uninitialized element" or "undefined element") end"#,
+	);
+
+	for output in [luajit(source), luau(source)] {
+		let mut lines = output.lines().map(str::trim).skip_while(|&v| v != "do");
+
+		assert_eq!(lines.next(), Some("do"));
+		assert_eq!(lines.next(), Some("local call_index = loc_0"));
+		assert_eq!(
+			lines.next(),
+			Some("local call_func = TABLE_LIST[0].data[call_index]")
+		);
+		assert_eq!(lines.next(), Some(check));
+		assert_eq!(lines.next(), Some("call_func()"));
+		assert_eq!(lines.next(), Some("end"));
+		assert_eq!(output.matches("loc_0").count(), 2);
+	}
+}
+
+#[test]
+fn memory_accessor_source() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func (export "copy") (param i32 i32)
+				local.get 0
+				local.get 1
+				i32.load
+				i32.store
+			)
+		)
+	"#;
+
+	let output = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			load_source: Some("HostMem.read".into()),
+			store_source: Some("HostMem.write".into()),
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains("local load_i32 = HostMem.read.i32\n"));
+	assert!(output.contains("local store_i32 = HostMem.write.i32\n"));
+
+	let output = luau_with(
+		source,
+		&codegen_luau::Options {
+			load_source: Some("HostMem.read".into()),
+			store_source: Some("HostMem.write".into()),
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains("local load_i32 = HostMem.read.i32\n"));
+	assert!(output.contains("local store_i32 = HostMem.write.i32\n"));
+	assert!(luau(source).contains("local load_i32 = rt.load.i32\n"));
+}
+
+#[test]
+fn merged_assignments() {
+	let source = r#"
+		(module
+			(func (export "independent") (param i32 i32)
+				(local.set 0 (i32.const 1))
+				(local.set 1 (i32.const 2))
+			)
+			(func (export "dependent") (param i32 i32)
+				(local.set 0 (i32.const 1))
+				(local.set 1 (local.get 0))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		let (independent, dependent) = output.split_once("FUNC_LIST[1]").unwrap();
+
+		assert!(independent.contains("loc_0, loc_1 = 1, 2\n"));
+		assert!(dependent.contains("loc_0 = 1\n"));
+		assert!(dependent.contains("loc_1 = loc_0\n"));
+	}
+}
+
+#[test]
+fn function_stats() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func $helper)
+			(func (export "copy") (param i32 i32)
+				(i32.store (local.get 0) (i32.load (local.get 1)))
+				(call $helper)
+				(br_if 0 (local.get 0))
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+
+	let luajit: Vec<_> = codegen_luajit::from_module_typed(
+		&wasm,
+		&type_info,
+		&codegen_luajit::Options::default(),
+		&mut std::io::sink(),
+	)
+	.unwrap()
+	.into_iter()
+	.map(|v| (v.index, v.loads, v.stores, v.calls, v.branches))
+	.collect();
+
+	let luau: Vec<_> = codegen_luau::from_module_typed(
+		&wasm,
+		&type_info,
+		&codegen_luau::Options::default(),
+		&mut std::io::sink(),
+	)
+	.unwrap()
+	.into_iter()
+	.map(|v| (v.index, v.loads, v.stores, v.calls, v.branches))
+	.collect();
+
+	for stats in [luajit, luau] {
+		assert_eq!(stats, [(0, 0, 0, 0, 0), (1, 1, 1, 1, 1)]);
+	}
+}
+
+#[test]
+fn immutable_global_inlined() {
+	let source = r#"
+		(module
+			(global $constant i32 (i32.const 42))
+			(global $mutable (mut i32) (i32.const 7))
+			(func (export "constant") (result i32) global.get $constant)
+			(func (export "mutable") (result i32) global.get $mutable)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		let (constant, mutable) = output.split_once("FUNC_LIST[1]").unwrap();
+
+		assert!(constant.contains("reg_0 = 42\n"));
+		assert!(mutable.contains("reg_0 = GLOBAL_LIST[1].value\n"));
+	}
+}
+
+#[test]
+fn transformed_func_list() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func (export "store")
+				(i32.store (i32.const 0) (i32.const 1))
+				(i32.store (i32.const 4) (i32.const 2))
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+	let mut func_list: Vec<_> = wasm
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i, v).unwrap())
+		.collect();
+
+	func_list[0].code_mut().code_mut().remove(0);
+
+	let mut output = Vec::new();
+
+	codegen_luajit::from_func_list(
+		&wasm,
+		&type_info,
+		&func_list,
+		&codegen_luajit::Options::default(),
+		&mut output,
+	)
+	.unwrap();
+
+	let output = String::from_utf8(output).unwrap();
+
+	assert!(!output.contains("store_i32(memory_at_0, 0, 1)"));
+	assert!(output.contains("store_i32(memory_at_0, 4, 2)"));
+}
+
+#[test]
+fn constructed_statements() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func (export "store") (param i32)
+				(i32.store (i32.const 0) (i32.const 1))
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+	let mut func_list: Vec<_> = wasm
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i, v).unwrap())
+		.collect();
+
+	let value = BinOp::new(
+		BinOpType::Add_I32,
+		Expression::GetLocal(Local::new(0)),
+		Expression::Value(Value::I32(3)),
+	);
+	let store = StoreAt::new(
+		StoreType::I32,
+		0,
+		8,
+		Expression::Value(Value::I32(0)),
+		Expression::BinOp(value),
+	);
+
+	func_list[0].code_mut().code_mut()[0] = Statement::StoreAt(store);
+
+	let mut output = Vec::new();
+
+	codegen_luau::from_func_list(
+		&wasm,
+		&type_info,
+		&func_list,
+		&codegen_luau::Options::default(),
+		&mut output,
+	)
+	.unwrap();
+
+	let output = String::from_utf8(output).unwrap();
+
+	assert!(!output.contains("store_i32(memory_at_0, 0, 1)"));
+	assert!(output.contains("store_i32(memory_at_0, 0 + 8, add_i32(loc_0, 3))"));
+}
+
+#[test]
+fn parameter_check() {
+	let source = r#"
+		(module
+			(func (export "checked") (param i32 i64 f64))
+		)
+	"#;
+
+	let output = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			assert_set: [0].into(),
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains(r#"assert(type(loc_0) == "number", "parameter 0 must be a number")"#));
+	assert!(output.contains(r#"assert(type(loc_1) == "cdata", "parameter 1 must be a cdata")"#));
+	assert!(output.contains(r#"assert(type(loc_2) == "number", "parameter 2 must be a number")"#));
+	assert!(!luajit(source).contains("assert("));
+
+	let output = luau_with(
+		source,
+		&codegen_luau::Options {
+			assert_set: [0].into(),
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains(r#"assert(type(loc_1) == "vector", "parameter 1 must be a vector")"#));
+}
+
+#[test]
+fn sorted_func_list() {
+	let source = r#"
+		(module
+			(func (export "zeta"))
+			(func)
+			(func (export "alpha"))
+		)
+	"#;
+
+	let position =
+		|output: &str, index: usize| output.find(&format!("FUNC_LIST[{index}] =")).unwrap();
+
+	let output = luajit(source);
+
+	assert!(position(&output, 0) < position(&output, 1));
+	assert!(position(&output, 1) < position(&output, 2));
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			sort_func_list: true,
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			sort_func_list: true,
+			..Default::default()
+		},
+	);
+
+	for output in [luajit, luau] {
+		assert!(position(&output, 2) < position(&output, 0));
+		assert!(position(&output, 0) < position(&output, 1));
+	}
+}
+
+#[test]
+fn br_table_targets_agree() {
+	let source = r#"
+		(module
+			(func (export "select") (param i32) (result i32)
+				(block
+					(block
+						(block
+							(block
+								(br_table 3 1 2 0 1 (local.get 0))
+							)
+							(return (i32.const 0))
+						)
+						(return (i32.const 1))
+					)
+					(return (i32.const 2))
+				)
+				(i32.const 3)
+			)
+		)
+	"#;
+
+	let search = |output: String| -> Vec<String> {
+		output
+			.lines()
+			.map(str::trim)
+			.filter(|v| v.contains("temp ") || v.starts_with("return { [0]"))
+			.filter(|v| !v.starts_with("local"))
+			.map(str::to_string)
+			.collect()
+	};
+
+	let luajit = search(luajit(source));
+	let luau = search(luau(source));
+
+	assert!(luajit.contains(&"return { [0] = 3, 1, 2, 0, }".to_string()));
+	assert!(luajit.contains(&"temp = br_map[1][loc_0] or 1".to_string()));
+	assert_eq!(luajit, luau);
+}
+
+#[test]
+fn luau_table_create() {
+	let source = r#"
+		(module
+			(table 64 funcref)
+			(table 2 funcref)
+			(func (export "select") (param i32)
+				(block
+					(br_table 0 0 (local.get 0))
+				)
+			)
+		)
+	"#;
+
+	let output = luau_with(
+		source,
+		&codegen_luau::Options {
+			table_create_threshold: Some(1),
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains("local br_map = table.create(1)\n"));
+	assert!(output.contains("TABLE_LIST[0] = { min = 64, max = 65535, data = table.create(64) }\n"));
+
+	let output = luau_with(
+		source,
+		&codegen_luau::Options {
+			table_create_threshold: Some(16),
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains("local br_map = {}\n"));
+	assert!(output.contains("TABLE_LIST[0] = { min = 64, max = 65535, data = table.create(64) }\n"));
+	assert!(output.contains("TABLE_LIST[1] = { min = 2, max = 65535, data = {} }\n"));
+}
+
+#[test]
+fn br_if_carries_value() {
+	let source = r#"
+		(module
+			(func (export "pick") (param i32) (result i32)
+				(block (result i32)
+					(i32.const 1)
+					(i32.const 7)
+					(br_if 0 (local.get 0))
+					(i32.add)
+				)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		let mut lines = output
+			.lines()
+			.map(str::trim)
+			.skip_while(|&v| v != "if loc_0 ~= 0 then");
+
+		assert_eq!(lines.next(), Some("if loc_0 ~= 0 then"));
+		assert_eq!(lines.next(), Some("reg_0 = reg_1"));
+	}
+}
+
+#[test]
+fn call_depth_guard() {
+	let source = r#"
+		(module
+			(func $recurse (export "recurse")
+				(call $recurse)
+			)
+		)
+	"#;
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			depth_limit: Some(200),
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			depth_limit: Some(200),
+			depth_counter: Some("_G.DEPTH".into()),
+			..Default::default()
+		},
+	);
+
+	for (output, counter) in [(luajit, "CALL_DEPTH"), (luau, "_G.DEPTH")] {
+		assert_eq!(
+			output.contains("local CALL_DEPTH = 0\n"),
+			counter == "CALL_DEPTH"
+		);
+		assert!(output.contains(&format!("if {counter} >= 200 then\n")));
+		assert!(output.contains(&format!("{counter} = {counter} + 1\n")));
+		assert!(output.contains(&format!("{counter} = {counter} - 1\n")));
+		assert!(output.contains(r#"["recurse"] = depth_guard(FUNC_LIST[0]),"#));
+	}
+}
+
+#[test]
+fn sub_word_load_helpers() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func (export "i32") (result i32 i32 i32 i32)
+				(i32.load8_s (i32.const 0))
+				(i32.load8_u (i32.const 0))
+				(i32.load16_s (i32.const 0))
+				(i32.load16_u (i32.const 0))
+			)
+			(func (export "i64") (result i64 i64 i64 i64 i64 i64)
+				(i64.load8_s (i32.const 0))
+				(i64.load8_u (i32.const 0))
+				(i64.load16_s (i32.const 0))
+				(i64.load16_u (i32.const 0))
+				(i64.load32_s (i32.const 0))
+				(i64.load32_u (i32.const 0))
+			)
+		)
+	"#;
+
+	let list = [
+		"i32_i8", "i32_u8", "i32_i16", "i32_u16", "i64_i8", "i64_u8", "i64_i16", "i64_u16",
+		"i64_i32", "i64_u32",
+	];
+
+	for output in [luajit(source), luau(source)] {
+		for name in list {
+			assert!(output.contains(&format!("local load_{name} = rt.load.{name}\n")));
+			assert!(output.contains(&format!("load_{name}(memory_at_0, 0)")));
+		}
+	}
+}
+
+#[test]
+fn pure_function_chunk() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func (export "add") (param i32 i32) (result i32)
+				(i32.add (local.get 0) (local.get 1))
+			)
+			(func (export "load") (result i32)
+				(i32.load (i32.const 0))
+			)
+			(func (export "call") (result i32)
+				(call 0 (i32.const 1) (i32.const 2))
+			)
+			(tag $e)
+			(func (export "guard") (result i32)
+				(try (result i32)
+					(do (i32.const 1))
+					(catch $e (i32.const 0))
+				)
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+	let func_list: Vec<_> = wasm
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i, v).unwrap())
+		.collect();
+
+	let mut luajit = Vec::new();
+	let mut luau = Vec::new();
+
+	for (func, expected) in func_list.iter().zip([true, false, false, false]) {
+		assert_eq!(
+			codegen_luajit::from_pure_func(func, &mut luajit).unwrap(),
+			expected
+		);
+		assert_eq!(
+			codegen_luau::from_pure_func(func, &mut luau).unwrap(),
+			expected
+		);
+	}
+
+	for output in [luajit, luau] {
+		let output = String::from_utf8(output).unwrap();
+
+		assert!(output.starts_with("local add_i32 = rt.add.i32\n"));
+		assert!(output.contains("return function(loc_0, loc_1)\n"));
+		assert!(!output.contains("MEMORY_LIST"));
+		assert!(!output.contains("FUNC_LIST"));
+	}
+}
+
+#[test]
+fn no_trailing_whitespace() {
+	let source = r#"
+		(module
+			(memory 1)
+			(global $g (mut i32) (i32.const 0))
+			(table 1 funcref)
+			(func (export "f") (param i32) (result i32)
+				(block
+					(loop
+						(br_if 1 (local.get 0))
+						(if (i32.eqz (local.get 0))
+							(then (global.set $g (i32.const 2)))
+						)
+						(br_table 0 1 (local.get 0))
+					)
+				)
+				(i32.store (i32.const 0) (local.get 0))
+				(select (local.get 0) (i32.const 2) (i32.load (i32.const 4)))
+				(call_indirect (param i32) (result i32) (i32.const 0))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		let trailing = output.lines().filter(|v| v.ends_with([' ', '\t'])).count();
+
+		assert_eq!(trailing, 0);
+		assert!(!output.contains("  "));
+	}
+}
+
+#[test]
+fn function_reference_constant() {
+	let source = r#"
+		(module
+			(type $t (func (result i32)))
+			(table 2 funcref)
+			(global funcref (ref.func 0))
+			(elem (i32.const 0) funcref (ref.func 1) (ref.null func))
+			(func (result i32) i32.const 1)
+			(func (export "call") (result i32)
+				(call_indirect (type $t) (i32.const 0))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(output.contains("GLOBAL_LIST[0] = { value = FUNC_LIST[0] }\n"));
+		assert!(output.contains("local data = { FUNC_LIST[1],nil, }\n"));
+	}
+}
+
+#[test]
+fn signed_and_unsigned_shift() {
+	let source = r#"
+		(module
+			(func (export "i32_s") (result i32)
+				(i32.shr_s (i32.const 0x80000000) (i32.const 4))
+			)
+			(func (export "i32_u") (result i32)
+				(i32.shr_u (i32.const 0x80000000) (i32.const 4))
+			)
+			(func (export "i64_s") (result i64)
+				(i64.shr_s (i64.const 0x8000000000000000) (i64.const 4))
+			)
+			(func (export "i64_u") (result i64)
+				(i64.shr_u (i64.const 0x8000000000000000) (i64.const 4))
+			)
+		)
+	"#;
+
+	let luajit = luajit(source);
+
+	assert!(luajit.contains("local shr_i32 = bit.arshift\n"));
+	assert!(luajit.contains("local shr_i64 = bit.arshift\n"));
+	assert!(luajit.contains("local shr_u32 = bit.rshift\n"));
+	assert!(luajit.contains("local shr_u64 = bit.rshift\n"));
+	assert!(luajit.contains("shr_i32(-2147483648, 4)"));
+	assert!(luajit.contains("shr_u32(-2147483648, 4)"));
+	assert!(luajit.contains("shr_i64(-9223372036854775808LL, 4LL)"));
+	assert!(luajit.contains("shr_u64(-9223372036854775808LL, 4LL)"));
+
+	let luau = luau(source);
+
+	assert!(luau.contains("local shr_i32 = rt.shr.i32\n"));
+	assert!(luau.contains("local shr_u32 = rt.shr.u32\n"));
+	assert!(luau.contains("shr_i32(2147483648, 4)"));
+	assert!(luau.contains("shr_u32(2147483648, 4)"));
+	assert!(luau.contains("shr_i64(i64_from_u32(0, 2147483648), i64_from_u32(4, 0))"));
+	assert!(luau.contains("shr_u64(i64_from_u32(0, 2147483648), i64_from_u32(4, 0))"));
+}
+
+#[test]
+fn transpile_bytes() {
+	let data = encode(r#"(module (func (export "f")))"#);
+
+	let luajit = codegen_luajit::transpile(&data).unwrap();
+	let luau = codegen_luau::transpile(&data).unwrap();
+
+	assert!(luajit.contains("-- Options: default\nlocal rt = (function()\n"));
+	assert!(luau.contains("-- Options: default\nlocal Integer = (function()\n"));
+
+	for output in [luajit, luau] {
+		assert!(output.contains("return function(wasm)\n"));
+		assert!(output.contains(r#"["f"] = FUNC_LIST[0],"#));
+	}
+
+	assert!(codegen_luajit::transpile(b"\0asm").is_err());
+	assert!(codegen_luau::transpile(b"\0asm").is_err());
+}
+
+#[test]
+fn trap_function_override() {
+	let source = r#"
+		(module
+			(type $t (func))
+			(table 1 funcref)
+			(func (export "trap") unreachable)
+			(func (export "call") (call_indirect (type $t) (i32.const 0)))
+		)
+	"#;
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			trap_function: Some("__wasm_trap".into()),
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			trap_function: Some("__wasm_trap".into()),
+			..Default::default()
+		},
+	);
+
+	for output in [luajit, luau] {
+		assert!(output.contains(r#"__wasm_trap("out of code bounds")"#));
+		assert!(output.contains("if not call_func then __wasm_trap("));
+		assert!(!output.contains("error(\"out of code bounds\")"));
+	}
+}
+
+#[test]
+fn shared_address_computed_once() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func (export "sum") (param i32 i32) (result i32)
+				(i32.store (i32.add (local.get 1) (i32.const 4)) (i32.const 0))
+				(i32.add
+					(i32.load (i32.add (local.get 0) (i32.const 4)))
+					(i32.add
+						(i32.load (i32.add (local.get 0) (i32.const 4)))
+						(i32.load offset=8 (i32.add (local.get 0) (i32.const 4)))
+					)
+				)
+			)
+			(func (export "moved") (param i32) (result i32)
+				(local.set 0 (i32.load (i32.add (local.get 0) (i32.const 4))))
+				(i32.load (i32.add (local.get 0) (i32.const 4)))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		let (sum, moved) = output.split_once("FUNC_LIST[1]").unwrap();
+
+		assert!(sum.contains("local addr_0 = add_i32(loc_0, 4)\n"));
+		assert!(sum.contains("load_i32(memory_at_0, addr_0 + 8)"));
+		assert!(sum.contains("store_i32(memory_at_0, add_i32(loc_1, 4), 0)"));
+		assert!(!sum.contains("local addr_1"));
+		assert!(!moved.contains("local addr_0"));
+	}
+}
+
+#[test]
+fn flat_table_mode() {
+	let source = r#"
+		(module
+			(type $t (func (result i32)))
+			(table 2 funcref)
+			(elem (i32.const 0) 0 1)
+			(func (result i32) i32.const 1)
+			(func (export "call") (param i32) (result i32)
+				(call_indirect (type $t) (local.get 0))
+			)
+		)
+	"#;
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			flat_table: true,
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			flat_table: true,
+			..Default::default()
+		},
+	);
+
+	assert!(luajit.contains("local TABLE = table_new(2, 0)\n"));
+	assert!(luau.contains("local TABLE = {}\n"));
+
+	for output in [luajit, luau] {
+		assert!(!output.contains("TABLE_LIST"));
+		assert!(output.contains("local target = TABLE\n"));
+		assert!(output.contains("local offset = 1 + 0\n"));
+		assert!(output.contains("local call_func = TABLE[call_index + 1]\n"));
+		assert!(output.contains(r#"call_index < 2 and "uninitialized element""#));
+		assert!(output.contains("reg_0 = call_func()"));
+	}
+
+	let exported = source.replace("(table 2 funcref)", r#"(table (export "t") 2 funcref)"#);
+	let output = luajit_with(
+		&exported,
+		&codegen_luajit::Options {
+			flat_table: true,
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains("local call_func = TABLE_LIST[0].data[call_index]\n"));
+}
+
+#[test]
+fn luau_analyzer_directive() {
+	let source = r#"(module (func (export "f")))"#;
+
+	let output = luau_with(
+		source,
+		&codegen_luau::Options {
+			analyzer_directive: Some("nocheck".into()),
+			..Default::default()
+		},
+	);
+
+	assert!(output.starts_with("--!nocheck\n"));
+	assert!(!luau(source).starts_with("--!"));
+}
+
+#[test]
+fn memory64_addressing() {
+	let source = r#"
+		(module
+			(memory i64 1)
+			(func (export "store") (param i64)
+				(i64.store offset=8 (i64.const 0x100000000) (local.get 0))
+			)
+			(func (export "grow") (result i64)
+				(drop (memory.grow (i64.const 1)))
+				(memory.size)
+			)
+		)
+	"#;
+
+	let luajit = luajit(source);
+
+	assert!(luajit.contains("store_i64(memory_at_0, 4294967296LL + 8, loc_0)"));
+	assert!(luajit.contains("rt.allocator.grow(memory_at_0, tonumber(1LL)) + 0LL"));
+	assert!(luajit.contains("(memory_at_0.min + 0LL)"));
+
+	let luau = luau(source);
+
+	assert!(
+		luau.contains("store_i64(memory_at_0, rt.convert.f64_u64(i64_from_u32(0, 1)) + 8, loc_0)")
+	);
+	assert!(luau.contains("rt.extend.i64_i32(rt.allocator.grow(memory_at_0, rt.convert.f64_u64("));
+	assert!(luau.contains("rt.extend.i64_u32(memory_at_0.min)"));
+}
+
+#[test]
+fn memory_growth_in_place() {
+	// LuaJIT allocates the maximum size up front and Luau keeps a sparse
+	// table, so neither reallocates or copies pages when growing
+	assert!(codegen_luajit::RUNTIME.contains("ffi.C.calloc(max, WASM_PAGE_SIZE)"));
+	assert!(!codegen_luajit::RUNTIME.contains("ffi.C.realloc"));
+	assert!(codegen_luau::RUNTIME.contains("return { min = min, max = max, data = {} }"));
+}
+
+#[test]
+fn entry_and_exit_hooks() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32) (result i32)
+				(if (local.get 0) (then (return (i32.const 1))))
+				(i32.const 2)
+			)
+		)
+	"#;
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			entry_hook: Some(Box::new(|i, w| writeln!(w, "\ttrace_enter({i})"))),
+			exit_hook: Some(Box::new(|i, w| writeln!(w, "\ttrace_leave({i})"))),
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			entry_hook: Some(Box::new(|i, w| writeln!(w, "\ttrace_enter({i})"))),
+			exit_hook: Some(Box::new(|i, w| writeln!(w, "\ttrace_leave({i})"))),
+			..Default::default()
+		},
+	);
+
+	for output in [luajit, luau] {
+		let (head, tail) = output.split_once("\ttrace_enter(0)\n").unwrap();
+
+		assert!(head.contains("local reg_0\n"));
+		assert_eq!(tail.matches("trace_leave(0)").count(), 1);
+		assert!(tail.contains("\ttrace_leave(0)\n\treturn reg_0\n"));
+		assert!(!tail.contains("\treturn reg_0\n\t"));
+	}
+}
+
+#[test]
+fn nan_comparisons() {
+	let source = r#"
+		(module
+			(func (export "lt") (param f64) (result i32) (f64.lt (local.get 0) (f64.const nan)))
+			(func (export "le") (param f64) (result i32) (f64.le (local.get 0) (f64.const nan)))
+			(func (export "gt") (param f32) (result i32) (f32.gt (local.get 0) (f32.const nan)))
+			(func (export "ge") (param f32) (result i32) (f32.ge (local.get 0) (f32.const nan)))
+		)
+	"#;
+
+	let luajit = luajit(source);
+	let luau = luau(source);
+
+	for symbol in ["<", "<=", ">", ">="] {
+		let condition = format!("loc_0 {symbol} -(0.0 / 0.0)");
+
+		assert!(luajit.contains(&format!("reg_0 = ({condition} and 1 or 0)\n")));
+		assert!(luau.contains(&format!("reg_0 = (if {condition} then 1 else 0)\n")));
+	}
+}
+
+#[test]
+fn recursive_components() {
+	let source = r#"
+		(module
+			(type $t (func))
+			(import "env" "host" (func))
+			(table 1 funcref)
+			(elem (i32.const 0) 5)
+			(func (call 2))
+			(func (call 3))
+			(func (call 2) (call 0))
+			(func (call_indirect (type $t) (i32.const 0)))
+			(func (call 5))
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+	let func_list: Vec<_> = wasm
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i + 1, v).unwrap())
+		.collect();
+
+	let graph = CallGraph::new(&wasm, &func_list);
+	let recursive: Vec<_> = graph.recursive_list().collect();
+
+	assert_eq!(graph.callee_list(4), [5]);
+	assert_eq!(recursive, [&[2, 3][..], &[5][..]]);
+	assert!(!graph.is_recursive(0));
+	assert!(!graph.is_recursive(1));
+	assert!(graph.is_recursive(3));
+	assert!(!graph.is_recursive(4));
+}
+
+#[test]
+fn shared_analyzers() {
+	let source = r"
+		(module
+			(type $t (func (param i32) (result i32)))
+			(table 1 funcref)
+			(global $g (mut i32) (i32.const 0))
+			(func (param i32) (result i32)
+				(i32.add (local.get 0) (i32.const 0))
+			)
+			(func (param i32) (result i32)
+				(call_indirect (type $t) (local.get 0) (i32.const 0))
+				(call_indirect (type $t) (local.get 0) (i32.const 0))
+				(i32.add)
+			)
+			(func (result i32)
+				(global.get $g)
+			)
+		)
+	";
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+	let func_list: Vec<_> = wasm
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i, v).unwrap())
+		.collect();
+
+	let purity: Vec<_> = func_list.iter().map(analyzer::pure::visit).collect();
+	let cache_count: Vec<_> = func_list
+		.iter()
+		.map(analyzer::call_indirect::visit)
+		.collect();
+
+	assert_eq!(purity, [true, false, false]);
+	assert_eq!(cache_count, [0, 2, 0]);
+	assert!(func_list
+		.iter()
+		.all(|v| analyzer::bounds::visit(v, &wasm).is_none()));
+
+	let Statement::SetTemporary(set) = &func_list[0].code().code()[0] else {
+		panic!("the sum must be set");
+	};
+	let Expression::BinOp(add) = set.value() else {
+		panic!("the sum must be an addition");
+	};
+
+	assert!(matches!(
+		analyzer::identity::as_identity(add),
+		Some(Expression::GetLocal(local)) if local.var() == 0
+	));
+
+	// Both calls are live at the addition, so neither shares a register
+	let (register_map, num_register) = analyzer::coalesce::visit(&func_list[1]);
+
+	assert_eq!(num_register, 2);
+	assert_ne!(register_map[0], register_map[1]);
+}
+
+#[test]
+fn indirect_call_targets() {
+	let source = r#"
+		(module
+			(type $t (func))
+			(table 1 funcref)
+			(global funcref (ref.func $global))
+			(elem declare func $stored)
+			(func $call (call_indirect (type $t) (i32.const 0)))
+			(func $stored (table.set (i32.const 0) (ref.func $stored)))
+			(func $exported (export "e") (call $call))
+			(func $global (call $call))
+			(func $alone (call $call))
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+
+	builder.set_trap_unsupported(true);
+
+	let func_list: Vec<_> = wasm
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i, v).unwrap())
+		.collect();
+
+	// None are in an active segment, yet each may be stored into the table
+	let graph = CallGraph::new(&wasm, &func_list);
+
+	assert_eq!(graph.callee_list(0), [1, 2, 3]);
+	assert!(graph.is_recursive(2));
+	assert!(graph.is_recursive(3));
+	assert!(!graph.is_recursive(4));
+}
+
+#[test]
+fn local_tee_value() {
+	let source = r#"
+		(module
+			(func (export "inline") (param i32) (result i32) (local i32)
+				(i32.add (local.tee 1 (i32.mul (local.get 0) (i32.const 3))) (i32.const 1))
+			)
+			(func (export "overwritten") (param i32) (result i32) (local i32)
+				(local.tee 1 (i32.mul (local.get 0) (i32.const 3)))
+				(local.set 1 (i32.const 9))
+				(local.get 1)
+				(i32.add)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		let (inline, overwritten) = output.split_once("FUNC_LIST[1]").unwrap();
+		let inline: Vec<_> = inline.lines().map(str::trim).collect();
+		let overwritten: Vec<_> = overwritten.lines().map(str::trim).collect();
+
+		assert!(inline.contains(&"loc_1 = mul_i32(loc_0, 3)"));
+		assert!(inline.contains(&"reg_0 = add_i32(loc_1, 1)"));
+		assert!(overwritten.contains(&"loc_1 = mul_i32(loc_0, 3)"));
+		assert!(overwritten.contains(&"reg_0, loc_1 = loc_1, 9"));
+		assert!(overwritten.contains(&"reg_0 = add_i32(reg_0, loc_1)"));
+	}
+}
+
+#[test]
+fn packed_import_results() {
+	let source = r#"
+		(module
+			(import "env" "pair" (func (result i32 i32)))
+			(import "env" "single" (func (result i32)))
+			(func (export "f") (result i32)
+				(call 0)
+				(call 1)
+				(i32.add)
+				(i32.add)
+			)
+		)
+	"#;
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			packed_result_set: [0].into(),
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			packed_result_set: [0].into(),
+			..Default::default()
+		},
+	);
+
+	assert!(luajit.contains("reg_0, reg_1 = unpack(FUNC_LIST[0]())\n"));
+	assert!(luau.contains("reg_0, reg_1 = table.unpack(FUNC_LIST[0]())\n"));
+
+	for output in [luajit, luau] {
+		assert!(output.contains("reg_2 = FUNC_LIST[1]()\n"));
+	}
+}
+
+#[test]
+fn zero_temporaries() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32) (result i32)
+				(i32.add (local.get 0) (i32.const 1))
+			)
+		)
+	"#;
+
+	let zeroed_luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			zero_temporaries: true,
+			..Default::default()
+		},
+	);
+
+	let zeroed_luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			zero_temporaries: true,
+			..Default::default()
+		},
+	);
+
+	for output in [zeroed_luajit, zeroed_luau] {
+		assert!(output.contains("\tlocal reg_0 = 0\n"));
+	}
+
+	assert!(luajit(source).contains("\tlocal reg_0\n"));
+}
+
+#[test]
+fn deferred_init() {
+	let source = r#"
+		(module
+			(import "env" "f" (func))
+			(memory (export "memory") 1)
+			(start 0)
+		)
+	"#;
+
+	let deferred_luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			deferred_init: true,
+			..Default::default()
+		},
+	);
+
+	let deferred_luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			deferred_init: true,
+			..Default::default()
+		},
+	);
+
+	for output in [deferred_luajit, deferred_luau] {
+		let (init, instantiate) = output.split_once("return function(wasm)\n").unwrap();
+
+		assert!(init.contains("local function INIT()\n\trun_init_code()\n"));
+		assert!(init.contains("\tFUNC_LIST[0]()\n\treturn {\n"));
+		assert!(instantiate.contains(r#"FUNC_LIST[0] = wasm["env"].func_list["f"]"#));
+		assert!(instantiate.ends_with("\treturn INIT\nend\n"));
+		assert!(!instantiate.contains("run_init_code"));
+	}
+
+	assert!(!luajit(source).contains("INIT"));
+}
+
+#[test]
+fn checked_arithmetic() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32 i64) (result i64)
+				(i64.mul
+					(i64.extend_i32_u (i32.add (local.get 0) (i32.const 1)))
+					(local.get 1)
+				)
+			)
+		)
+	"#;
+
+	let checked_luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			checked_arithmetic: true,
+			..Default::default()
+		},
+	);
+
+	let checked_luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			checked_arithmetic: true,
+			..Default::default()
+		},
+	);
+
+	for output in [checked_luajit, checked_luau] {
+		assert!(output.contains("rt.debug.checked_mul_i64("));
+		assert!(output.contains("rt.debug.checked_add_i32(loc_0, 1)"));
+	}
+
+	assert!(!luau(source).contains("rt.debug"));
+}
+
+#[test]
+fn module_manifest() {
+	let source = r#"
+		(module
+			(import "env" "log" (func (param i32)))
+			(import "env" "memory" (memory 1))
+			(import "env" "base" (global i32))
+			(table 2 funcref)
+			(memory 3 5)
+			(func $f (export "run"))
+			(export "table" (table 0))
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let manifest = Manifest::from_module(&wasm);
+
+	let import_list: Vec<_> = manifest
+		.import_list
+		.iter()
+		.map(|v| (v.name, v.index))
+		.collect();
+
+	assert_eq!(import_list, [("log", 0), ("memory", 0), ("base", 0)]);
+	assert_eq!(manifest.find_func_export("run"), Some(1));
+	assert_eq!(manifest.export_list.len(), 2);
+	assert_eq!(
+		manifest.table_list,
+		[Limits {
+			initial: 2,
+			maximum: None
+		}]
+	);
+	assert_eq!(
+		manifest.memory_list,
+		[
+			Limits {
+				initial: 1,
+				maximum: None
+			},
+			Limits {
+				initial: 3,
+				maximum: Some(5)
+			},
+		]
+	);
+	assert_eq!(manifest.global_list.len(), 1);
+}
+
+#[test]
+fn manifest_import_types() {
+	let source = r#"
+		(module
+			(import "env" "add" (func (param i32 f64) (result i64)))
+			(import "env" "table" (table 1 4 funcref))
+			(import "env" "memory" (memory 2))
+			(import "env" "counter" (global (mut i32)))
+			(import "env" "limit" (global f32))
+			(import "env" "error" (tag (param i32)))
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let manifest = Manifest::from_module(&wasm);
+
+	let ty_list: Vec<_> = manifest.import_list.iter().map(|v| v.ty).collect();
+
+	assert_eq!(
+		ty_list,
+		[
+			ImportType::Func {
+				param_list: &[ValType::I32, ValType::F64],
+				result_list: &[ValType::I64],
+			},
+			ImportType::Table(Limits {
+				initial: 1,
+				maximum: Some(4)
+			}),
+			ImportType::Memory(Limits {
+				initial: 2,
+				maximum: None
+			}),
+			ImportType::Global {
+				content_type: ValType::I32,
+				mutable: true,
+			},
+			ImportType::Global {
+				content_type: ValType::F32,
+				mutable: false,
+			},
+			ImportType::Tag {
+				param_list: &[ValType::I32],
+			},
+		]
+	);
+	assert_eq!(manifest.import_list[5].index, 0);
+}
+
+#[test]
+fn luau_i64_equality_exact() {
+	let source = r#"
+		(module
+			(func (export "f") (param i64) (result i32)
+				(i32.and
+					(i64.eq (local.get 0) (i64.const 9007199254740993))
+					(i64.ne (local.get 0) (i64.const 9007199254740992))
+				)
+			)
+		)
+	"#;
+
+	// Both constants round to the same double, so they must stay as words
+	let output = luau(source);
+
+	assert!(output.contains("eq_i64(loc_0, i64_from_u32(1, 2097152))"));
+	assert!(output.contains("ne_i64(loc_0, i64_from_u32(0, 2097152))"));
+}
+
+#[test]
+fn ravi_annotations() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32 f64 i64) (result f32)
+				(local f32 externref)
+				(local.get 3)
+			)
+		)
+	"#;
+
+	let options = codegen_luajit::Options {
+		ravi_annotations: true,
+		..Default::default()
+	};
+	let output = luajit_with(source, &options);
+
+	assert!(output.contains("function(loc_0: integer, loc_1: number, loc_2)\n"));
+	assert!(output.contains("\tlocal loc_3: number = 0.0\n"));
+	assert!(output.contains("\tlocal loc_4 = nil\n"));
+	assert!(!output.contains("reg_0:"));
+
+	let options = codegen_luajit::Options {
+		vararg_set: [0].into(),
+		..options
+	};
+	let output = luajit_with(source, &options);
+
+	assert!(output.contains("\tlocal loc_0: integer, loc_1: number, loc_2 = ...\n"));
+	assert!(!luajit(source).contains(": integer"));
+}
+
+#[test]
+fn luau_i64_multiply_exact() {
+	let source = r#"
+		(module
+			(func (export "f") (result i32)
+				(i64.eq
+					(i64.mul (i64.const 0x123456789ABCDEF1) (i64.const 0x0FEDCBA987654321))
+					(i64.const 3613193367603040273)
+				)
+			)
+		)
+	"#;
+
+	// The product needs all 64 bits, so it must go through the two-word
+	// multiply of the runtime rather than a multiply of doubles. Only the
+	// emitted call is checked here, not the product the runtime computes
+	let output = luau(source);
+
+	assert!(output.contains("local mul_i64 = rt.mul.i64"));
+	assert!(output.contains(
+		"eq_i64(mul_i64(i64_from_u32(2596069105, 305419896), i64_from_u32(2271560481, 267242409)), i64_from_u32(1824968721, 841262137))"
+	));
+}
+
+#[test]
+fn narrow_loads_into_i64() {
+	let source = r#"
+		(module
+			(memory 1)
+			(data (i32.const 0) "\80")
+			(func (export "f") (result i64 i64 i64)
+				(i64.load8_s (i32.const 0))
+				(i64.load8_u (i32.const 0))
+				(i64.load32_s (i32.const 0))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(output.contains("load_i64_i8(memory_at_0, 0)"));
+		assert!(output.contains("load_i64_u8(memory_at_0, 0)"));
+		assert!(output.contains("load_i64_i32(memory_at_0, 0)"));
+		assert!(!output.contains("load_i32_i8"));
+	}
+}
+
+#[test]
+fn required_runtime_module() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32) (result i32)
+				(i32.add (local.get 0) (i32.const 1))
+			)
+		)
+	"#;
+
+	let module_luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			runtime_module: Some(r#""wasynth.runtime""#.into()),
+			..Default::default()
+		},
+	);
+
+	let module_luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			analyzer_directive: Some("native".into()),
+			runtime_module: Some("script.Runtime".into()),
+			..Default::default()
+		},
+	);
+
+	assert!(module_luajit.starts_with("local rt = require(\"wasynth.runtime\")\n"));
+	assert!(module_luau.starts_with("--!native\nlocal rt = require(script.Runtime)\n"));
+	assert!(!luajit(source).contains("require(\"wasynth"));
+}
+
+#[test]
+fn else_if_chain() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32) (result i32)
+				(if (result i32) (i32.eq (local.get 0) (i32.const 1))
+					(then (i32.const 10))
+					(else
+						(if (result i32) (i32.eq (local.get 0) (i32.const 2))
+							(then (i32.const 20))
+							(else
+								(if (result i32) (i32.eq (local.get 0) (i32.const 3))
+									(then (i32.const 30))
+									(else (i32.const 40))
+								)
+							)
+						)
+					)
+				)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(output.contains("elseif loc_0 == 2 then"));
+		assert!(output.contains("elseif loc_0 == 3 then"));
+		assert_eq!(output.matches("else\n").count(), 1);
+	}
+}
+
+#[test]
+fn unsupported_instruction_traps() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32) (result i32)
+				(block (result i32)
+					(local.get 0)
+					atomic.fence
+				)
+				(i32.add (i32.const 1))
+			)
+			(func (export "g") (result i32)
+				(i32.const 0)
+				atomic.fence
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+
+	builder.set_trap_unsupported(true);
+
+	let func_list: Vec<_> = wasm
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i, v).unwrap())
+		.collect();
+
+	let unsupported_list = builder.unsupported_list();
+
+	assert_eq!(unsupported_list.len(), 2);
+	assert_eq!(unsupported_list[0], (Some(0), "AtomicFence".into()));
+	assert_eq!(unsupported_list[1].0, Some(1));
+
+	let mut output = Vec::new();
+
+	codegen_luajit::from_func_list(
+		&wasm,
+		&type_info,
+		&func_list,
+		&codegen_luajit::Options::default(),
+		&mut output,
+	)
+	.unwrap();
+
+	let output = String::from_utf8(output).unwrap();
+
+	assert_eq!(
+		output
+			.matches(r#"error("unsupported: AtomicFence")"#)
+			.count(),
+		2
+	);
+	assert!(output.contains("\terror(\"unsupported: AtomicFence\")\n\treg_0 = add_i32(reg_0, 1)\n"));
+}
+
+#[test]
+fn coalesced_registers() {
+	let source = r#"
+		(module
+			(import "env" "g" (func $g (result i32)))
+			(func (export "f") (param i32) (result i32)
+				(i32.add (local.get 0) (call $g))
+				(i32.add (local.get 0) (call $g))
+				i32.mul
+			)
+		)
+	"#;
+
+	let coalesced_luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			coalesce_registers: true,
+			..Default::default()
+		},
+	);
+
+	let coalesced_luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			coalesce_registers: true,
+			..Default::default()
+		},
+	);
+
+	for output in [coalesced_luajit, coalesced_luau] {
+		assert_eq!(output.matches("local reg_").count(), 2);
+		assert!(output.contains("reg_0 = mul_i32(add_i32(loc_0, reg_0), add_i32(loc_0, reg_1))"));
+	}
+
+	for output in [luajit(source), luau(source)] {
+		assert_eq!(output.matches("local reg_").count(), 3);
+	}
+}
+
+#[test]
+fn coalesced_registers_split_assignments() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32 i32 i32 i32) (result i32)
+				(local.get 1)
+				(local.get 0)
+				(i32.const 5)
+				(local.set 0)
+				(drop)
+				(local.get 2)
+				(local.get 3)
+				(i32.const 7)
+				(local.set 3)
+				(i32.add)
+				(i32.add)
+			)
+		)
+	"#;
+
+	let coalesced_luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			coalesce_registers: true,
+			..Default::default()
+		},
+	);
+
+	let coalesced_luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			coalesce_registers: true,
+			..Default::default()
+		},
+	);
+
+	// Both saved values land in `reg_0`, so they must not share a statement
+	for output in [coalesced_luajit, coalesced_luau] {
+		assert!(output.contains("reg_0, loc_0 = loc_0, 5\n"));
+		assert!(output.contains("reg_0, loc_3 = loc_3, 7\n"));
+		assert!(!output.contains("reg_0, loc_0, reg_0"));
+	}
+}
+
+#[test]
+fn out_of_bounds_index() {
+	let source = r"
+		(module
+			(global (mut i32) (i32.const 0))
+			(func (result i32) (global.get 0))
+		)
+	";
+
+	let declared = encode(source);
+	let declared = Module::try_from_data(&declared).unwrap();
+	let type_info = TypeInfo::from_module(&declared);
+	let mut builder = Factory::from_type_info(&type_info);
+	let func_list: Vec<_> = declared
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i, v).unwrap())
+		.collect();
+
+	let data = encode("(module (func (result i32) (i32.const 0)))");
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+
+	let error = codegen_luajit::from_func_list(
+		&wasm,
+		&type_info,
+		&func_list,
+		&codegen_luajit::Options::default(),
+		&mut Vec::new(),
+	)
+	.unwrap_err();
+
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	assert_eq!(
+		error.to_string(),
+		"function 0: global 0 is out of bounds of 0"
+	);
+
+	let result = codegen_luau::from_func_list(
+		&wasm,
+		&type_info,
+		&func_list,
+		&codegen_luau::Options::default(),
+		&mut Vec::new(),
+	);
+
+	assert!(result.is_err());
+}
+
+#[test]
+fn br_table_on_local() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32) (result i32)
+				(block
+					(block
+						(block
+							(br_table 0 1 2 (local.get 0))
+						)
+						(return (i32.const 1))
+					)
+					(return (i32.const 2))
+				)
+				(i32.const 3)
+			)
+		)
+	"#;
+
+	// `temp` holds the target depth looked up for the index, not the index
+	// itself, so a bare local is still read through `br_map`
+	let output = luajit(source);
+
+	assert!(output.contains("temp = br_map[1][loc_0] or 2\n"));
+	assert!(output.contains("if temp < 1 then"));
+	assert!(!output.contains("loc_0 =="));
+}
+
+#[test]
+fn table_get_and_set() {
+	let source = r#"
+		(module
+			(table 4 funcref)
+			(func (export "f") (param i32)
+				(table.set 0 (local.get 0) (table.get 0 (i32.const 1)))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(output.contains("reg_1 = rt.table.get(TABLE_LIST[0], 1)\n"));
+		assert!(output.contains("rt.table.set(TABLE_LIST[0], loc_0, reg_1)\n"));
+	}
+
+	let flat = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			flat_table: true,
+			..Default::default()
+		},
+	);
+
+	let check = concat!(
+		"if table_index < 0 or table_index >= 4 then ",
+		r#"error("out of bounds table access") end"#,
+	);
+
+	assert!(flat.contains("reg_1 = TABLE[table_index + 1]\n"));
+	assert!(flat.contains(&format!(
+		"do\n\t\tlocal table_index = loc_0\n\t\t{check}\n\t\tTABLE[table_index + 1] = reg_1\n\tend\n"
+	)));
+	assert_eq!(flat.matches("loc_0").count(), 2);
+
+	for runtime in [codegen_luajit::RUNTIME, codegen_luau::RUNTIME] {
+		assert!(runtime.contains(r#"error("out of bounds table access", 3)"#));
+	}
+}
+
+#[test]
+fn shift_counts_masked() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32 i64) (result i32 i32 i64 i64 i32 i64)
+				(i32.shl (local.get 0) (i32.const 32))
+				(i32.shr_u (local.get 0) (i32.const 33))
+				(i64.shl (local.get 1) (i64.const 64))
+				(i64.shr_s (local.get 1) (i64.const 65))
+				(i32.rotl (local.get 0) (local.get 0))
+				(i64.shl (local.get 1) (local.get 1))
+			)
+		)
+	"#;
+
+	let luajit = luajit(source);
+	let luau = luau(source);
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains("shl_i32(loc_0, 0)"));
+		assert!(output.contains("shr_u32(loc_0, 1)"));
+		assert!(output.contains("rotl_i32(loc_0, loc_0)"));
+		assert!(output.contains("shl_i64(loc_1, loc_1)"));
+	}
+
+	assert!(luajit.contains("shl_i64(loc_1, 0LL)"));
+	assert!(luajit.contains("shr_i64(loc_1, 1LL)"));
+	assert!(luau.contains("shl_i64(loc_1, i64_ZERO)"));
+	assert!(luau.contains("shr_i64(loc_1, i64_ONE)"));
+
+	// Counts only known at run time are masked by the library doing the shift
+	assert!(luajit.contains("local shl_i32 = bit.lshift\n"));
+	assert!(luajit.contains("local shl_i64 = bit.lshift\n"));
+	assert!(luau.contains("local shl_i32 = rt.shl.i32\n"));
+	assert!(codegen_luau::RUNTIME.contains("return bit_lshift(lhs, rhs % 32)"));
+
+	assert_eq!(codegen_luau::NUMERIC.matches("local count = ").count(), 3);
+	assert_eq!(codegen_luau::NUMERIC.matches(" % 64\n").count(), 3);
+}
+
+#[test]
+fn folded_identities() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32 i64 f64) (result i32 i64 f64 i32)
+				(i32.or (i32.add (local.get 0) (i32.const 0)) (i32.const 0))
+				(i64.mul (i64.const 1) (i64.shl (local.get 1) (i64.const 0)))
+				(f64.add (local.get 2) (f64.const 0))
+				(i32.sub (i32.const 0) (local.get 0))
+			)
+		)
+	"#;
+
+	let folded_luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			fold_identities: true,
+			..Default::default()
+		},
+	);
+
+	let folded_luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			fold_identities: true,
+			..Default::default()
+		},
+	);
+
+	for output in [folded_luajit, folded_luau] {
+		let list = "= loc_0, loc_1, (loc_2 + 0e0), sub_i32(0, loc_0)\n";
+
+		assert!(output.contains(list));
+	}
+
+	assert!(luajit(source).contains("add_i32(loc_0, 0)"));
+}
+
+#[test]
+fn readable_labels() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32)
+				(block
+					(loop
+						(br_if 1 (local.get 0))
+						(br 0)
+					)
+				)
+			)
+		)
+	"#;
+
+	let output = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			readable_labels: true,
+			..Default::default()
+		},
+	);
+
+	assert!(output.contains("::loop_2::\n"));
+	assert!(output.contains("goto loop_2\n"));
+	assert!(output.contains("goto block_exit_1\n"));
+	assert!(output.contains("::block_exit_1::\n"));
+	assert!(!output.contains("continue_at"));
+
+	assert!(luajit(source).contains("goto continue_at_2\n"));
+}
+
+#[test]
+fn output_hash() {
+	use std::io::Write;
+
+	let data = encode(r#"(module (func (export "f") (result i32) i32.const 1))"#);
+	let other = encode(r#"(module (func (export "f") (result i32) i32.const 2))"#);
+
+	let (luajit, hash) = codegen_luajit::transpile_with_hash(&data).unwrap();
+
+	assert_eq!(luajit, codegen_luajit::transpile(&data).unwrap());
+	assert_eq!(hash, codegen_luajit::transpile_with_hash(&data).unwrap().1);
+	assert_ne!(hash, codegen_luajit::transpile_with_hash(&other).unwrap().1);
+
+	let (luau, hash) = codegen_luau::transpile_with_hash(&data).unwrap();
+
+	assert_eq!(luau, codegen_luau::transpile(&data).unwrap());
+	assert_ne!(hash, codegen_luau::transpile_with_hash(&other).unwrap().1);
+
+	// Known FNV-1a values for no input and for a single `a`
+	let mut w = codegen_luajit::Fingerprint::new(Vec::new());
+
+	assert_eq!(w.hash(), 0xCBF2_9CE4_8422_2325);
+
+	w.write_all(b"a").unwrap();
+
+	assert_eq!(w.hash(), 0xAF63_DC4C_8601_EC8C);
+}
+
+#[test]
+fn small_i64_comparison_constants() {
+	let source = r#"
+		(module
+			(func (export "f") (param i64) (result i32)
+				(i32.add
+					(i64.lt_s (local.get 0) (i64.const -100))
+					(i64.eq (local.get 0) (i64.const 9007199254740993))
+				)
+			)
+			(func (export "g") (param i64) (result i64)
+				(i64.add (local.get 0) (i64.const 1))
+			)
+		)
+	"#;
+
+	let output = luajit(source);
+
+	assert!(output.contains("loc_0 < -100 and"));
+	assert!(output.contains("loc_0 == 9007199254740993LL and"));
+	assert!(output.contains("(loc_0 + 1LL)"));
+}
+
+#[test]
+fn luau_unsigned_division() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32) (result i32)
+				(i32.add
+					(i32.div_u (i32.const 0xFFFFFFFF) (i32.const 2))
+					(i32.rem_u (local.get 0) (i32.const 0x80000000))
+				)
+			)
+			(func (export "g") (param i64) (result i64)
+				(i64.rem_u (local.get 0) (i64.const -1))
+			)
+		)
+	"#;
+
+	let output = luau(source);
+
+	assert!(output.contains("local div_u32 = rt.div.u32\n"));
+	assert!(output.contains("div_u32(4294967295, 2)"));
+	assert!(output.contains("(loc_0 % 2147483648)"));
+	assert!(output.contains("rem_u64(loc_0, i64_from_u32(4294967295, 4294967295))"));
+	assert!(!output.contains("math.floor"));
+}
+
+#[test]
+fn lazy_elements() {
+	let source = r#"
+		(module
+			(table 4 funcref)
+			(elem (i32.const 1) $f $f)
+			(func $f (export "f") (param i32) (result i32)
+				(call_indirect (result i32) (local.get 0))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("set_lazy_elements"));
+		assert!(output.contains("local data = { FUNC_LIST[0],FUNC_LIST[0], }\n"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			lazy_elements: true,
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			lazy_elements: true,
+			..Default::default()
+		},
+	);
+
+	for output in [luajit, luau] {
+		let (helper, init) = output.split_once("local function run_init_code()").unwrap();
+
+		assert!(helper.contains("local function set_lazy_elements(target, offset, data)\n"));
+		assert!(helper.contains("__newindex = function(list, index, value)\n"));
+		assert!(init.contains("local data = { 0,0, }\n"));
+		assert!(init.contains("set_lazy_elements(target, offset, data)\n"));
+		assert!(!init.contains("table.move"));
+	}
+}
+
+#[test]
+fn linked_module_list() {
+	let host = encode(
+		r#"(module (func (export "add") (param i32 i32) (result i32) (i32.add (local.get 0) (local.get 1))))"#,
+	);
+	let plugin = encode(
+		r#"
+		(module
+			(import "host" "add" (func $add (param i32 i32) (result i32)))
+			(import "env" "log" (func $log (param i32)))
+			(func (export "run") (call $log (call $add (i32.const 1) (i32.const 2))))
+		)
+	"#,
+	);
+
+	let list = [("host", host.as_slice()), ("plugin", plugin.as_slice())];
+
+	for output in [
+		codegen_luajit::transpile_list(&list).unwrap(),
+		codegen_luau::transpile_list(&list).unwrap(),
+	] {
+		assert_eq!(output.matches("local rt = (function()").count(), 1);
+		assert_eq!(output.matches("local FUNC_LIST = ").count(), 2);
+
+		let (_, link) = output.rsplit_once("return function(wasm)\n").unwrap();
+
+		assert!(output.contains("MODULE_LIST[\"host\"] = (function()\n"));
+		assert!(output.contains("FUNC_LIST[0] = wasm[\"host\"].func_list[\"add\"]\n"));
+		assert!(link.contains("\tlinked[\"host\"] = MODULE_LIST[\"host\"](linked)\n"));
+		assert!(link.contains("\tlinked[\"plugin\"] = MODULE_LIST[\"plugin\"](linked)\n"));
+		assert!(link.ends_with("\treturn setmetatable(linked, nil)\nend\n"));
+	}
+
+	let host = Module::try_from_data(&host).unwrap();
+	let plugin = Module::try_from_data(&plugin).unwrap();
+	let list = [("host", &host), ("plugin", &plugin)];
+
+	let options = codegen_luajit::Options {
+		deferred_init: true,
+		..Default::default()
+	};
+	let error = codegen_luajit::from_module_list(&list, &options, &mut Vec::new()).unwrap_err();
+
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+	let options = codegen_luau::Options {
+		deferred_init: true,
+		..Default::default()
+	};
+	let error = codegen_luau::from_module_list(&list, &options, &mut Vec::new()).unwrap_err();
+
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn luau_repeat_loops() {
+	let source = r#"
+		(module
+			(func (export "count") (param i32) (result i32) (local i32)
+				(loop
+					(local.set 1 (i32.add (local.get 1) (i32.const 1)))
+					(br_if 0 (i32.lt_u (local.get 1) (local.get 0)))
+				)
+				(local.get 1)
+			)
+			(func (export "search") (param i32)
+				(loop
+					(br_if 0 (i32.eqz (local.get 0)))
+					(local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+					(br_if 0 (local.get 0))
+				)
+			)
+		)
+	"#;
+
+	let output = luau(source);
+	let (count, search) = output.split_once("FUNC_LIST[1]").unwrap();
+
+	assert!(count.contains("\t\trepeat\n"));
+	assert!(count.contains("\t\tuntil not (loc_1 < loc_0)\n"));
+	assert!(!count.contains("continue"));
+
+	assert!(!search.contains("repeat"));
+	assert!(search.contains("continue"));
+}
+
+#[test]
+fn source_locations() {
+	let source = r#"
+		(module
+			(func $first (export "first"))
+			(func $second (export "second"))
+		)
+	"#;
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			location_map: [(1, "src/lib.rs:42".into())].into(),
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			location_map: [(1, "src/lib.rs:42".into())].into(),
+			..Default::default()
+		},
+	);
+
+	for output in [luajit, luau] {
+		assert!(output.contains("-- src/lib.rs:42\nFUNC_LIST[1] = --[[ second ]] function"));
+		assert_eq!(output.matches("-- src/").count(), 1);
+	}
+}
+
+#[test]
+fn division_policy() {
+	let source = r#"
+		(module
+			(func (export "f") (param i32 i64) (result i32)
+				(i32.add
+					(i32.div_u (local.get 0) (i32.const 0))
+					(i32.wrap_i64 (i64.rem_s (local.get 1) (i64.const 0)))
+				)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("rt.guard"));
+	}
+
+	let policy_list = [
+		(
+			codegen_luajit::DivisionPolicy::ReturnZero,
+			codegen_luau::DivisionPolicy::ReturnZero,
+			"zero",
+		),
+		(
+			codegen_luajit::DivisionPolicy::ReturnWrapped,
+			codegen_luau::DivisionPolicy::ReturnWrapped,
+			"wrapped",
+		),
+	];
+
+	for (luajit_policy, luau_policy, prefix) in policy_list {
+		let luajit = luajit_with(
+			source,
+			&codegen_luajit::Options {
+				division_policy: luajit_policy,
+				..Default::default()
+			},
+		);
+
+		let luau = luau_with(
+			source,
+			&codegen_luau::Options {
+				division_policy: luau_policy,
+				..Default::default()
+			},
+		);
+
+		assert!(luajit.contains(&format!("rt.guard.{prefix}_div_u32(loc_0, 0)")));
+		assert!(luajit.contains(&format!("rt.guard.{prefix}_rem_i64(loc_1, 0LL)")));
+		assert!(luau.contains(&format!("rt.guard.{prefix}_div_u32(loc_0, 0)")));
+		assert!(luau.contains(&format!("rt.guard.{prefix}_rem_i64(loc_1, i64_ZERO)")));
+	}
+
+	for runtime in [codegen_luajit::RUNTIME, codegen_luau::RUNTIME] {
+		assert!(runtime.contains("guard[\"zero_\" .. name]"));
+		assert!(runtime.contains("guard[\"wrapped_\" .. name]"));
+		assert!(runtime.contains("module.guard = guard\n"));
+	}
+}
+
+#[test]
+fn unbound_global_trap() {
+	let source = r#"
+		(module
+			(import "env" "base" (global i32))
+			(global i32 (i32.const 1))
+			(func (export "f") (result i32) (global.get 0))
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("setmetatable(GLOBAL_LIST"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			trap_unbound_globals: true,
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			trap_unbound_globals: true,
+			..Default::default()
+		},
+	);
+
+	for output in [luajit, luau] {
+		assert!(output.contains("setmetatable(GLOBAL_LIST, {\n"));
+		assert!(output.contains("local name = ({ [0] = \"env.base\", })[index]\n"));
+		assert!(output.contains("error(\"unbound import global: \" .. tostring(name or index))\n"));
+	}
+}
+
+#[test]
+fn flexible_import_results() {
+	let source = r#"
+		(module
+			(import "env" "pair" (func $pair (param i32) (result i32 i64)))
+			(import "env" "strict" (func $strict (result f64)))
+			(func (export "f") (result i32)
+				(call $pair (i32.const 1))
+				(drop)
+				(drop (call $strict))
+			)
+		)
+	"#;
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			flexible_result_set: [0].into(),
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			flexible_result_set: [0].into(),
+			..Default::default()
+		},
+	);
+
+	assert!(luajit.contains(
+		"reg_0, reg_1 = FUNC_LIST[0](1)\n\treg_0 = reg_0 or 0\n\treg_1 = reg_1 or 0LL\n"
+	));
+	assert!(luau.contains("\t\treg_0 = reg_0 or 0\n\t\treg_1 = reg_1 or i64_ZERO\n"));
+	assert!(luau.contains("local i64_ZERO = rt.i64.ZERO\n"));
+
+	for output in [luajit, luau] {
+		assert_eq!(output.matches(" = reg_").count(), 2);
+	}
+}
+
+#[test]
+fn version_banner() {
+	let source = r#"(module (func (export "f")))"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("Generated by Wasynth"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			banner: true,
+			flat_table: true,
+			..Default::default()
+		},
+	);
+
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			banner: true,
+			analyzer_directive: Some("native".into()),
+			fold_identities: true,
+			..Default::default()
+		},
+	);
+
+	let version = env!("CARGO_PKG_VERSION");
+
+	assert!(luajit.starts_with(&format!(
+		"-- Generated by Wasynth {version} for LuaJIT\n-- Options: flat_table\n"
+	)));
+
+	assert!(luau.starts_with(&format!(
+		"--!native\n-- Generated by Wasynth {version} for Luau\n-- Options: fold_identities, analyzer_directive\n"
+	)));
+
+	let data = encode(source);
+
+	for output in [
+		codegen_luajit::transpile(&data).unwrap(),
+		codegen_luau::transpile(&data).unwrap(),
+	] {
+		assert_eq!(output.matches("-- Options: default\n").count(), 1);
+		assert!(output.starts_with("-- Generated by Wasynth"));
+	}
+}
+
+#[test]
+fn demote_rounds_to_f32() {
+	let source = r#"
+		(module
+			(func (export "round") (result f32)
+				(f32.demote_f64 (f64.const 0.1))
+			)
+			(func (export "widen") (param f32) (result f64)
+				(f64.promote_f32 (local.get 0))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(output.contains("local demote_f32_f64 = rt.demote.f32_f64\n"));
+		assert!(output.contains("demote_f32_f64(1e-1)"));
+		assert!(output.contains("promote_f64_f32(loc_0)"));
+	}
+
+	assert!(codegen_luajit::RUNTIME.contains(
+		"function demote.f32_f64(num)\n\t\tRE_INSTANCE.f32 = num\n\n\t\treturn RE_INSTANCE.f32\n"
+	));
+
+	assert!(codegen_luau::RUNTIME
+		.contains("function demote.f32_f64(num)\n\t\treturn (string_unpack(\"f\", string_pack(\"f\", num)))\n"));
+}
+
+#[test]
+fn hoisted_loads() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func (export "sum") (param i32) (result i32) (local i32)
+				(loop $continue
+					(local.set 1 (i32.add (local.get 1) (i32.load (i32.const 16))))
+					(local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+					(br_if $continue (local.get 0))
+				)
+				(local.get 1)
+			)
+			(func (export "fill") (param i32)
+				(loop $continue
+					(i32.store (local.get 0) (i32.load (i32.const 16)))
+					(local.set 0 (i32.sub (local.get 0) (i32.const 4)))
+					(br_if $continue (local.get 0))
+				)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("hoist_"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			hoist_loads: true,
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			hoist_loads: true,
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert_eq!(output.matches("local hoist_0\n").count(), 1);
+		assert!(output.contains("hoist_0 = load_i32(memory_at_0, 16)\n"));
+		assert!(output.contains("add_i32(loc_1, hoist_0)"));
+		assert!(output.contains("store_i32(memory_at_0, loc_0, load_i32(memory_at_0, 16))"));
+	}
+
+	assert!(luajit.contains("hoist_0 = load_i32(memory_at_0, 16)\n\t::continue_at_1::\n"));
+	assert!(luau.contains("hoist_0 = load_i32(memory_at_0, 16)\n\t\trepeat\n"));
+}
+
+#[test]
+fn counting_loops() {
+	let source = r#"
+		(module
+			(func (export "sum") (param $n i32) (result i32) (local $i i32) (local $acc i32)
+				(loop $continue
+					(local.set $acc (i32.add (local.get $acc) (local.get $i)))
+					(local.set $i (i32.add (local.get $i) (i32.const 1)))
+					(br_if $continue (i32.lt_s (local.get $i) (local.get $n)))
+				)
+				(local.get $acc)
+			)
+			(func (export "moving") (param $n i32) (local $i i32)
+				(loop $continue
+					(local.set $n (i32.sub (local.get $n) (i32.const 1)))
+					(local.set $i (i32.add (local.get $i) (i32.const 1)))
+					(br_if $continue (i32.ne (local.get $i) (local.get $n)))
+				)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("rt.count"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			counting_loops: true,
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			counting_loops: true,
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert_eq!(output.matches("rt.count.").count(), 1);
+		assert!(output.contains("for _ = 1, rt.count.lt_i32(add_i32(loc_1, 1), loc_0) do\n"));
+	}
+
+	assert!(luajit.contains("goto continue_at_1"));
+	assert!(luau.contains("until not (loc_1 ~= loc_0)"));
+}
+
+#[test]
+fn branch_coverage() {
+	let source = r#"
+		(module
+			(func (export "pick") (param i32) (result i32)
+				(block $outer
+					(block $inner
+						(br_table $inner $outer (local.get 0))
+					)
+					(br_if $outer (local.get 0))
+					(if (local.get 0) (then (nop)))
+				)
+				(local.get 0)
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+
+	let mut output = Vec::new();
+	let options = codegen_luajit::Options {
+		coverage_table: Some("COVERAGE".into()),
+		..Default::default()
+	};
+	let legend =
+		codegen_luajit::from_module_covered(&wasm, &type_info, &options, &mut output).unwrap();
+	let luajit = String::from_utf8(output).unwrap();
+
+	let mut output = Vec::new();
+	let options = codegen_luau::Options {
+		coverage_table: Some("COVERAGE".into()),
+		..Default::default()
+	};
+	let legend_luau =
+		codegen_luau::from_module_covered(&wasm, &type_info, &options, &mut output).unwrap();
+	let luau = String::from_utf8(output).unwrap();
+
+	let branch = |site, outcome| codegen_luajit::Branch {
+		function: 0,
+		site,
+		outcome,
+	};
+
+	assert_eq!(
+		legend,
+		[
+			branch(0, codegen_luajit::Outcome::Target(0)),
+			branch(0, codegen_luajit::Outcome::Target(1)),
+			branch(1, codegen_luajit::Outcome::Taken),
+			branch(1, codegen_luajit::Outcome::NotTaken),
+			branch(2, codegen_luajit::Outcome::Then),
+			branch(2, codegen_luajit::Outcome::Else),
+		]
+	);
+
+	assert_eq!(legend_luau.len(), legend.len());
+
+	for output in [&luajit, &luau] {
+		for id in 0..legend.len() {
+			let line = format!("COVERAGE[{id}] = (COVERAGE[{id}] or 0) + 1\n");
+
+			assert_eq!(output.matches(&line).count(), 1);
+		}
+	}
+
+	assert!(luajit.contains("end\n\tCOVERAGE[3] = (COVERAGE[3] or 0) + 1\n"));
+	assert!(luajit.contains("else\n\t\tCOVERAGE[5] = (COVERAGE[5] or 0) + 1\n"));
+}
+
+#[test]
+fn mutual_recursion() {
+	let source = r#"
+		(module
+			(func $even (export "even") (param i32) (result i32)
+				(if (result i32) (local.get 0)
+					(then (call $odd (i32.sub (local.get 0) (i32.const 1))))
+					(else (i32.const 1))
+				)
+			)
+			(func $odd (param i32) (result i32)
+				(if (result i32) (local.get 0)
+					(then (call $even (i32.sub (local.get 0) (i32.const 1))))
+					(else (i32.const 0))
+				)
+			)
+		)
+	"#;
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			runtime_module: Some("\"runtime\"".into()),
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			runtime_module: Some("\"runtime\"".into()),
+			..Default::default()
+		},
+	);
+
+	// Functions are reached through `FUNC_LIST`, which is declared before
+	// any of them, so each sees the other however they are ordered
+	for output in [&luajit, &luau] {
+		let declared = output.find("local FUNC_LIST = ").unwrap();
+		let first = output.find("FUNC_LIST[0] = ").unwrap();
+		let second = output.find("FUNC_LIST[1] = ").unwrap();
+
+		assert!(declared < first && first < second);
+		assert!(output[first..second].contains("FUNC_LIST[1]("));
+		assert!(output[second..].contains("FUNC_LIST[0]("));
+	}
+}
+
+#[test]
+fn parameter_coercion() {
+	let source = r#"
+		(module
+			(func (export "mix") (param i32 f64 i64) (result i64)
+				(local.get 2)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("tonumber"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			coerce_set: [0].into(),
+			assert_set: [0].into(),
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			coerce_set: [0].into(),
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains("\tloc_0 = tonumber(loc_0) or 0\n\tloc_1 = tonumber(loc_1) or 0\n"));
+	}
+
+	let coerce = luajit
+		.find(
+			r#"if type(loc_2) ~= "cdata" then loc_2 = rt.truncate.i64_f64(tonumber(loc_2) or 0) end"#,
+		)
+		.unwrap();
+
+	assert!(coerce < luajit.find("assert(type(loc_2)").unwrap());
+	assert!(luau.contains(r#"" then loc_2 = rt.truncate.i64_f64(tonumber(loc_2) or 0) end"#));
+}
+
+#[test]
+fn saturating_truncation() {
+	let mut source = String::from("(module\n");
+
+	for (result, input) in [
+		("i32", "f32"),
+		("i32", "f64"),
+		("i64", "f32"),
+		("i64", "f64"),
+	] {
+		for sign in ["s", "u"] {
+			let name = format!("{result}.trunc_sat_{input}_{sign}");
+
+			source.push_str(&format!(
+				"(func (export \"{name}\") (result {result} {result} {result})
+					({name} ({input}.const nan))
+					({name} ({input}.const 1e30))
+					({name} ({input}.const -1e30))
+				)\n"
+			));
+		}
+	}
+
+	source.push(')');
+
+	for output in [luajit(&source), luau(&source)] {
+		for name in [
+			"i32_f32", "i32_f64", "u32_f32", "u32_f64", "i64_f32", "i64_f64", "u64_f32", "u64_f64",
+		] {
+			assert!(output.contains(&format!("local saturate_{name} = rt.saturate.{name}\n")));
+			assert!(output.contains(&format!("saturate_{name}(-(0.0 / 0.0))")));
+			assert!(output.contains(&format!("saturate_{name}(1")));
+			assert!(output.contains(&format!("saturate_{name}(-1")));
+		}
+
+		assert!(!output.contains("truncate_"));
+	}
+
+	// Every helper maps NaN to zero rather than trapping
+	let nan_list = [
+		"function saturate.i32_f32(num)\n\t\tif num ~= num then\n\t\t\treturn 0\n",
+		"function saturate.u32_f32(num)\n\t\tif num <= 0 or num ~= num then\n\t\t\treturn 0\n",
+		"\t\telseif num ~= num then\n\t\t\treturn NUM_ZERO\n",
+		"\t\telseif num <= 0 or num ~= num then\n\t\t\treturn NUM_ZERO\n",
+	];
+
+	for nan in nan_list {
+		assert!(codegen_luajit::RUNTIME.contains(nan));
+	}
+
+	for nan in &nan_list[2..] {
+		assert!(codegen_luau::RUNTIME.contains(nan));
+	}
+
+	assert_eq!(
+		codegen_luau::RUNTIME
+			.matches("_f32(num)\n\t\tif num ~= num then\n\t\t\treturn 0\n")
+			.count(),
+		2
+	);
+}
+
+// Hosts are not run, so each variant is checked by the call written for
+// every edge case
+#[test]
+fn saturating_truncation_variants() {
+	use std::fmt::Write;
+
+	let variant_list = [
+		("i32.trunc_sat_f32_s", "i32_f32", "1.0000000150474662e30"),
+		("i32.trunc_sat_f32_u", "u32_f32", "1.0000000150474662e30"),
+		("i32.trunc_sat_f64_s", "i32_f64", "1e30"),
+		("i32.trunc_sat_f64_u", "u32_f64", "1e30"),
+		("i64.trunc_sat_f32_s", "i64_f32", "1.0000000150474662e30"),
+		("i64.trunc_sat_f32_u", "u64_f32", "1.0000000150474662e30"),
+		("i64.trunc_sat_f64_s", "i64_f64", "1e30"),
+		("i64.trunc_sat_f64_u", "u64_f64", "1e30"),
+	];
+
+	let mut source = String::from("(module\n");
+
+	for (op, _, _) in variant_list {
+		let (result, input) = (&op[..3], &op[14..17]);
+
+		writeln!(
+			source,
+			"(func (result {result} {result} {result})
+				({op} ({input}.const nan))
+				({op} ({input}.const 1e30))
+				({op} ({input}.const -1e30))
+			)"
+		)
+		.unwrap();
+	}
+
+	source.push(')');
+
+	for output in [luajit(&source), luau(&source)] {
+		for (i, (_, name, large)) in variant_list.iter().enumerate() {
+			let (_, body) = output.split_once(&format!("FUNC_LIST[{i}] = ")).unwrap();
+			let call = format!(
+				"reg_0, reg_1, reg_2 = saturate_{name}(-(0.0 / 0.0)), saturate_{name}({large}), saturate_{name}(-{large})\n"
+			);
+
+			assert!(body[..body.find("\nend\n").unwrap()].contains(&call));
+		}
+	}
+}
+
+// Each edge case is checked by the branch of the helper that handles it
+#[test]
+fn saturating_truncation_helpers() {
+	let edge_list = [
+		(
+			"i32",
+			[
+				[
+					"if num ~= num then\n\t\t\treturn 0\n",
+					"elseif num <= -0x80000000 then\n\t\t\treturn -0x80000000\n",
+					"elseif num >= 0x7FFFFFFF then\n\t\t\treturn 0x7FFFFFFF\n",
+				],
+				[
+					"if num ~= num then\n\t\t\treturn 0\n",
+					"math_clamp(truncate_f64(num), -0x80000000, 0x7FFFFFFF)",
+					"math_clamp(truncate_f64(num), -0x80000000, 0x7FFFFFFF)",
+				],
+			],
+		),
+		(
+			"u32",
+			[
+				[
+					"if num <= 0 or num ~= num then\n\t\t\treturn 0\n",
+					"if num <= 0 or num ~= num then\n\t\t\treturn 0\n",
+					"elseif num >= 0xFFFFFFFF then\n\t\t\treturn -1\n",
+				],
+				[
+					"if num ~= num then\n\t\t\treturn 0\n",
+					"math_clamp(truncate_f64(num), 0, 0xFFFFFFFF)",
+					"math_clamp(truncate_f64(num), 0, 0xFFFFFFFF)",
+				],
+			],
+		),
+		(
+			"i64",
+			[
+				[
+					"elseif num ~= num then\n\t\t\treturn NUM_ZERO\n",
+					"elseif num <= -2 ^ 63 then\n\t\t\treturn NUM_MIN_I64\n",
+					"if num >= 2 ^ 63 - 1 then\n\t\t\treturn NUM_MAX_I64\n",
+				],
+				[
+					"elseif num ~= num then\n\t\t\treturn NUM_ZERO\n",
+					"elseif num <= -2 ^ 63 then\n\t\t\treturn NUM_MIN_I64\n",
+					"if num >= 2 ^ 63 - 1 then\n\t\t\treturn NUM_MAX_I64\n",
+				],
+			],
+		),
+		(
+			"u64",
+			[
+				[
+					"elseif num <= 0 or num ~= num then\n\t\t\treturn NUM_ZERO\n",
+					"elseif num <= 0 or num ~= num then\n\t\t\treturn NUM_ZERO\n",
+					"if num >= 2 ^ 64 then\n\t\t\treturn NUM_MAX_U64\n",
+				],
+				[
+					"elseif num <= 0 or num ~= num then\n\t\t\treturn NUM_ZERO\n",
+					"elseif num <= 0 or num ~= num then\n\t\t\treturn NUM_ZERO\n",
+					"if num >= 2 ^ 64 then\n\t\t\treturn NUM_MAX_U64\n",
+				],
+			],
+		),
+	];
+
+	// The `f64` variants share the helper of their `f32` counterpart
+	for (runtime, side) in [(codegen_luajit::RUNTIME, 0), (codegen_luau::RUNTIME, 1)] {
+		for (kind, expected_list) in &edge_list {
+			let (_, helper) = runtime
+				.split_once(&format!("function saturate.{kind}_f32(num)\n"))
+				.unwrap();
+			let helper = &helper[..helper.find("\n\tend\n").unwrap()];
+
+			assert!(runtime.contains(&format!("saturate.{kind}_f64 = saturate.{kind}_f32\n")));
+
+			for expected in &expected_list[side] {
+				assert!(helper.contains(expected), "{kind}: {expected}");
+			}
+		}
+	}
+}
+
+#[test]
+fn export_signatures() {
+	let source = r#"
+		(module
+			(func (export "add") (param i32 i32) (result i32)
+				(i32.add (local.get 0) (local.get 1))
+			)
+			(func (export "wide") (param i64) (result f64 i64)
+				(f64.const 1)
+				(local.get 0)
+			)
+			(memory (export "memory") 1)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("signature_list"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			export_signatures: true,
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			export_signatures: true,
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains(
+			"\t\tsignature_list = {\n\
+			\t\t\t[\"add\"] = { params = 2, results = 1, param_types = { \"i32\", \"i32\", }, result_types = { \"i32\", } },\n\
+			\t\t\t[\"wide\"] = { params = 1, results = 2, param_types = { \"i64\", }, result_types = { \"f64\", \"i64\", } },\n\
+			\t\t},\n"
+		));
+		assert!(!output.contains("[\"memory\"] = {"));
+	}
+}
+
+#[test]
+fn function_size_limit() {
+	let body = "(global.set 0 (i32.add (global.get 0) (local.get 0)))".repeat(64);
+	let source = format!(
+		"(module
+			(global (mut i32) (i32.const 0))
+			(func (export \"small\") (param i32) (result i32) (local.get 0))
+			(func (export \"large\") (param i32) {body})
+		)"
+	);
+
+	let data = encode(&source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+
+	let luajit = codegen_luajit::Options {
+		function_size_limit: Some(200),
+		..Default::default()
+	};
+	let luau = codegen_luau::Options {
+		function_size_limit: Some(200),
+		..Default::default()
+	};
+
+	let error_list = [
+		codegen_luajit::from_module_typed(&wasm, &type_info, &luajit, &mut Vec::new()).unwrap_err(),
+		codegen_luau::from_module_typed(&wasm, &type_info, &luau, &mut Vec::new()).unwrap_err(),
+	];
+
+	for error in error_list {
+		let message = error.to_string();
+
+		assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+		assert!(message.starts_with("function 1 is "));
+		assert!(message.ends_with(" bytes long, past the limit of 200"));
+	}
+
+	let luajit = codegen_luajit::Options {
+		function_size_limit: Some(200_000),
+		..Default::default()
+	};
+
+	codegen_luajit::from_module_typed(&wasm, &type_info, &luajit, &mut Vec::new()).unwrap();
+}
+
+#[test]
+fn instantiate_from_imports() {
+	let source = r#"
+		(module
+			(import "env" "log" (func $log (param i32)))
+			(import "env" "memory" (memory 1))
+			(import "env" "table" (table 1 funcref))
+			(import "env" "base" (global i32))
+			(func $start
+				(call $log (i32.load (global.get 0)))
+			)
+			(start $start)
+			(export "start" (func $start))
+		)
+	"#;
+
+	// The chunk returns the function that instantiates the module, which
+	// binds each import before running any code and then gives the exports
+	for output in [luajit(source), luau(source)] {
+		let line_list = [
+			"return function(wasm)\n",
+			"\tFUNC_LIST[0] = wasm[\"env\"].func_list[\"log\"]\n",
+			"\tTABLE_LIST[0] = wasm[\"env\"].table_list[\"table\"]\n",
+			"\tMEMORY_LIST[0] = wasm[\"env\"].memory_list[\"memory\"]\n",
+			"\tGLOBAL_LIST[0] = wasm[\"env\"].global_list[\"base\"]\n",
+			"\trun_init_code()\n",
+			"\tmemory_at_0 = MEMORY_LIST[0]\n",
+			"\tFUNC_LIST[1]()\n",
+			"\t\t\t[\"start\"] = FUNC_LIST[1],\n",
+		];
+
+		let mut last = 0;
+
+		for line in line_list {
+			let position = output[last..].find(line).unwrap() + last;
+
+			last = position + line.len();
+		}
+	}
+}
+
+#[test]
+fn registers_reused_across_types() {
+	let source = r#"
+		(module
+			(func (export "mixed") (param f32 i32) (result i32)
+				(local f32)
+				(local.set 2
+					(block (result f32)
+						(f32.add (local.get 0) (f32.const 1))
+					)
+				)
+				(block (result i32)
+					(i32.add (local.get 1) (i32.reinterpret_f32 (local.get 2)))
+				)
+			)
+		)
+	"#;
+
+	// Registers are plain Lua locals, so a slot that held an `f32` is simply
+	// overwritten by the next value. Validation means a value is only read
+	// as the type it was written as, and changing type always goes through
+	// an explicit helper
+	for output in [luajit(source), luau(source)] {
+		assert_eq!(output.matches("local reg_").count(), 1);
+		assert!(output.contains("reg_0 = (loc_0 + 1e0)\n"));
+		assert!(output.contains("reg_0 = add_i32(loc_1, reinterpret_i32_f32(loc_2))\n"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			coalesce_registers: true,
+			..Default::default()
+		},
+	);
+
+	assert!(luajit.contains("reinterpret_i32_f32(loc_2)"));
+}
+
+#[test]
+fn inline_cached_indirect_calls() {
+	let source = r#"
+		(module
+			(type $unary (func (param i32) (result i32)))
+			(table 1 funcref)
+			(elem (i32.const 0) $double)
+			(func $double (param i32) (result i32)
+				(i32.add (local.get 0) (local.get 0))
+			)
+			(func (export "sum") (param i32) (result i32) (local i32)
+				(loop $continue
+					(local.set 1
+						(i32.add (local.get 1) (call_indirect (type $unary) (local.get 0) (i32.const 0)))
+					)
+					(local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+					(br_if $continue (local.get 0))
+				)
+				(local.get 1)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("cache_"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			inline_cache_set: [1].into(),
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			inline_cache_set: [1].into(),
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert_eq!(
+			output
+				.matches("local cache_index_0, cache_func_0\n")
+				.count(),
+			1
+		);
+		assert!(output.contains("local call_index = 0\n"));
+		assert!(output.contains("if cache_index_0 ~= call_index then\n"));
+		assert!(output.contains("local call_func = TABLE_LIST[0].data[call_index]\n"));
+		assert!(output.contains("cache_index_0, cache_func_0 = call_index, call_func\n"));
+		assert!(output.contains("reg_1 = cache_func_0(loc_0)\n"));
+		assert_eq!(output.matches("if not call_func then").count(), 1);
+	}
+}
+
+#[test]
+fn guarded_write_path() {
+	let source = r#"
+		(module
+			(memory 1)
+			(func (export "write") (param i32)
+				(i32.store (local.get 0) (i32.load (i32.const 0)))
+				(memory.fill (local.get 0) (i32.const 0) (i32.const 8))
+				(memory.copy (local.get 0) (i32.const 16) (i32.const 8))
+			)
+		)
+	"#;
+
+	assert!(luajit(source).contains("rt.store.fill(memory_at_0, loc_0, 8, 0)"));
+	assert!(luau(source).contains("rt.store.zero(memory_at_0, loc_0, 8)"));
+
+	for output in [luajit(source), luau(source)] {
+		assert!(output.contains("rt.store.copy(memory_at_0, loc_0, memory_at_0, 16, 8)"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			load_source: Some("Guard.read".into()),
+			store_source: Some("Guard.write".into()),
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			load_source: Some("Guard.read".into()),
+			store_source: Some("Guard.write".into()),
+			..Default::default()
+		},
+	);
+
+	// Every write made by running code goes through the write accessor
+	for output in [&luajit, &luau] {
+		assert!(output.contains("local load_i32 = Guard.read.i32\n"));
+		assert!(output.contains("local store_i32 = Guard.write.i32\n"));
+		assert!(output.contains("Guard.write.copy(memory_at_0, loc_0, memory_at_0, 16, 8)"));
+		assert!(!output.contains("rt.store.") && !output.contains("rt.load."));
+	}
+
+	assert!(luajit.contains("Guard.write.fill(memory_at_0, loc_0, 8, 0)"));
+	assert!(luau.contains("Guard.write.zero(memory_at_0, loc_0, 8)"));
+}
+
+#[test]
+fn opcode_comments() {
+	let source = r#"
+		(module
+			(memory 1)
+			(global $g (mut i32) (i32.const 0))
+			(func (export "run") (param i32) (result i32)
+				(local i32)
+				(local.set 1 (i32.add (local.get 0) (i32.const 1)))
+				(global.set $g (local.get 1))
+				(i32.store (local.get 0) (local.get 1))
+				(loop $top
+					(local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+					(br_if $top (local.get 0))
+				)
+				(local.get 1)
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("-- local.set"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			opcode_comments: true,
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			opcode_comments: true,
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains("-- local.set\n"));
+		assert!(output.contains("-- global.set\n"));
+		assert!(output.contains("-- i32.store\n"));
+		assert!(output.contains("-- loop\n"));
+		assert!(output.contains("-- local.get\n"));
+	}
+
+	assert!(luajit.contains("\t\t-- local.set\n\t\tloc_0 = sub_i32(loc_0, 1)\n"));
+	assert!(luajit.contains("\t\t-- br_if\n\t\tif loc_0 ~= 0 then\n"));
+}
+
+#[test]
+fn fall_through_returns() {
+	let source = r#"
+		(module
+			(global $g (mut i32) (i32.const 0))
+			(func (export "void") (param i32)
+				(block $out
+					(br_if $out (local.get 0))
+					(global.set $g (i32.const 1))
+					(br 1)
+				)
+				(global.set $g (i32.const 2))
+			)
+			(func (export "pick") (param i32) (result i32)
+				(block $out (result i32)
+					(br_if $out (i32.const 7) (local.get 0))
+					(if (i32.eqz (local.get 0)) (then (return (i32.const 8))))
+					(br 1 (i32.const 9))
+				)
+			)
+			(func (export "either") (param i32) (result i64)
+				(if (result i64) (local.get 0)
+					(then (i64.const 1))
+					(else (i64.const 2))
+				)
+			)
+		)
+	"#;
+	let luajit = luajit(source);
+	let luau = luau(source);
+
+	// Void functions leave through the end of their body, whether they fall
+	// through or branch to it, and never write a `return`
+	assert!(luajit.contains("\tgoto continue_at_0\n\t::continue_at_1::\n\tGLOBAL_LIST[0].value = 2\n\t::continue_at_0::\nend\nFUNC_LIST[1]"));
+	assert!(luau.contains("\t\tGLOBAL_LIST[0].value = 2\n\t\tbreak\n\tend\nend\nFUNC_LIST[1]"));
+
+	// Validation makes every path to the end of a function set its results,
+	// so each branch, return, and arm writes `reg_0` before leaving
+	for output in [&luajit, &luau] {
+		assert!(output.contains("reg_0 = 7\n"));
+		assert!(output.contains("reg_1 = 8\n"));
+		assert!(output.contains("reg_1 = 9\n"));
+		assert_eq!(output.matches("return reg_0\nend\n").count(), 2);
+	}
+
+	assert!(luajit.contains("\t::continue_at_1::\n\t::continue_at_0::\n\treturn reg_0\nend\n"));
+	assert!(luajit.contains("\telse\n\t\treg_0 = 2LL\n\tend\n\treturn reg_0\nend\n"));
+	assert!(luau.contains("\t\tbreak\n\tend\n\treturn reg_0\nend\nFUNC_LIST[2]"));
+}
+
+#[test]
+fn raw_table_access() {
+	let source = r#"
+		(module
+			(type $t (func (result i32)))
+			(table $tab 2 funcref)
+			(func $one (result i32) (i32.const 1))
+			(elem (i32.const 0) $one)
+			(func (export "run") (param i32) (result i32)
+				(table.set $tab (i32.const 1) (table.get $tab (local.get 0)))
+				(call_indirect (type $t) (local.get 0))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("rawget(") && !output.contains("raw_get"));
+		assert!(output.contains("local call_func = TABLE_LIST[0].data[call_index]\n"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			raw_access: true,
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			raw_access: true,
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains("reg_1 = rt.table.raw_get(TABLE_LIST[0], loc_0)\n"));
+		assert!(output.contains("rt.table.raw_set(TABLE_LIST[0], 1, reg_1)\n"));
+		assert!(output.contains("local call_func = rawget(TABLE_LIST[0].data, call_index)\n"));
+		assert!(output.contains("reg_0 = call_func()"));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			raw_access: true,
+			flat_table: true,
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			raw_access: true,
+			flat_table: true,
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains("reg_1 = rawget(TABLE, table_index + 1)\n"));
+		assert!(output.contains("rawset(TABLE, table_index + 1, reg_1)\n"));
+		assert!(output.contains("local call_func = rawget(TABLE, call_index + 1)\n"));
+	}
+
+	// Lazy elements are resolved through a metatable, which raw access skips
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			raw_access: true,
+			lazy_elements: true,
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			raw_access: true,
+			lazy_elements: true,
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains("local call_func = TABLE_LIST[0].data[call_index]\n"));
+		assert!(output.contains("rt.table.get(TABLE_LIST[0], loc_0)"));
+	}
+}
+
+#[test]
+fn fuzzing_trap_counter() {
+	let source = r#"
+		(module
+			(func (export "boom") (result i32)
+				unreachable
+			)
+			(func (export "div") (param i32 i32) (result i32)
+				(i32.div_s (local.get 0) (local.get 1))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("trap_guard"));
+		assert!(output.contains(r#"["boom"] = FUNC_LIST[0],"#));
+	}
+
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			trap_counter: Some("Fuzz".into()),
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			trap_counter: Some("Fuzz".into()),
+			..Default::default()
+		},
+	);
+
+	// Traps from the runtime, such as dividing by zero, are caught as well
+	for output in [&luajit, &luau] {
+		assert!(output.contains("local function trap_restore(ok, ...)\n\tif not ok then\n"));
+		assert!(output.contains("\t\tFuzz.count = (Fuzz.count or 0) + 1\n"));
+		assert!(output.contains("\t\tFuzz.trapped = true\n\t\tFuzz.message = (...)\n\t\treturn\n"));
+		assert!(output.contains("\t\treturn trap_restore(pcall(func, ...))\n"));
+		assert!(output.contains(r#"["boom"] = trap_guard(FUNC_LIST[0]),"#));
+		assert!(output.contains(r#"["div"] = trap_guard(FUNC_LIST[1]),"#));
+	}
+
+	// The depth is restored before the trap is recorded
+	let luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			trap_counter: Some("Fuzz".into()),
+			depth_limit: Some(100),
+			..Default::default()
+		},
+	);
+	let luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			trap_counter: Some("Fuzz".into()),
+			depth_limit: Some(100),
+			..Default::default()
+		},
+	);
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains(r#"["boom"] = trap_guard(depth_guard(FUNC_LIST[0])),"#));
+	}
+}
+
+// There is no `v128` lowering yet for relaxed SIMD to build on, so its
+// instructions are reported and trap like any other unsupported one
+#[test]
+fn relaxed_simd_is_unsupported() {
+	let source = r#"
+		(module
+			(func (export "madd") (param v128 v128 v128) (result v128)
+				(f32x4.relaxed_madd (local.get 0) (local.get 1) (local.get 2))
+			)
+			(func (export "swizzle") (param v128 v128) (result v128)
+				(i8x16.relaxed_swizzle (local.get 0) (local.get 1))
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+
+	builder.set_trap_unsupported(true);
+
+	let func_list: Vec<_> = wasm
+		.code_section()
+		.iter()
+		.enumerate()
+		.map(|(i, v)| builder.create_indexed(i, v).unwrap())
+		.collect();
+
+	assert_eq!(
+		builder.unsupported_list(),
+		[
+			(Some(0), "F32x4RelaxedMadd".into()),
+			(Some(1), "I8x16RelaxedSwizzle".into()),
+		]
+	);
+
+	let mut output = Vec::new();
+
+	codegen_luajit::from_func_list(
+		&wasm,
+		&type_info,
+		&func_list,
+		&codegen_luajit::Options::default(),
+		&mut output,
+	)
+	.unwrap();
+
+	let output = String::from_utf8(output).unwrap();
+
+	assert!(output.contains("\terror(\"unsupported: F32x4RelaxedMadd\")\n"));
+	assert!(output.contains("\terror(\"unsupported: I8x16RelaxedSwizzle\")\n"));
+}
+
+#[test]
+fn typed_reference_select() {
+	let source = r#"
+		(module
+			(table $tab 2 funcref)
+			(func $one)
+			(elem (i32.const 0) $one)
+			(func (export "pick") (param i32)
+				(table.set $tab (i32.const 1)
+					(select (result funcref)
+						(table.get $tab (i32.const 1))
+						(table.get $tab (i32.const 0))
+						(local.get 0)
+					)
+				)
+			)
+			(func (export "num") (param i32) (result i32)
+				(select (result i32) (i32.const 1) (i32.const 2) (local.get 0))
+			)
+		)
+	"#;
+	let luajit = luajit(source);
+	let luau = luau(source);
+
+	// The first operand is the empty entry, which `and`/`or` would pass over
+	assert!(luajit.contains("if loc_0 ~= 0 then\n\t\treg_1 = reg_1\n\telse\n\t\treg_1 = reg_2\n"));
+	assert!(luau.contains("\t\tif loc_0 ~= 0 then\n\t\t\twhile true do\n\t\t\t\treg_1 = reg_1\n"));
+	assert!(luau.contains("\t\telse\n\t\t\twhile true do\n\t\t\t\treg_1 = reg_2\n"));
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains("rt.table.set(TABLE_LIST[0], reg_0, reg_1)\n"));
+	}
+
+	// Numbers are never false, so typed numeric selects keep the expression
+	assert!(luajit.contains("reg_0 = (loc_0 ~= 0 and 1 or 2)\n"));
+	assert!(luau.contains("reg_0 = (if loc_0 ~= 0 then 1 else 2)\n"));
+}
+
+#[test]
+fn single_function_patch() {
+	let source = r#"
+		(module
+			(import "env" "log" (func $log (param i32)))
+			(memory 1)
+			(func $id (param i32) (result i32) (local.get 0))
+			(func (export "run") (param i32) (result i32)
+				(call $log (i32.load (local.get 0)))
+				(i32.add (call $id (local.get 0)) (i32.const 1))
+			)
+		)
+	"#;
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+
+	let luajit_options = codegen_luajit::Options {
+		internal_lists: true,
+		..Default::default()
+	};
+	let luau_options = codegen_luau::Options {
+		internal_lists: true,
+		..Default::default()
+	};
+
+	let mut luajit = Vec::new();
+	let mut luau = Vec::new();
+
+	codegen_luajit::from_func_patch(&wasm, &type_info, 2, &luajit_options, &mut luajit).unwrap();
+	codegen_luau::from_func_patch(&wasm, &type_info, 2, &luau_options, &mut luau).unwrap();
+
+	// The chunk binds only what it uses from the instance and the runtime
+	for output in [luajit, luau] {
+		let output = String::from_utf8(output).unwrap();
+		let line_list = [
+			"local internal_list = ...\n",
+			"local FUNC_LIST = internal_list.FUNC_LIST\n",
+			"local MEMORY_LIST = internal_list.MEMORY_LIST\n",
+			"local add_i32 = rt.add.i32\n",
+			"local load_i32 = rt.load.i32\n",
+			"memory_at_0 = MEMORY_LIST[0]\n",
+			"FUNC_LIST[2] = function(loc_0)\n",
+			"FUNC_LIST[0](load_i32(memory_at_0, loc_0))\n",
+			"reg_0 = FUNC_LIST[1](loc_0)\n",
+		];
+
+		let mut last = 0;
+
+		for line in line_list {
+			let position = output[last..].find(line).unwrap() + last;
+
+			last = position + line.len();
+		}
+
+		assert!(!output.contains("FUNC_LIST[1] ="));
+		assert!(!output.contains("TABLE_LIST") && !output.contains("GLOBAL_LIST"));
+		assert!(!output.contains("run_init_code") && !output.contains("return function"));
+	}
+
+	let luajit = luajit_with(source, &luajit_options);
+	let luau = luau_with(source, &luau_options);
+	let line = "\t\tinternal_list = { FUNC_LIST = FUNC_LIST, MEMORY_LIST = MEMORY_LIST, },\n";
+
+	for output in [&luajit, &luau] {
+		assert!(output.contains(line));
+	}
+
+	// Imported functions have no body to write
+	let error =
+		codegen_luajit::from_func_patch(&wasm, &type_info, 0, &luajit_options, &mut Vec::new())
+			.unwrap_err();
+
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+	assert_eq!(error.to_string(), "function 0 is not defined by the module");
+
+	let error = codegen_luau::from_func_patch(
+		&wasm,
+		&type_info,
+		2,
+		&codegen_luau::Options {
+			depth_limit: Some(100),
+			..Default::default()
+		},
+		&mut Vec::new(),
+	)
+	.unwrap_err();
+
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+// Constants are formatted by Rust itself, which never consults the locale of
+// the host, so the digits and the `.` separator are the same everywhere
+#[test]
+fn float_formatting_is_deterministic() {
+	let source = r#"
+		(module
+			(func (export "a") (result f64) f64.const -0.0)
+			(func (export "b") (result f64) f64.const 1.5)
+			(func (export "c") (result f64) f64.const 123456.789)
+			(func (export "d") (result f64) f64.const 1e300)
+			(func (export "e") (result f32) f32.const 3.4028235e38)
+			(func (export "f") (result f64) f64.const 0x0.fffffffffffffp-1022)
+		)
+	"#;
+
+	let exact = [
+		("-0e0", -0.0),
+		("1.5e0", 1.5),
+		("1.23456789e5", 123_456.789),
+		("1e300", 1e300),
+		("3.4028234663852886e38", f64::from(f32::MAX)),
+		(
+			"2.225073858507201e-308",
+			f64::from_bits(0x000F_FFFF_FFFF_FFFF),
+		),
+	];
+
+	for (first, second) in [
+		(luajit(source), luajit(source)),
+		(luau(source), luau(source)),
+	] {
+		assert_eq!(first, second);
+
+		for (literal, number) in exact {
+			assert!(first.contains(&format!("reg_0 = {literal}\n")));
+			assert_eq!(literal.parse::<f64>().unwrap().to_bits(), number.to_bits());
+		}
+	}
+}
+
+#[test]
+fn max_stack_depth() {
+	let source = r#"
+		(module
+			(func $pair (param i32 i32) (result i32 i32)
+				local.get 0
+				local.get 1
+			)
+			(func (export "nested") (result i32)
+				(i32.add
+					(i32.const 1)
+					(i32.mul (i32.const 2) (i32.add (i32.const 3) (i32.const 4)))
+				)
+			)
+			(func (export "calls") (result i32)
+				i32.const 1
+				i32.const 2
+				i32.const 3
+				call $pair
+				i32.add
+				i32.add
+			)
+			(func (export "dead") (result i32)
+				(block (result i32)
+					i32.const 1
+					br 0
+					i32.const 2
+					i32.const 3
+					i32.const 4
+					i32.const 5
+					drop
+					drop
+					drop
+				)
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let mut builder = Factory::from_type_info(&type_info);
+
+	for (i, func) in wasm.code_section().iter().enumerate() {
+		let depth = stack_depth::max_stack_depth(&type_info, i, func).unwrap();
+		let ast = builder.create_indexed(i, func).unwrap();
+
+		assert_eq!(depth, [2, 4, 3, 1][i], "function {i}");
+		assert!(ast.num_stack() <= depth, "function {i}");
+	}
+}
+
+#[test]
+fn unused_labels_are_skipped() {
+	let source = r#"
+		(module
+			(func (export "exit") (param i32) (result i32)
+				(block $exit (result i32)
+					(i32.add (local.get 0) (i32.const 1))
+					(br $exit)
+				)
+			)
+			(func (export "skip") (param i32) (result i32)
+				(block $skip
+					(br_if $skip (local.get 0))
+					(local.set 0 (i32.const 2))
+				)
+				(local.get 0)
+			)
+		)
+	"#;
+
+	let output = luajit(source);
+	let (exit, skip) = output.split_once("FUNC_LIST[1] =").unwrap();
+
+	assert!(!exit.contains("goto"), "{output}");
+	assert!(!exit.contains("::continue_at_"), "{output}");
+	assert!(skip.contains("goto continue_at_1"), "{output}");
+	assert!(skip.contains("::continue_at_1::"), "{output}");
+}
+
+#[test]
+fn output_size_limit() {
+	let source = r#"
+		(module
+			(memory 1)
+			(data (i32.const 0) "a very long string of data that keeps going on and on and on")
+			(func (export "add") (param i32 i32) (result i32)
+				(i32.add (local.get 0) (local.get 1))
+			)
+		)
+	"#;
+
+	let data = encode(source);
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+	let full = luajit(source).len();
+
+	let options = codegen_luajit::Options {
+		output_size_limit: Some(256),
+		..Default::default()
+	};
+	let mut output = Vec::new();
+	let error =
+		codegen_luajit::from_module_typed(&wasm, &type_info, &options, &mut output).unwrap_err();
+
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	assert!(error.to_string().contains("256 bytes"), "{error}");
+	assert!(output.len() <= 256);
+
+	let options = codegen_luau::Options {
+		output_size_limit: Some(256),
+		..Default::default()
+	};
+	let mut output = Vec::new();
+	let error =
+		codegen_luau::from_module_typed(&wasm, &type_info, &options, &mut output).unwrap_err();
+
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	assert!(output.len() <= 256);
+
+	let options = codegen_luajit::Options {
+		output_size_limit: Some(full),
+		..Default::default()
+	};
+
+	assert_eq!(luajit_with(source, &options).len(), full);
+}
+
+#[test]
+fn select_evaluates_both_values() {
+	let source = r#"
+		(module
+			(memory 1)
+			(global $count (mut i32) (i32.const 0))
+			(func $bump (result i32)
+				(global.set $count (i32.add (global.get $count) (i32.const 1)))
+				(global.get $count)
+			)
+			(func (export "call") (param i32) (result i32)
+				(select (call $bump) (i32.const 0) (local.get 0))
+			)
+			(func (export "trap") (param i32 i32) (result i32)
+				(select
+					(i32.div_s (i32.const 1) (local.get 1))
+					(i32.load (local.get 1))
+					(local.get 0)
+				)
+			)
+			(func (export "pure") (param i32 i32) (result i32)
+				(select (i32.add (i32.const 1) (local.get 1)) (i32.const 2) (local.get 0))
+			)
+		)
+	"#;
+
+	let output = luajit(source);
+
+	assert!(output.contains("reg_0 = FUNC_LIST[0]()\n\treg_0 = (loc_0 ~= 0 and reg_0 or 0)"));
+	assert!(output.contains(
+		"reg_0, reg_1 = div_i32(1, loc_1), load_i32(memory_at_0, loc_1)\n\treg_0 = (loc_0 ~= 0 and reg_0 or reg_1)"
+	));
+	assert!(output.contains("reg_0 = (loc_0 ~= 0 and add_i32(1, loc_1) or 2)"));
+
+	let output = luau(source);
+
+	assert!(output.contains("reg_0 = (if loc_0 ~= 0 then reg_0 else reg_1)"));
+	assert!(output.contains("reg_0 = (if loc_0 ~= 0 then add_i32(1, loc_1) else 2)"));
+}
+
+#[test]
+fn memoized_pure_functions() {
+	let source = r#"
+		(module
+			(global $g (mut i32) (i32.const 0))
+			(func (export "mul") (param i32 i32) (result i32)
+				(i32.mul (local.get 0) (local.get 1))
+			)
+			(func (export "read") (result i32)
+				(global.get $g)
+			)
+			(func (export "mix") (param i64 f32 f64) (result f64)
+				(f64.add (local.get 2) (f64.promote_f32 (local.get 1)))
+			)
+			(func (export "host") (param externref) (result i32)
+				(i32.const 1)
+			)
+		)
+	"#;
+
+	let options = codegen_luajit::Options {
+		memoize_set: [0, 1, 2, 3].into(),
+		..Default::default()
+	};
+	let output = luajit_with(source, &options);
+
+	assert!(output.contains(
+		"\tlocal memo_func, memo_cache = FUNC_LIST[0], {}\n\tFUNC_LIST[0] = function(loc_0, loc_1)\n"
+	));
+	assert!(output.contains(r#"local key = tostring(loc_0) .. "," .. tostring(loc_1)"#));
+	assert!(output.contains("\t\t\tresult = { memo_func(loc_0, loc_1) }\n"));
+	assert!(output.contains("\t\treturn unpack(result)\n"));
+	assert!(!output.contains("FUNC_LIST[1], {}"));
+	assert!(!output.contains("FUNC_LIST[3], {}"));
+
+	// Printing keeps only 14 digits, which `0.1` and `0.1 + 2^-55` share
+	let near = 0.1 + 2.0_f64.powi(-55);
+
+	assert_ne!(near.to_bits(), 0.1_f64.to_bits());
+	assert_eq!(format!("{near:.13e}"), format!("{:.13e}", 0.1));
+	assert!(output.contains(concat!(
+		r#"local key = tostring(loc_0) .. "," .. "#,
+		r#"tostring(rt.reinterpret.i64_f64(loc_1)) .. "," .. "#,
+		r#"tostring(rt.reinterpret.i64_f64(loc_2))"#,
+	)));
+
+	let options = codegen_luau::Options {
+		memoize_set: [0, 2, 3].into(),
+		..Default::default()
+	};
+	let output = luau_with(source, &options);
+
+	assert!(output.contains("\tlocal memo_func, memo_cache = FUNC_LIST[0], {}\n"));
+	assert!(output.contains("\t\treturn table.unpack(result)\n"));
+	assert!(!output.contains("FUNC_LIST[3], {}"));
+	assert!(output.contains(concat!(
+		r#"local key = string.pack("I4I4", rt.i64.into_u32(loc_0)) .. "," .. "#,
+		r#"string.pack("d", loc_1) .. "," .. string.pack("d", loc_2)"#,
+	)));
+}
+
+#[test]
+fn exception_handling() {
+	let source = r#"
+		(module
+			(tag $e (export "e") (param i32))
+			(func $raise (param i32)
+				(throw $e (local.get 0))
+			)
+			(func (export "catch") (param i32) (result i32)
+				(block $out (result i32)
+					(try (result i32)
+						(do
+							(drop (br_if $out (i32.const 9) (local.get 0)))
+							(call $raise (i32.const 7))
+							(i32.const 1)
+						)
+						(catch $e
+							(i32.add (i32.const 1))
+						)
+						(catch_all
+							(rethrow 0)
+						)
+					)
+				)
+			)
+		)
+	"#;
+
+	let output = luajit(source);
+
+	assert!(output.contains("\terror({ tag = TAG_LIST[0], loc_0 })\n"));
+	assert!(output.contains("local ok_2, exception_2 = pcall(function()\n"));
+	assert!(output.contains("\t\t\t\tdo return 1 end\n"));
+	assert!(output
+		.contains(r#"if type(exception_2) == "table" and exception_2.tag == TAG_LIST[0] then"#));
+	assert!(output.contains("\t\t\t\treg_0 = exception_2[1]\n"));
+	assert!(output.contains("\t\telseif exception_2 == 1 then\n\t\t\tgoto continue_at_1\n"));
+	assert!(output.contains("\tTAG_LIST[0] = {}\n"));
+	assert!(output.contains(r#"["e"] = TAG_LIST[0],"#));
+
+	let output = luau(source);
+
+	assert!(output.contains("local ok_2, exception_2 = pcall(function()\n"));
+	assert!(output.contains("error(exception_2, 0)"));
+	assert!(output.contains("\tTAG_LIST[0] = {}\n"));
+}
+
+#[test]
+fn caught_exceptions_restore_depth() {
+	let source = r#"
+		(module
+			(tag $e)
+			(func $raise
+				(throw $e)
+			)
+			(func (export "f") (param i32)
+				(loop $again
+					(try
+						(do
+							(call $raise)
+						)
+						(catch $e)
+					)
+					(br_if $again (local.tee 0 (i32.sub (local.get 0) (i32.const 1))))
+				)
+			)
+		)
+	"#;
+
+	let guarded_luajit = luajit_with(
+		source,
+		&codegen_luajit::Options {
+			depth_limit: Some(100),
+			..Default::default()
+		},
+	);
+
+	let guarded_luau = luau_with(
+		source,
+		&codegen_luau::Options {
+			depth_limit: Some(100),
+			..Default::default()
+		},
+	);
+
+	// `$raise` never reaches its decrement, so each catch would leak a level
+	for output in [guarded_luajit, guarded_luau] {
+		let (_, handler) = output.split_once("if not ok_2 then\n").unwrap();
+
+		assert!(output.contains("local depth_2 = CALL_DEPTH\n"));
+		assert!(handler.trim_start().starts_with("CALL_DEPTH = depth_2\n"));
+	}
+
+	for output in [luajit(source), luau(source)] {
+		assert!(!output.contains("depth_"));
+	}
+}
+
+#[test]
+fn memory_fill_zero() {
+	let source = r#"
+		(module
+			(memory 4)
+			(func (export "clear") (param i32)
+				(memory.fill (i32.const 0) (i32.const 0) (i32.const 262144))
+				(memory.fill (local.get 0) (i32.const 256) (i32.const 100))
+				(memory.fill (local.get 0) (i32.const 7) (i32.const 100))
+			)
+		)
+	"#;
+
+	let output = luau(source);
+
+	assert!(output.contains("rt.store.zero(memory_at_0, 0, 262144)\n"));
+	assert!(output.contains("rt.store.zero(memory_at_0, loc_0, 100)\n"));
+	assert!(output.contains("rt.store.fill(memory_at_0, loc_0, 100, 7)\n"));
+}
+
+#[derive(Default)]
+struct CountWrites {
+	data: Vec<u8>,
+	num_write: usize,
+}
+
+impl std::io::Write for CountWrites {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.num_write += 1;
+		self.data.extend_from_slice(buf);
+
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+#[test]
+fn buffered_output() {
+	let body =
+		"(func (param i32) (result i32) (i32.add (local.get 0) (i32.const 1)))\n".repeat(200);
+	let data = encode(&format!("(module {body})"));
+	let wasm = Module::try_from_data(&data).unwrap();
+	let type_info = TypeInfo::from_module(&wasm);
+
+	let mut buffered = CountWrites::default();
+	let mut unbuffered = CountWrites::default();
+	let options = codegen_luajit::Options {
+		write_buffer_size: Some(0),
+		..Default::default()
+	};
+
+	codegen_luajit::from_module_typed(
+		&wasm,
+		&type_info,
+		&codegen_luajit::Options::default(),
+		&mut buffered,
+	)
+	.unwrap();
+	codegen_luajit::from_module_typed(&wasm, &type_info, &options, &mut unbuffered).unwrap();
+
+	assert_eq!(buffered.data, unbuffered.data);
+	assert!(buffered.num_write * 100 < unbuffered.num_write);
+
+	let mut buffered = CountWrites::default();
+
+	codegen_luau::from_module_typed(
+		&wasm,
+		&type_info,
+		&codegen_luau::Options {
+			write_buffer_size: Some(1 << 16),
+			..Default::default()
+		},
+		&mut buffered,
+	)
+	.unwrap();
+
+	assert!(buffered.num_write <= (buffered.data.len() >> 16) + 1);
+}
+
+#[test]
+fn i31_references() {
+	let source = r#"
+		(module
+			(func (export "round_trip") (param i32) (result i32 i32)
+				(local i31ref)
+				(local.set 1 (i31.new (local.get 0)))
+				(i31.get_s (local.get 1))
+				(i31.get_u (local.get 1))
+			)
+		)
+	"#;
+
+	for output in [luajit(source), luau(source)] {
+		assert!(output.contains("local wrap_i31_i32 = rt.wrap.i31_i32\n"));
+		assert!(output.contains("loc_1 = wrap_i31_i32(loc_0)\n"));
+		assert!(output.contains("extend_i32_i31(loc_1)"));
+		assert!(output.contains("extend_u32_i31(loc_1)"));
+	}
+
+	// Struct types cannot be read yet, so such modules fail to load
+	let data = encode("(module (type (struct (field i32))))");
+
+	assert!(Module::try_from_data(&data).is_err());
+}
+
+#[test]
+fn released_externs() {
+	let source = r#"
+		(module
+			(table 1 externref)
+			(func (export "swap") (param externref) (result externref)
+				(local externref)
+				(local.set 1 (local.get 0))
+				(local.set 0 (table.get 0 (i32.const 0)))
+				(local.get 1)
+			)
+			(func (export "both") (param externref externref externref)
+				(local.set 0 (local.get 2))
+				(local.set 1 (local.get 2))
+			)
+		)
+	"#;
+
+	let options = codegen_luajit::Options {
+		release_externs: true,
+		..Default::default()
+	};
+	let output_luajit = luajit_with(source, &options);
+	let options = codegen_luau::Options {
+		release_externs: true,
+		..Default::default()
+	};
+	let output_luau = luau_with(source, &options);
+
+	for output in [&output_luajit, &output_luau] {
+		assert!(output.contains("local loc_1 = nil\n"));
+		let (swap, both) = output.split_once("FUNC_LIST[1]").unwrap();
+
+		assert!(swap.contains("loc_1 = rt.extern.replace(loc_1, loc_0, loc_0)\n"));
+		assert!(swap.contains("loc_0, reg_0 = rt.extern.replace(loc_0, reg_0, loc_1), loc_1\n"));
+		assert!(swap.contains("\trt.extern.leave({ loc_0, loc_1, }, reg_0)\n\treturn reg_0\n"));
+		assert!(both.contains("\tloc_0 = rt.extern.replace(loc_0, loc_2, loc_1, loc_2)\n"));
+		assert!(both.contains("\tloc_1 = rt.extern.replace(loc_1, loc_2, loc_0, loc_2)\n"));
+	}
+
+	for runtime in [codegen_luajit::RUNTIME, codegen_luau::RUNTIME] {
+		assert!(runtime.contains("function extern.replace(old, new, ...)"));
+		assert!(runtime.contains("if select(i, ...) == old then"));
+	}
+
+	assert!(!luajit(source).contains("rt.extern"));
+}
+
+#[test]
+fn conditional_moves() {
+	let source = r#"
+		(module
+			(func (export "max") (param i32 i32) (result i32)
+				(if (i32.lt_s (local.get 0) (local.get 1))
+					(then (local.set 0 (local.get 1)))
+				)
+				(local.get 0)
+			)
+			(func (export "pick") (param i32 externref externref) (result externref)
+				(if (local.get 0)
+					(then (local.set 1 (local.get 2)))
+				)
+				(if (local.get 0)
+					(then (local.set 0 (i32.const 1)) (local.set 0 (i32.const 2)))
+				)
+				(local.get 1)
+			)
+		)
+	"#;
+
+	let options = codegen_luajit::Options {
+		conditional_moves: true,
+		..Default::default()
+	};
+	let output = luajit_with(source, &options);
+
+	assert!(output.contains("\tloc_0 = (loc_0 < loc_1 and loc_1 or loc_0)\n"));
+	assert!(output.contains("\tif loc_0 ~= 0 then\n\t\tloc_1 = loc_2\n\tend\n"));
+	assert!(output.contains("\tif loc_0 ~= 0 then\n\t\tloc_0 = 1\n\t\tloc_0 = 2\n\tend\n"));
+	assert!(!luajit(source).contains(" and loc_1 or loc_0"));
+
+	let options = codegen_luau::Options {
+		conditional_moves: true,
+		..Default::default()
+	};
+	let output = luau_with(source, &options);
+
+	assert!(output.contains("\t\tloc_0 = if lt_i32(loc_0, loc_1) then loc_1 else loc_0\n"));
+	assert!(output.contains("\t\tloc_1 = if loc_0 ~= 0 then loc_2 else loc_1\n"));
+	assert!(output.contains("\t\tif loc_0 ~= 0 then\n\t\t\twhile true do\n\t\t\t\tloc_0 = 1\n"));
+}
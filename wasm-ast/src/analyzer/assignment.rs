@@ -0,0 +1,103 @@
+use std::collections::BTreeSet;
+
+use crate::{
+	node::{Expression, Local, Statement, Temporary},
+	visit::{Driver, Visitor},
+};
+
+// Longer runs risk exhausting the registers Lua needs for the right hand sides
+const MAX_RUN_LEN: usize = 8;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Variable {
+	Temporary(usize),
+	Local(usize),
+}
+
+fn as_assignment<'a>(
+	stat: &'a Statement,
+	register_of: &dyn Fn(usize) -> usize,
+) -> Option<(Variable, &'a Expression)> {
+	match stat {
+		Statement::SetTemporary(s) => {
+			Some((Variable::Temporary(register_of(s.var().var())), s.value()))
+		}
+		Statement::SetLocal(s) => Some((Variable::Local(s.var().var()), s.value())),
+		_ => None,
+	}
+}
+
+struct Visit<'a> {
+	register_of: &'a dyn Fn(usize) -> usize,
+	read_set: BTreeSet<Variable>,
+}
+
+impl Visitor for Visit<'_> {
+	fn visit_get_temporary(&mut self, v: Temporary) {
+		let register = (self.register_of)(v.var());
+
+		self.read_set.insert(Variable::Temporary(register));
+	}
+
+	fn visit_get_local(&mut self, v: Local) {
+		self.read_set.insert(Variable::Local(v.var()));
+	}
+}
+
+fn read_set(value: &Expression, register_of: &dyn Fn(usize) -> usize) -> BTreeSet<Variable> {
+	let mut visit = Visit {
+		register_of,
+		read_set: BTreeSet::new(),
+	};
+
+	value.accept(&mut visit);
+
+	visit.read_set
+}
+
+/// Splits the code into runs of statements that can be written at once.
+///
+/// Every run longer than one is made of assignments that can be written as a
+/// single multiple assignment. Assignments only join a run if they neither
+/// read nor overwrite a variable assigned earlier in it, so evaluating all
+/// values up front is equivalent. Temporaries are compared by the register
+/// `register_of` gives them, as coalesced ones share a name in the output.
+#[must_use]
+pub fn visit<'a>(
+	code: &'a [Statement],
+	register_of: &dyn Fn(usize) -> usize,
+) -> Vec<&'a [Statement]> {
+	let mut list = Vec::new();
+	let mut start = 0;
+	let mut write_set = BTreeSet::new();
+
+	for (i, stat) in code.iter().enumerate() {
+		let Some((var, value)) = as_assignment(stat, register_of) else {
+			list.extend((start < i).then(|| &code[start..i]));
+			list.push(&code[i..=i]);
+
+			start = i + 1;
+			write_set.clear();
+
+			continue;
+		};
+
+		let is_dependent = i - start == MAX_RUN_LEN
+			|| write_set.contains(&var)
+			|| read_set(value, register_of)
+				.iter()
+				.any(|v| write_set.contains(v));
+
+		if is_dependent {
+			list.push(&code[start..i]);
+
+			start = i;
+			write_set.clear();
+		}
+
+		write_set.insert(var);
+	}
+
+	list.extend((start < code.len()).then(|| &code[start..]));
+	list
+}
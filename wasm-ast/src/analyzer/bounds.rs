@@ -0,0 +1,114 @@
+use crate::{
+	module::Module,
+	node::{
+		Call, CallIndirect, Catch, FuncData, GetGlobal, LoadAt, MemoryCopy, MemoryFill, MemoryGrow,
+		MemorySize, SetGlobal, StoreAt, TableGet, TableSet, Throw,
+	},
+	visit::{Driver, Visitor},
+};
+
+struct Visit {
+	function_space: usize,
+	table_space: usize,
+	memory_space: usize,
+	global_space: usize,
+	tag_space: usize,
+	error: Option<String>,
+}
+
+impl Visit {
+	fn check(&mut self, kind: &str, index: usize, space: usize) {
+		if index < space || self.error.is_some() {
+			return;
+		}
+
+		self.error = Some(format!("{kind} {index} is out of bounds of {space}"));
+	}
+
+	fn check_memory(&mut self, memory: usize) {
+		self.check("memory", memory, self.memory_space);
+	}
+
+	fn check_global(&mut self, global: usize) {
+		self.check("global", global, self.global_space);
+	}
+}
+
+impl Visitor for Visit {
+	fn visit_get_global(&mut self, get: GetGlobal) {
+		self.check_global(get.var());
+	}
+
+	fn visit_load_at(&mut self, load: &LoadAt) {
+		self.check_memory(load.memory());
+	}
+
+	fn visit_memory_size(&mut self, size: &MemorySize) {
+		self.check_memory(size.memory());
+	}
+
+	fn visit_call(&mut self, call: &Call) {
+		self.check("function", call.function(), self.function_space);
+	}
+
+	fn visit_call_indirect(&mut self, call: &CallIndirect) {
+		self.check("table", call.table(), self.table_space);
+	}
+
+	fn visit_set_global(&mut self, set: &SetGlobal) {
+		self.check_global(set.var());
+	}
+
+	fn visit_store_at(&mut self, store: &StoreAt) {
+		self.check_memory(store.memory());
+	}
+
+	fn visit_memory_grow(&mut self, grow: &MemoryGrow) {
+		self.check_memory(grow.memory());
+	}
+
+	fn visit_memory_copy(&mut self, copy: &MemoryCopy) {
+		self.check_memory(copy.destination().memory());
+		self.check_memory(copy.source().memory());
+	}
+
+	fn visit_memory_fill(&mut self, fill: &MemoryFill) {
+		self.check_memory(fill.destination().memory());
+	}
+
+	fn visit_table_get(&mut self, get: &TableGet) {
+		self.check("table", get.table(), self.table_space);
+	}
+
+	fn visit_table_set(&mut self, set: &TableSet) {
+		self.check("table", set.table(), self.table_space);
+	}
+
+	fn visit_throw(&mut self, throw: &Throw) {
+		self.check("tag", throw.tag(), self.tag_space);
+	}
+
+	fn visit_catch(&mut self, catch: &Catch) {
+		self.check("tag", catch.tag(), self.tag_space);
+	}
+}
+
+/// Returns a message naming the first item out of bounds of the module.
+///
+/// Syntax trees built or edited by hand may name items the module never
+/// declared, which would otherwise only fail once the code runs.
+#[must_use]
+pub fn visit(ast: &FuncData, wasm: &Module) -> Option<String> {
+	let mut visit = Visit {
+		function_space: wasm.function_space(),
+		table_space: wasm.table_space(),
+		memory_space: wasm.memory_space(),
+		global_space: wasm.global_space(),
+		tag_space: wasm.tag_space(),
+		error: None,
+	};
+
+	ast.accept(&mut visit);
+
+	visit.error
+}
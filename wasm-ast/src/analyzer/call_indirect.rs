@@ -0,0 +1,28 @@
+use crate::{
+	node::{CallIndirect, FuncData},
+	visit::{Driver, Visitor},
+};
+
+#[derive(Default)]
+struct Visit {
+	count: usize,
+}
+
+impl Visitor for Visit {
+	fn visit_call_indirect(&mut self, _: &CallIndirect) {
+		self.count += 1;
+	}
+}
+
+/// Returns the number of indirect calls in the function.
+///
+/// Each indirect call gets its own cache, so they are counted up front to
+/// declare the locals holding them.
+#[must_use]
+pub fn visit(ast: &FuncData) -> usize {
+	let mut visit = Visit::default();
+
+	ast.accept(&mut visit);
+
+	visit.count
+}
@@ -0,0 +1,143 @@
+use std::ops::Range;
+
+use crate::{
+	node::{
+		Br, BrIf, BrTable, Call, CallIndirect, Catch, FuncData, MemoryGrow, ResultList,
+		SetTemporary, TableGet, Temporary,
+	},
+	visit::{Driver, Visitor},
+};
+
+struct Visit {
+	range_list: Vec<Option<Range<usize>>>,
+	position: usize,
+}
+
+impl Visit {
+	fn add_temporary(&mut self, var: usize) {
+		let position = self.position;
+		let range = self.range_list[var].get_or_insert(position..position);
+
+		range.start = range.start.min(position);
+		range.end = range.end.max(position);
+	}
+
+	// Values named together are read or written at once, so they share a
+	// position and can never share a register
+	fn add_result_list(&mut self, list: ResultList) {
+		for temporary in list.iter() {
+			self.add_temporary(temporary.var());
+		}
+
+		self.position += 1;
+	}
+
+	fn add_br(&mut self, br: Br) {
+		for temporary in br
+			.align()
+			.new_range()
+			.iter()
+			.chain(br.align().old_range().iter())
+		{
+			self.add_temporary(temporary.var());
+		}
+
+		self.position += 1;
+	}
+}
+
+impl Visitor for Visit {
+	fn visit_get_temporary(&mut self, temporary: Temporary) {
+		self.add_temporary(temporary.var());
+		self.position += 1;
+	}
+
+	fn visit_set_temporary(&mut self, set: &SetTemporary) {
+		self.add_temporary(set.var().var());
+		self.position += 1;
+	}
+
+	fn visit_call(&mut self, call: &Call) {
+		self.add_result_list(call.result_list());
+	}
+
+	fn visit_call_indirect(&mut self, call: &CallIndirect) {
+		self.add_result_list(call.result_list());
+	}
+
+	fn visit_memory_grow(&mut self, grow: &MemoryGrow) {
+		self.add_temporary(grow.result().var());
+		self.position += 1;
+	}
+
+	fn visit_table_get(&mut self, get: &TableGet) {
+		self.add_temporary(get.result().var());
+		self.position += 1;
+	}
+
+	fn visit_br(&mut self, br: Br) {
+		self.add_br(br);
+	}
+
+	fn visit_br_if(&mut self, br_if: &BrIf) {
+		self.add_br(br_if.target());
+	}
+
+	fn visit_br_table(&mut self, table: &BrTable) {
+		for &br in table.data() {
+			self.add_br(br);
+		}
+
+		self.add_br(table.default());
+	}
+
+	fn visit_catch(&mut self, catch: &Catch) {
+		self.add_result_list(catch.result_list());
+	}
+}
+
+/// Returns the register of each temporary and the number of registers.
+///
+/// Temporaries come from the operand stack, so a value is only carried
+/// around a loop through its parameters, and those are always named by the
+/// branch that continues it. Spans over the code in order are then enough
+/// to tell which temporaries are never live at once.
+#[must_use]
+pub fn visit(ast: &FuncData) -> (Vec<usize>, usize) {
+	let mut visit = Visit {
+		range_list: vec![None; ast.num_stack()],
+		position: 0,
+	};
+
+	ast.accept(&mut visit);
+	visit.add_result_list(ResultList::new(0, ast.num_result()));
+
+	let mut order: Vec<_> = (0..ast.num_stack()).collect();
+
+	order.sort_by_key(|&var| visit.range_list[var].as_ref().map(|v| v.start));
+
+	let mut register_map = vec![0; ast.num_stack()];
+	let mut last_of = Vec::new();
+
+	for var in order {
+		let Some(range) = &visit.range_list[var] else {
+			continue;
+		};
+
+		// The lowest register free by the start of this span is taken
+		let register = last_of
+			.iter()
+			.position(|&last| last < range.start)
+			.unwrap_or(last_of.len());
+
+		if register == last_of.len() {
+			last_of.push(range.end);
+		} else {
+			last_of[register] = range.end;
+		}
+
+		register_map[var] = register;
+	}
+
+	(register_map, last_of.len())
+}
@@ -0,0 +1,19 @@
+use crate::node::{If, SetLocal, Statement};
+
+/// Returns the assignment of an `if` that can always be made.
+///
+/// An `if` with no `else` that only sets a local can always set it, keeping
+/// the old value when the condition fails.
+#[must_use]
+pub fn visit(node: &If) -> Option<&SetLocal> {
+	let on_true = node.on_true();
+
+	if node.on_false().is_some() || on_true.label_type().is_some() || on_true.last().is_some() {
+		return None;
+	}
+
+	match on_true.code() {
+		[Statement::SetLocal(v)] => Some(v),
+		_ => None,
+	}
+}
@@ -0,0 +1,77 @@
+use crate::{
+	node::{BinOpType, Block, CmpOp, CmpOpType, Expression, SetLocal, Statement, Value},
+	visit::{Driver, Visitor},
+};
+
+use super::repeat_loop;
+
+#[derive(Default)]
+struct Visit {
+	set_list: Vec<usize>,
+}
+
+impl Visitor for Visit {
+	fn visit_set_local(&mut self, set: &SetLocal) {
+		self.set_list.push(set.var().var());
+	}
+}
+
+fn is_increment(value: &Expression, var: usize) -> bool {
+	let Expression::BinOp(op) = value else {
+		return false;
+	};
+
+	matches!(op.op_type(), BinOpType::Add_I32)
+		&& matches!(op.lhs(), Expression::GetLocal(v) if v.var() == var)
+		&& matches!(op.rhs(), Expression::Value(Value::I32(1)))
+}
+
+fn is_invariant(bound: &Expression, set_list: &[usize]) -> bool {
+	match bound {
+		Expression::Value(_) => true,
+		Expression::GetLocal(v) => !set_list.contains(&v.var()),
+		_ => false,
+	}
+}
+
+/// Returns the increment and the test of a loop that counts one local up
+/// by one on every pass until it reaches a bound fixed for the whole loop.
+///
+/// The local may be set nowhere else, so the number of passes is known as
+/// soon as the loop is entered.
+#[must_use]
+pub fn visit(block: &Block) -> Option<(&Expression, &CmpOp)> {
+	let br_if = repeat_loop::visit(block)?;
+	let Expression::CmpOp(condition) = br_if.condition() else {
+		return None;
+	};
+
+	if !matches!(
+		condition.op_type(),
+		CmpOpType::LtS_I32 | CmpOpType::LtU_I32 | CmpOpType::Ne_I32
+	) {
+		return None;
+	}
+
+	let Expression::GetLocal(counter) = condition.lhs() else {
+		return None;
+	};
+
+	let var = counter.var();
+	let mut visit = Visit::default();
+
+	block.accept(&mut visit);
+
+	if visit.set_list.iter().filter(|&&v| v == var).count() != 1
+		|| !is_invariant(condition.rhs(), &visit.set_list)
+	{
+		return None;
+	}
+
+	block.code().iter().find_map(|stat| match stat {
+		Statement::SetLocal(v) if v.var().var() == var && is_increment(v.value(), var) => {
+			Some((v.value(), condition))
+		}
+		_ => None,
+	})
+}
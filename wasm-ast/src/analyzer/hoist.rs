@@ -0,0 +1,149 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+	node::{
+		Block, Call, CallIndirect, Expression, FuncData, LabelType, LoadAt, MemoryCopy, MemoryFill,
+		MemoryGrow, SetGlobal, SetLocal, Statement, StoreAt,
+	},
+	visit::{Driver, Visitor},
+};
+
+// Each hoisted load takes a register so only the first few are kept
+const MAX_LOAD_COUNT: usize = 8;
+
+#[derive(Default)]
+struct Effect {
+	has_effect: bool,
+	set_list: BTreeSet<usize>,
+}
+
+impl Visitor for Effect {
+	fn visit_call(&mut self, _: &Call) {
+		self.has_effect = true;
+	}
+
+	fn visit_call_indirect(&mut self, _: &CallIndirect) {
+		self.has_effect = true;
+	}
+
+	fn visit_set_local(&mut self, set: &SetLocal) {
+		self.set_list.insert(set.var().var());
+	}
+
+	fn visit_set_global(&mut self, _: &SetGlobal) {
+		self.has_effect = true;
+	}
+
+	fn visit_store_at(&mut self, _: &StoreAt) {
+		self.has_effect = true;
+	}
+
+	fn visit_memory_grow(&mut self, _: &MemoryGrow) {
+		self.has_effect = true;
+	}
+
+	fn visit_memory_copy(&mut self, _: &MemoryCopy) {
+		self.has_effect = true;
+	}
+
+	fn visit_memory_fill(&mut self, _: &MemoryFill) {
+		self.has_effect = true;
+	}
+}
+
+fn is_invariant(pointer: &Expression, set_list: &BTreeSet<usize>) -> bool {
+	match pointer {
+		Expression::Value(_) => true,
+		Expression::GetLocal(v) => !set_list.contains(&v.var()),
+		Expression::UnOp(v) => is_invariant(v.rhs(), set_list),
+		Expression::BinOp(v) => is_invariant(v.lhs(), set_list) && is_invariant(v.rhs(), set_list),
+		_ => false,
+	}
+}
+
+// Only one side of a `Select` runs, so loads under one are left in place
+fn add_load_list<'a>(
+	expression: &'a Expression,
+	set_list: &BTreeSet<usize>,
+	list: &mut Vec<&'a LoadAt>,
+) {
+	match expression {
+		Expression::LoadAt(v) if is_invariant(v.pointer(), set_list) => list.push(v),
+		Expression::LoadAt(v) => add_load_list(v.pointer(), set_list, list),
+		Expression::UnOp(v) => add_load_list(v.rhs(), set_list, list),
+		Expression::BinOp(v) => {
+			add_load_list(v.lhs(), set_list, list);
+			add_load_list(v.rhs(), set_list, list);
+		}
+		Expression::CmpOp(v) => {
+			add_load_list(v.lhs(), set_list, list);
+			add_load_list(v.rhs(), set_list, list);
+		}
+		_ => {}
+	}
+}
+
+/// Returns the loads that can be read once before the loop is entered.
+///
+/// Nothing in the loop may write memory or globals, and the loads must run
+/// on every pass before any branch so that hoisting them adds no trap.
+#[must_use]
+pub fn hoisted_list(block: &Block) -> Vec<&LoadAt> {
+	if block.label_type() != Some(LabelType::Backward) {
+		return Vec::new();
+	}
+
+	let mut effect = Effect::default();
+
+	block.accept(&mut effect);
+
+	if effect.has_effect {
+		return Vec::new();
+	}
+
+	let mut list = Vec::new();
+
+	for stat in block.code() {
+		let value = match stat {
+			Statement::SetTemporary(v) => v.value(),
+			Statement::SetLocal(v) => v.value(),
+			Statement::Block(_) | Statement::BrIf(_) | Statement::If(_) | Statement::Try(_) => {
+				break
+			}
+			_ => continue,
+		};
+
+		add_load_list(value, &effect.set_list, &mut list);
+	}
+
+	list
+}
+
+#[derive(Default)]
+struct Visit {
+	load_map: HashMap<usize, usize>,
+}
+
+impl Visitor for Visit {
+	fn visit_block(&mut self, block: &Block) {
+		for load in hoisted_list(block) {
+			let len = self.load_map.len();
+
+			if len == MAX_LOAD_COUNT {
+				break;
+			}
+
+			self.load_map.insert(load as *const _ as usize, len);
+		}
+	}
+}
+
+/// Maps each hoisted load to the register it is read into.
+#[must_use]
+pub fn visit(ast: &FuncData) -> HashMap<usize, usize> {
+	let mut visit = Visit::default();
+
+	ast.accept(&mut visit);
+
+	visit.load_map
+}
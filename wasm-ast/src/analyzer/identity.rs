@@ -0,0 +1,54 @@
+use crate::node::{BinOp, BinOpType, Expression, Value};
+
+fn is_constant(expression: &Expression, wanted: i64) -> bool {
+	match *expression {
+		Expression::Value(Value::I32(value)) => i64::from(value) == wanted,
+		Expression::Value(Value::I64(value)) => value == wanted,
+		_ => false,
+	}
+}
+
+/// Returns the operand an integer operation always evaluates to.
+///
+/// This is `x` for `x + 0` and the like. Integer values are kept wrapped to
+/// their width, so these hold for every value of `x`. Float operations are
+/// never folded since `-0 + 0` is `+0`.
+#[must_use]
+pub fn as_identity(op: &BinOp) -> Option<&Expression> {
+	let (lhs, rhs) = (op.lhs(), op.rhs());
+
+	match op.op_type() {
+		BinOpType::Add_I32
+		| BinOpType::Add_I64
+		| BinOpType::Or_I32
+		| BinOpType::Or_I64
+		| BinOpType::Xor_I32
+		| BinOpType::Xor_I64 => {
+			if is_constant(rhs, 0) {
+				Some(lhs)
+			} else if is_constant(lhs, 0) {
+				Some(rhs)
+			} else {
+				None
+			}
+		}
+		BinOpType::Mul_I32 | BinOpType::Mul_I64 => {
+			if is_constant(rhs, 1) {
+				Some(lhs)
+			} else if is_constant(lhs, 1) {
+				Some(rhs)
+			} else {
+				None
+			}
+		}
+		BinOpType::Sub_I32
+		| BinOpType::Sub_I64
+		| BinOpType::Shl_I32
+		| BinOpType::Shl_I64
+		| BinOpType::ShrS_I32
+		| BinOpType::ShrS_I64
+		| BinOpType::ShrU_I32
+		| BinOpType::ShrU_I64 => is_constant(rhs, 0).then_some(lhs),
+		_ => None,
+	}
+}
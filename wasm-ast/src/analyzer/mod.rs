@@ -0,0 +1,13 @@
+//! Passes over syntax trees that both backends use to decide what to write.
+//! None of them write code, so each only answers questions about a tree.
+
+pub mod assignment;
+pub mod bounds;
+pub mod call_indirect;
+pub mod coalesce;
+pub mod conditional_move;
+pub mod counting_loop;
+pub mod hoist;
+pub mod identity;
+pub mod pure;
+pub mod repeat_loop;
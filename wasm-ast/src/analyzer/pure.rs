@@ -0,0 +1,62 @@
+use crate::{
+	node::{
+		Call, CallIndirect, Catch, FuncData, GetGlobal, SetGlobal, TableGet, TableSet, Throw, Try,
+	},
+	visit::{Driver, Visitor},
+};
+
+struct Visit {
+	is_pure: bool,
+}
+
+impl Visitor for Visit {
+	fn visit_get_global(&mut self, _: GetGlobal) {
+		self.is_pure = false;
+	}
+
+	fn visit_set_global(&mut self, _: &SetGlobal) {
+		self.is_pure = false;
+	}
+
+	fn visit_call(&mut self, _: &Call) {
+		self.is_pure = false;
+	}
+
+	fn visit_call_indirect(&mut self, _: &CallIndirect) {
+		self.is_pure = false;
+	}
+
+	fn visit_table_get(&mut self, _: &TableGet) {
+		self.is_pure = false;
+	}
+
+	fn visit_table_set(&mut self, _: &TableSet) {
+		self.is_pure = false;
+	}
+
+	fn visit_throw(&mut self, _: &Throw) {
+		self.is_pure = false;
+	}
+
+	fn visit_catch(&mut self, _: &Catch) {
+		self.is_pure = false;
+	}
+
+	fn visit_try(&mut self, _: &Try) {
+		self.is_pure = false;
+	}
+}
+
+/// Returns whether the function only depends on its parameters and memory.
+///
+/// Memory accesses are left for callers to find, so only globals, tables,
+/// calls, and exceptions are checked, the last as handlers read the list
+/// of tags.
+#[must_use]
+pub fn visit(ast: &FuncData) -> bool {
+	let mut visit = Visit { is_pure: true };
+
+	ast.accept(&mut visit);
+
+	visit.is_pure
+}
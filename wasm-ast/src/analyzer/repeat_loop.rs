@@ -0,0 +1,62 @@
+use crate::node::{Block, Br, BrIf, LabelType, Statement, Terminator};
+
+const fn is_target(br: Br, depth: usize) -> bool {
+	br.target() == depth
+}
+
+fn has_branch_to(code: &[Statement], last: Option<&Terminator>, depth: usize) -> bool {
+	let in_code = code.iter().any(|stat| match stat {
+		Statement::Block(v) => has_branch_to(v.code(), v.last(), depth + 1),
+		Statement::BrIf(v) => is_target(v.target(), depth),
+		Statement::If(v) => {
+			let on_true = v.on_true();
+
+			has_branch_to(on_true.code(), on_true.last(), depth + 1)
+				|| v.on_false()
+					.is_some_and(|v| has_branch_to(v.code(), v.last(), depth + 1))
+		}
+		Statement::Try(v) => {
+			let body = v.body();
+
+			has_branch_to(body.code(), body.last(), depth + 1)
+				|| v.catch_list()
+					.iter()
+					.any(|v| has_branch_to(v.block().code(), v.block().last(), depth + 1))
+				|| v.catch_all()
+					.is_some_and(|v| has_branch_to(v.code(), v.last(), depth + 1))
+		}
+		_ => false,
+	});
+
+	in_code
+		|| match last {
+			Some(Terminator::Br(v)) => is_target(*v, depth),
+			Some(Terminator::BrTable(v)) => {
+				is_target(v.default(), depth) || v.data().iter().any(|&v| is_target(v, depth))
+			}
+			_ => false,
+		}
+}
+
+/// Returns the `br_if` that alone continues the loop, placed at its end.
+///
+/// Such a loop runs its body once and then again while the condition holds,
+/// which is what `repeat` does without needing `continue`.
+#[must_use]
+pub fn visit(block: &Block) -> Option<&BrIf> {
+	if block.label_type() != Some(LabelType::Backward) || block.last().is_some() {
+		return None;
+	}
+
+	let Some((Statement::BrIf(last), code)) = block.code().split_last() else {
+		return None;
+	};
+
+	let target = last.target();
+
+	if target.target() != 0 || !target.align().is_aligned() || has_branch_to(code, None, 0) {
+		return None;
+	}
+
+	Some(last)
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeSet;
+
+use wasmparser::{ElementItems, ExternalKind, Operator, OperatorsReader};
+
+use crate::{
+	module::{External, Module},
+	node::{Call, CallIndirect, FuncData},
+	visit::{Driver, Visitor},
+};
+
+struct Visit {
+	callee_set: BTreeSet<usize>,
+	has_indirect: bool,
+}
+
+impl Visitor for Visit {
+	fn visit_call(&mut self, call: &Call) {
+		self.callee_set.insert(call.function());
+	}
+
+	fn visit_call_indirect(&mut self, _: &CallIndirect) {
+		self.has_indirect = true;
+	}
+}
+
+fn load_ref_func_list(reader: OperatorsReader, func_set: &mut BTreeSet<usize>) {
+	for op in reader {
+		if let Ok(Operator::RefFunc { function_index }) = op {
+			func_set.insert(function_index as usize);
+		}
+	}
+}
+
+// Any function placed in a table may be the target of an indirect call. Code
+// can store those it names with `ref.func` into one, and the host those that
+// are exported, so both count as placed
+fn load_table_func_set(wasm: &Module) -> BTreeSet<usize> {
+	let mut func_set = BTreeSet::new();
+
+	for global in wasm.global_section() {
+		load_ref_func_list(global.init_expr.get_operators_reader(), &mut func_set);
+	}
+
+	for body in wasm.code_section() {
+		if let Ok(reader) = body.get_operators_reader() {
+			load_ref_func_list(reader, &mut func_set);
+		}
+	}
+
+	let export_iter = wasm
+		.export_section()
+		.iter()
+		.filter(|v| v.kind == ExternalKind::Func)
+		.map(|v| v.index as usize);
+
+	func_set.extend(export_iter);
+
+	for element in wasm.element_section() {
+		match element.items.clone() {
+			ElementItems::Functions(functions) => {
+				func_set.extend(functions.into_iter().map(|v| v.unwrap() as usize));
+			}
+			ElementItems::Expressions(expressions) => {
+				for init in expressions {
+					load_ref_func_list(init.unwrap().get_operators_reader(), &mut func_set);
+				}
+			}
+		}
+	}
+
+	func_set
+}
+
+// Tarjan's algorithm, driven by an explicit stack so deep call chains
+// cannot overflow the host's own
+fn find_component_list(edge_list: &[Vec<usize>]) -> Vec<Vec<usize>> {
+	const UNVISITED: usize = usize::MAX;
+
+	let mut order_of = vec![UNVISITED; edge_list.len()];
+	let mut low_of = vec![0; edge_list.len()];
+	let mut on_stack = vec![false; edge_list.len()];
+	let mut stack = Vec::new();
+	let mut component_list = Vec::new();
+	let mut next = 0;
+
+	for root in 0..edge_list.len() {
+		if order_of[root] != UNVISITED {
+			continue;
+		}
+
+		let mut work = vec![(root, 0)];
+
+		order_of[root] = next;
+		low_of[root] = next;
+		next += 1;
+		stack.push(root);
+		on_stack[root] = true;
+
+		while let Some((node, position)) = work.last_mut() {
+			let node = *node;
+
+			if let Some(&callee) = edge_list[node].get(*position) {
+				*position += 1;
+
+				if order_of[callee] == UNVISITED {
+					order_of[callee] = next;
+					low_of[callee] = next;
+					next += 1;
+					stack.push(callee);
+					on_stack[callee] = true;
+					work.push((callee, 0));
+				} else if on_stack[callee] {
+					low_of[node] = low_of[node].min(order_of[callee]);
+				}
+
+				continue;
+			}
+
+			work.pop();
+
+			if let Some(&(parent, _)) = work.last() {
+				low_of[parent] = low_of[parent].min(low_of[node]);
+			}
+
+			if low_of[node] == order_of[node] {
+				let mut component = Vec::new();
+
+				loop {
+					let last = stack.pop().unwrap();
+
+					on_stack[last] = false;
+					component.push(last);
+
+					if last == node {
+						break;
+					}
+				}
+
+				component.sort_unstable();
+				component_list.push(component);
+			}
+		}
+	}
+
+	component_list
+}
+
+/// The functions each function may call, grouped into strongly connected
+/// components.
+///
+/// Indirect calls are assumed to reach every function placed in a table by
+/// an element segment, named by `ref.func`, or exported. Imported functions
+/// have no known callees, so a host calling back into exported functions
+/// may still recurse through them unseen.
+pub struct CallGraph {
+	edge_list: Vec<Vec<usize>>,
+	component_list: Vec<Vec<usize>>,
+	component_of: Vec<usize>,
+}
+
+impl CallGraph {
+	/// Builds the graph from the bodies of every function defined, not
+	/// imported, by the module.
+	#[must_use]
+	pub fn new(wasm: &Module, func_list: &[FuncData]) -> Self {
+		let offset = wasm.import_count(External::Func);
+		let table_func_set = load_table_func_set(wasm);
+		let mut edge_list = vec![Vec::new(); offset];
+
+		for func in func_list {
+			let mut visit = Visit {
+				callee_set: BTreeSet::new(),
+				has_indirect: false,
+			};
+
+			func.accept(&mut visit);
+
+			if visit.has_indirect {
+				visit.callee_set.extend(&table_func_set);
+			}
+
+			edge_list.push(visit.callee_set.into_iter().collect());
+		}
+
+		let component_list = find_component_list(&edge_list);
+		let mut component_of = vec![0; edge_list.len()];
+
+		for (i, component) in component_list.iter().enumerate() {
+			for &node in component {
+				component_of[node] = i;
+			}
+		}
+
+		Self {
+			edge_list,
+			component_list,
+			component_of,
+		}
+	}
+
+	/// Returns the functions that the function at `index` may call.
+	#[must_use]
+	pub fn callee_list(&self, index: usize) -> &[usize] {
+		&self.edge_list[index]
+	}
+
+	/// Returns every strongly connected component, with callees listed
+	/// before their callers.
+	#[must_use]
+	pub fn component_list(&self) -> &[Vec<usize>] {
+		&self.component_list
+	}
+
+	/// Returns whether the function at `index` may call itself, directly or
+	/// through other functions.
+	#[must_use]
+	pub fn is_recursive(&self, index: usize) -> bool {
+		let component = &self.component_list[self.component_of[index]];
+
+		component.len() > 1 || self.edge_list[index].contains(&index)
+	}
+
+	/// Returns the components whose functions are recursive.
+	pub fn recursive_list(&self) -> impl Iterator<Item = &[usize]> {
+		self.component_list
+			.iter()
+			.filter(|v| self.is_recursive(v[0]))
+			.map(Vec::as_slice)
+	}
+}
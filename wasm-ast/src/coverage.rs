@@ -0,0 +1,26 @@
+/// The way a branch went, counted when `coverage_table` is set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+	/// A `br_if` whose condition held.
+	Taken,
+	/// A `br_if` whose condition did not hold.
+	NotTaken,
+	/// An `if` that ran its first arm.
+	Then,
+	/// An `if` that ran its `else` arm, or skipped a missing one.
+	Else,
+	/// A `br_table` that went to the block this many levels out.
+	Target(usize),
+}
+
+/// What a counter in the coverage table counts, found at the same index in
+/// the legend as its key in the table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Branch {
+	/// Index of the function in the module's function space.
+	pub function: usize,
+	/// Index of the branch among those of the function, in written order.
+	pub site: usize,
+	/// The way the branch went.
+	pub outcome: Outcome,
+}
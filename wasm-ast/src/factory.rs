@@ -1,14 +1,15 @@
-use wasmparser::{BlockType, FunctionBody, MemArg, Operator, Result};
+use wasmparser::{BlockType, FunctionBody, MemArg, Operator, Result, ValType};
 
 use crate::{
 	module::{read_checked, read_checked_locals, TypeInfo},
 	node::{
-		BinOp, BinOpType, Block, Br, BrIf, BrTable, Call, CallIndirect, CmpOp, CmpOpType,
+		BinOp, BinOpType, Block, Br, BrIf, BrTable, Call, CallIndirect, Catch, CmpOp, CmpOpType,
 		Expression, FuncData, GetGlobal, If, LabelType, LoadAt, LoadType, Local, MemoryArgument,
-		MemoryCopy, MemoryFill, MemoryGrow, MemorySize, Select, SetGlobal, SetLocal, Statement,
-		StoreAt, StoreType, Terminator, UnOp, UnOpType, Value,
+		MemoryCopy, MemoryFill, MemoryGrow, MemorySize, ResultList, Rethrow, Select, SetGlobal,
+		SetLocal, SetTemporary, Statement, StoreAt, StoreType, TableGet, TableSet, Terminator,
+		Throw, Try, UnOp, UnOpType, Unsupported, Value,
 	},
-	stack::{ReadGet, Stack},
+	stack::{MayTrap, ReadGet, Stack},
 };
 
 #[derive(Clone, Copy)]
@@ -17,13 +18,33 @@ enum BlockVariant {
 	Backward,
 	If,
 	Else,
+	Try,
 }
 
 enum BlockData {
-	Forward { num_result: usize },
-	Backward { num_param: usize },
-	If { num_result: usize, ty: BlockType },
-	Else { num_result: usize },
+	Forward {
+		num_result: usize,
+	},
+	Backward {
+		num_param: usize,
+	},
+	If {
+		num_result: usize,
+		ty: BlockType,
+	},
+	Else {
+		num_result: usize,
+	},
+	Try {
+		num_result: usize,
+		ty: BlockType,
+	},
+	Catch {
+		num_result: usize,
+		ty: BlockType,
+		tag: Option<usize>,
+		result_list: ResultList,
+	},
 }
 
 impl Default for BlockData {
@@ -35,9 +56,11 @@ impl Default for BlockData {
 impl From<BlockData> for LabelType {
 	fn from(data: BlockData) -> Self {
 		match data {
-			BlockData::Forward { .. } | BlockData::If { .. } | BlockData::Else { .. } => {
-				Self::Forward
-			}
+			BlockData::Forward { .. }
+			| BlockData::If { .. }
+			| BlockData::Else { .. }
+			| BlockData::Try { .. }
+			| BlockData::Catch { .. } => Self::Forward,
 			BlockData::Backward { .. } => Self::Backward,
 		}
 	}
@@ -215,6 +238,10 @@ pub struct Factory<'a> {
 	target: StatList,
 
 	nested_unreachable: usize,
+
+	trap_unsupported: bool,
+	function: Option<usize>,
+	unsupported_list: Vec<(Option<usize>, String)>,
 }
 
 impl<'a> Factory<'a> {
@@ -225,18 +252,38 @@ impl<'a> Factory<'a> {
 			pending: Vec::new(),
 			target: StatList::new(),
 			nested_unreachable: 0,
+			trap_unsupported: false,
+			function: None,
+			unsupported_list: Vec::new(),
 		}
 	}
 
+	/// Sets whether instructions that cannot be lowered are replaced by a
+	/// trap and recorded, rather than causing a panic.
+	pub const fn set_trap_unsupported(&mut self, trap: bool) {
+		self.trap_unsupported = trap;
+	}
+
+	/// Returns every instruction replaced by a trap so far, named by its
+	/// opcode and paired with the index of its function. Code built with
+	/// `create_anonymous` has no index.
+	#[must_use]
+	pub fn unsupported_list(&self) -> &[(Option<usize>, String)] {
+		&self.unsupported_list
+	}
+
 	#[must_use]
 	pub fn create_anonymous(&mut self, list: &[Operator]) -> FuncData {
+		self.function = None;
+
 		let data = self.build_stat_list(list, 1);
 
 		FuncData {
+			param_data: Vec::new(),
 			local_data: Vec::new(),
 			num_result: 1,
 			num_param: 0,
-			num_stack: data.stack.capacity,
+			num_stack: data.stack.capacity.max(1),
 			code: data.into(),
 		}
 	}
@@ -249,13 +296,21 @@ impl<'a> Factory<'a> {
 		let local_data = read_checked_locals(func.get_locals_reader()?)?;
 
 		let (num_param, num_result) = self.type_info.by_func_index(index);
+
+		self.function = Some(index);
+
 		let data = self.build_stat_list(&code, num_result);
 
+		// Bodies that never fall through, such as a lone `unreachable`, still
+		// end with a read of the result registers so they must be declared
+		let num_stack = data.stack.capacity.max(num_result);
+
 		Ok(FuncData {
+			param_data: self.type_info.by_func_index_params(index).to_vec(),
 			local_data,
 			num_result,
 			num_param,
-			num_stack: data.stack.capacity,
+			num_stack,
 			code: data.into(),
 		})
 	}
@@ -276,6 +331,7 @@ impl<'a> Factory<'a> {
 
 				BlockData::Else { num_result }
 			}
+			BlockVariant::Try => BlockData::Try { num_result, ty },
 		};
 
 		self.target.stack = old.stack.split_last(num_param, num_result);
@@ -295,6 +351,40 @@ impl<'a> Factory<'a> {
 		self.start_block(ty, BlockVariant::Else);
 	}
 
+	// Handlers start where the `try` did, but with the values of the
+	// exception in place of its parameters
+	fn start_catch(&mut self, tag: Option<usize>) {
+		let (BlockData::Try { ty, .. } | BlockData::Catch { ty, .. }) = self.target.block_data
+		else {
+			unreachable!()
+		};
+
+		let (_, num_result) = self.type_info.by_block_type(ty);
+		let num_value = tag.map_or(0, |tag| self.type_info.by_tag_index(tag));
+
+		self.target.leak_all();
+		self.end_block();
+
+		let mut old = std::mem::take(&mut self.target);
+
+		old.stack.pop_len(num_result).for_each(drop);
+
+		let result_list = old.stack.push_temporaries(num_value);
+
+		self.target.block_data = BlockData::Catch {
+			num_result,
+			ty,
+			tag,
+			result_list,
+		};
+
+		self.target.stack = old.stack.split_last(num_value, num_result);
+
+		old.stack.push_temporaries(num_result);
+
+		self.pending.push(old);
+	}
+
 	fn end_block(&mut self) {
 		let old = self.pending.pop().unwrap();
 		let now = std::mem::replace(&mut self.target, old);
@@ -315,6 +405,29 @@ impl<'a> Factory<'a> {
 
 				last.on_false = Some(Box::new(now.into()));
 
+				return;
+			}
+			BlockData::Try { .. } => Statement::Try(Try {
+				body: Box::new(now.into()),
+				catch_list: Vec::new(),
+				catch_all: None,
+			}),
+			BlockData::Catch {
+				tag, result_list, ..
+			} => {
+				let Statement::Try(last) = self.target.code.last_mut().unwrap() else {
+					unreachable!()
+				};
+
+				match tag {
+					Some(tag) => last.catch_list.push(Catch {
+						tag,
+						result_list,
+						block: now.into(),
+					}),
+					None => last.catch_all = Some(Box::new(now.into())),
+				}
+
 				return;
 			}
 		};
@@ -338,7 +451,9 @@ impl<'a> Factory<'a> {
 		let result = match block.block_data {
 			BlockData::Forward { num_result }
 			| BlockData::If { num_result, .. }
-			| BlockData::Else { num_result } => num_result,
+			| BlockData::Else { num_result }
+			| BlockData::Try { num_result, .. }
+			| BlockData::Catch { num_result, .. } => num_result,
 			BlockData::Backward { num_param } => num_param,
 		};
 
@@ -349,6 +464,15 @@ impl<'a> Factory<'a> {
 		Br { target, align }
 	}
 
+	fn add_throw(&mut self, tag: usize) {
+		let num_value = self.type_info.by_tag_index(tag);
+		let value_list = self.target.stack.pop_len(num_value).collect();
+		let term = Terminator::Throw(Throw { tag, value_list });
+
+		self.target.set_terminator(term);
+		self.nested_unreachable += 1;
+	}
+
 	fn add_call(&mut self, function: usize) {
 		let (num_param, num_result) = self.type_info.by_func_index(function);
 		let param_list = self.target.stack.pop_len(num_param).collect();
@@ -385,10 +509,60 @@ impl<'a> Factory<'a> {
 		self.target.code.push(data);
 	}
 
+	// Lua only evaluates the value a `select` picks while WebAssembly evaluates
+	// both, so values that may trap are read into temporaries beforehand
+	fn add_select(&mut self) {
+		let condition = self.target.stack.pop().into();
+
+		self.target
+			.stack
+			.leak_last_into(&mut self.target.code, 2, MayTrap::run);
+
+		let data = Expression::Select(Select {
+			condition,
+			on_false: self.target.stack.pop().into(),
+			on_true: self.target.stack.pop().into(),
+		});
+
+		self.target.stack.push(data);
+	}
+
+	// Lua's `and`/`or` idiom gives the wrong operand when the chosen one is a
+	// `nil` reference, so the choice is made by a branch into a temporary
+	fn add_reference_select(&mut self) {
+		let condition = self.target.stack.pop().into();
+		let on_false = self.target.stack.pop();
+		let on_true = self.target.stack.pop();
+
+		// Values left on the stack may still read the slot the result takes
+		self.target.leak_all();
+
+		let var = self.target.stack.push_temporary();
+		let branch = |value: Expression| Block {
+			label_type: None,
+			code: vec![Statement::SetTemporary(SetTemporary {
+				var,
+				value: value.into(),
+			})],
+			last: None,
+		};
+
+		let data = Statement::If(If {
+			condition,
+			on_true: branch(on_true).into(),
+			on_false: Some(branch(on_false).into()),
+		});
+
+		self.target.code.push(data);
+	}
+
 	#[cold]
 	fn drop_unreachable(&mut self, op: &Operator) {
 		match op {
-			Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+			Operator::Block { .. }
+			| Operator::Loop { .. }
+			| Operator::If { .. }
+			| Operator::Try { .. } => {
 				self.nested_unreachable += 1;
 			}
 			Operator::Else if self.nested_unreachable == 1 => {
@@ -396,18 +570,55 @@ impl<'a> Factory<'a> {
 
 				self.start_else();
 			}
-			Operator::End if self.nested_unreachable == 1 => {
+			Operator::Catch { tag_index } if self.nested_unreachable == 1 => {
+				self.nested_unreachable -= 1;
+
+				self.start_catch(Some((*tag_index).try_into().unwrap()));
+			}
+			Operator::CatchAll if self.nested_unreachable == 1 => {
+				self.nested_unreachable -= 1;
+
+				self.start_catch(None);
+			}
+			Operator::End | Operator::Delegate { .. } if self.nested_unreachable == 1 => {
 				self.nested_unreachable -= 1;
 
 				self.end_block();
 			}
-			Operator::End => {
+			Operator::End | Operator::Delegate { .. } => {
 				self.nested_unreachable -= 1;
 			}
 			_ => {}
 		}
 	}
 
+	// Traps are stack polymorphic, so the rest of the block is dropped as if
+	// it followed an `unreachable`
+	#[cold]
+	fn add_unsupported(&mut self, op: &Operator) {
+		let debug = format!("{op:?}");
+		let name = debug
+			.split(|c: char| !c.is_alphanumeric())
+			.next()
+			.unwrap_or_default();
+
+		self.unsupported_list
+			.push((self.function, name.to_string()));
+
+		let term = Terminator::Unsupported(Unsupported {
+			name: name.to_string(),
+		});
+
+		self.target.set_terminator(term);
+
+		// A `delegate` also closes its `try`, so nothing after it is dropped
+		if matches!(op, Operator::Delegate { .. }) {
+			self.end_block();
+		} else {
+			self.nested_unreachable += 1;
+		}
+	}
+
 	#[allow(clippy::too_many_lines)]
 	fn add_instruction(&mut self, op: &Operator) {
 		if self.target.try_add_operation(op) {
@@ -436,6 +647,25 @@ impl<'a> Factory<'a> {
 			Operator::Else => {
 				self.start_else();
 			}
+			Operator::Try { blockty } => {
+				self.start_block(blockty, BlockVariant::Try);
+			}
+			Operator::Catch { tag_index } => {
+				self.start_catch(Some(tag_index.try_into().unwrap()));
+			}
+			Operator::CatchAll => {
+				self.start_catch(None);
+			}
+			Operator::Throw { tag_index } => {
+				self.add_throw(tag_index.try_into().unwrap());
+			}
+			Operator::Rethrow { relative_depth } => {
+				let target = relative_depth.try_into().unwrap();
+
+				self.target
+					.set_terminator(Terminator::Rethrow(Rethrow { target }));
+				self.nested_unreachable += 1;
+			}
 			Operator::End => {
 				self.target.leak_all();
 				self.end_block();
@@ -500,15 +730,10 @@ impl<'a> Factory<'a> {
 			Operator::Drop => {
 				self.target.stack.pop();
 			}
-			Operator::Select => {
-				let data = Expression::Select(Select {
-					condition: self.target.stack.pop().into(),
-					on_false: self.target.stack.pop().into(),
-					on_true: self.target.stack.pop().into(),
-				});
-
-				self.target.stack.push(data);
-			}
+			Operator::TypedSelect {
+				ty: ValType::Ref(_),
+			} => self.add_reference_select(),
+			Operator::Select | Operator::TypedSelect { .. } => self.add_select(),
 			Operator::LocalGet { local_index } => {
 				let var = local_index.try_into().unwrap();
 				let data = Expression::GetLocal(Local { var });
@@ -539,9 +764,15 @@ impl<'a> Factory<'a> {
 			}
 			Operator::GlobalGet { global_index } => {
 				let var = global_index.try_into().unwrap();
-				let data = Expression::GetGlobal(GetGlobal { var });
 
-				self.target.stack.push(data);
+				// Immutable globals with constant initializers are read as the constant
+				if let Some(value) = self.type_info.by_global_index(var) {
+					self.target.push_constant(value);
+				} else {
+					let data = Expression::GetGlobal(GetGlobal { var });
+
+					self.target.stack.push(data);
+				}
 			}
 			Operator::GlobalSet { global_index } => {
 				let var = global_index.try_into().unwrap();
@@ -639,10 +870,35 @@ impl<'a> Factory<'a> {
 
 				self.target.code.push(data);
 			}
+			Operator::TableGet { table } => {
+				let index = self.target.stack.pop().into();
+				let result = self.target.stack.push_temporary();
+
+				let data = Statement::TableGet(TableGet {
+					table: table.try_into().unwrap(),
+					result,
+					index,
+				});
+
+				self.target.code.push(data);
+			}
+			Operator::TableSet { table } => {
+				let value = self.target.stack.pop().into();
+				let index = self.target.stack.pop().into();
+
+				let data = Statement::TableSet(TableSet {
+					table: table.try_into().unwrap(),
+					index,
+					value,
+				});
+
+				self.target.code.push(data);
+			}
 			Operator::I32Const { value } => self.target.push_constant(value),
 			Operator::I64Const { value } => self.target.push_constant(value),
 			Operator::F32Const { value } => self.target.push_constant(value.bits()),
 			Operator::F64Const { value } => self.target.push_constant(value.bits()),
+			_ if self.trap_unsupported => self.add_unsupported(op),
 			_ => panic!("Unsupported instruction: {op:?}"),
 		}
 	}
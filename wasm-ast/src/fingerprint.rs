@@ -0,0 +1,48 @@
+use std::io::{Result, Write};
+
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// A writer that hashes everything passed through it with 64-bit FNV-1a.
+///
+/// Output is deterministic for a given module and set of options, so the
+/// hash can be used as a cache key without reading the output again.
+pub struct Fingerprint<W> {
+	inner: W,
+	hash: u64,
+}
+
+impl<W: Write> Fingerprint<W> {
+	pub const fn new(inner: W) -> Self {
+		Self {
+			inner,
+			hash: FNV_OFFSET,
+		}
+	}
+
+	/// Returns the hash of the bytes written so far.
+	#[must_use]
+	pub const fn hash(&self) -> u64 {
+		self.hash
+	}
+
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+impl<W: Write> Write for Fingerprint<W> {
+	fn write(&mut self, buf: &[u8]) -> Result<usize> {
+		let len = self.inner.write(buf)?;
+
+		for &byte in &buf[..len] {
+			self.hash = (self.hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+		}
+
+		Ok(len)
+	}
+
+	fn flush(&mut self) -> Result<()> {
+		self.inner.flush()
+	}
+}
@@ -1,6 +1,14 @@
+pub mod analyzer;
+pub mod call_graph;
+pub mod coverage;
 pub mod factory;
+pub mod fingerprint;
+pub mod manifest;
 pub mod module;
 pub mod node;
+pub mod size_limit;
+pub mod stack_depth;
+pub mod stats;
 pub mod visit;
 
 mod stack;
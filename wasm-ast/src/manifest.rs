@@ -0,0 +1,197 @@
+use wasmparser::{Type, TypeRef, ValType};
+
+use crate::module::{External, Module};
+
+/// The size bounds of a table, in elements, or a memory, in pages.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Limits {
+	pub initial: u64,
+	pub maximum: Option<u64>,
+}
+
+/// What the value supplied for an import must be, enough for hosts to
+/// generate stubs for it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ImportType<'a> {
+	Func {
+		param_list: &'a [ValType],
+		result_list: &'a [ValType],
+	},
+	Table(Limits),
+	Memory(Limits),
+	Global {
+		content_type: ValType,
+		mutable: bool,
+	},
+	Tag {
+		param_list: &'a [ValType],
+	},
+}
+
+/// A value the host must supply, found at `index` in the list of its kind.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ImportEntry<'a> {
+	pub module: &'a str,
+	pub name: &'a str,
+	pub kind: External,
+	pub index: usize,
+	pub ty: ImportType<'a>,
+}
+
+/// A value the module provides, taken from `index` in the list of its kind.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ExportEntry<'a> {
+	pub name: &'a str,
+	pub kind: External,
+	pub index: usize,
+}
+
+/// A description of what a translated module expects and provides.
+///
+/// Indices match the `FUNC_LIST`, `TABLE_LIST`, `MEMORY_LIST`,
+/// `GLOBAL_LIST`, and `TAG_LIST` of the generated code, so build systems
+/// can link modules or validate host bindings without reading the Lua
+/// source. Each import also carries the type the host must supply.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Manifest<'a> {
+	pub import_list: Vec<ImportEntry<'a>>,
+	pub export_list: Vec<ExportEntry<'a>>,
+	pub table_list: Vec<Limits>,
+	pub memory_list: Vec<Limits>,
+	pub global_list: Vec<ValType>,
+}
+
+impl<'a> Manifest<'a> {
+	#[must_use]
+	pub fn from_module(wasm: &'a Module) -> Self {
+		let mut temp = Self {
+			import_list: Vec::new(),
+			export_list: Vec::new(),
+			table_list: Vec::new(),
+			memory_list: Vec::new(),
+			global_list: Vec::new(),
+		};
+
+		temp.load_import_list(wasm);
+		temp.load_export_list(wasm);
+		temp.load_definition_list(wasm);
+		temp
+	}
+
+	fn load_import_list(&mut self, wasm: &'a Module) {
+		let mut func_count = 0;
+		let mut tag_count = 0;
+
+		for import in wasm.import_section() {
+			let (index, ty) = match import.ty {
+				TypeRef::Func(index) => {
+					let Type::Func(ty) = &wasm.type_section()[usize::try_from(index).unwrap()]
+					else {
+						unreachable!("functions must have a function type")
+					};
+
+					func_count += 1;
+
+					let ty = ImportType::Func {
+						param_list: ty.params(),
+						result_list: ty.results(),
+					};
+
+					(func_count - 1, ty)
+				}
+				TypeRef::Table(ty) => {
+					let limits = Limits {
+						initial: ty.initial.into(),
+						maximum: ty.maximum.map(Into::into),
+					};
+
+					self.table_list.push(limits);
+
+					(self.table_list.len() - 1, ImportType::Table(limits))
+				}
+				TypeRef::Memory(ty) => {
+					let limits = Limits {
+						initial: ty.initial,
+						maximum: ty.maximum,
+					};
+
+					self.memory_list.push(limits);
+
+					(self.memory_list.len() - 1, ImportType::Memory(limits))
+				}
+				TypeRef::Global(ty) => {
+					self.global_list.push(ty.content_type);
+
+					let ty = ImportType::Global {
+						content_type: ty.content_type,
+						mutable: ty.mutable,
+					};
+
+					(self.global_list.len() - 1, ty)
+				}
+				TypeRef::Tag(ty) => {
+					let Type::Func(ty) =
+						&wasm.type_section()[usize::try_from(ty.func_type_idx).unwrap()]
+					else {
+						unreachable!("tags must have a function type")
+					};
+
+					tag_count += 1;
+
+					let ty = ImportType::Tag {
+						param_list: ty.params(),
+					};
+
+					(tag_count - 1, ty)
+				}
+			};
+
+			self.import_list.push(ImportEntry {
+				module: import.module,
+				name: import.name,
+				kind: import.ty.into(),
+				index,
+				ty,
+			});
+		}
+	}
+
+	fn load_export_list(&mut self, wasm: &'a Module) {
+		let iter = wasm.export_section().iter().map(|v| ExportEntry {
+			name: v.name,
+			kind: v.kind.into(),
+			index: v.index.try_into().unwrap(),
+		});
+
+		self.export_list.extend(iter);
+	}
+
+	fn load_definition_list(&mut self, wasm: &Module) {
+		let iter = wasm.table_section().iter().map(|v| Limits {
+			initial: v.ty.initial.into(),
+			maximum: v.ty.maximum.map(Into::into),
+		});
+
+		self.table_list.extend(iter);
+
+		let iter = wasm.memory_section().iter().map(|v| Limits {
+			initial: v.initial,
+			maximum: v.maximum,
+		});
+
+		self.memory_list.extend(iter);
+
+		let iter = wasm.global_section().iter().map(|v| v.ty.content_type);
+
+		self.global_list.extend(iter);
+	}
+
+	/// Returns the index in `FUNC_LIST` of the function exported as `name`.
+	#[must_use]
+	pub fn find_func_export(&self, name: &str) -> Option<usize> {
+		self.export_list
+			.iter()
+			.find(|v| v.kind == External::Func && v.name == name)
+			.map(|v| v.index)
+	}
+}
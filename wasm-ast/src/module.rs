@@ -1,11 +1,14 @@
 use std::collections::HashMap;
 
 use wasmparser::{
-	BlockType, Data, Element, Export, ExternalKind, FunctionBody, Global, Import, LocalsReader,
-	MemoryType, Name, NameSectionReader, Parser, Payload, Result, Table, Type, TypeRef, ValType,
+	BlockType, Data, Element, Export, ExternalKind, FuncType, FunctionBody, Global, Import,
+	LocalsReader, MemoryType, Name, NameSectionReader, Operator, Parser, Payload, Result, Table,
+	TagType, Type, TypeRef, ValType,
 };
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+use crate::node::Value;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum External {
 	Func,
 	Table,
@@ -64,6 +67,7 @@ pub struct Module<'a> {
 	element_section: Vec<Element<'a>>,
 	data_section: Vec<Data<'a>>,
 	code_section: Vec<FunctionBody<'a>>,
+	tag_section: Vec<TagType>,
 
 	name_section: HashMap<u32, &'a str>,
 
@@ -86,6 +90,7 @@ impl<'a> Module<'a> {
 			element_section: Vec::new(),
 			data_section: Vec::new(),
 			code_section: Vec::new(),
+			tag_section: Vec::new(),
 			name_section: HashMap::new(),
 			start_section: None,
 		};
@@ -106,6 +111,7 @@ impl<'a> Module<'a> {
 				Payload::ExportSection(v) => self.export_section = read_checked(v)?,
 				Payload::ElementSection(v) => self.element_section = read_checked(v)?,
 				Payload::DataSection(v) => self.data_section = read_checked(v)?,
+				Payload::TagSection(v) => self.tag_section = read_checked(v)?,
 				Payload::CodeSectionEntry(v) => {
 					self.code_section.push(v);
 				}
@@ -156,6 +162,11 @@ impl<'a> Module<'a> {
 		self.import_count(External::Global) + self.global_section.len()
 	}
 
+	#[must_use]
+	pub fn tag_space(&self) -> usize {
+		self.import_count(External::Tag) + self.tag_section.len()
+	}
+
 	#[must_use]
 	pub fn type_section(&self) -> &[Type] {
 		&self.type_section
@@ -206,6 +217,11 @@ impl<'a> Module<'a> {
 		&self.code_section
 	}
 
+	#[must_use]
+	pub fn tag_section(&self) -> &[TagType] {
+		&self.tag_section
+	}
+
 	#[must_use]
 	pub const fn name_section(&self) -> &HashMap<u32, &'a str> {
 		&self.name_section
@@ -220,6 +236,8 @@ impl<'a> Module<'a> {
 pub struct TypeInfo<'a> {
 	type_list: &'a [Type],
 	func_list: Vec<usize>,
+	tag_list: Vec<usize>,
+	constant_data: Vec<Option<Value>>,
 }
 
 impl<'a> TypeInfo<'a> {
@@ -228,10 +246,14 @@ impl<'a> TypeInfo<'a> {
 		let mut temp = Self {
 			type_list: &wasm.type_section,
 			func_list: Vec::new(),
+			tag_list: Vec::new(),
+			constant_data: Vec::new(),
 		};
 
 		temp.load_import_list(&wasm.import_section);
 		temp.load_func_list(&wasm.func_section);
+		temp.load_tag_list(&wasm.tag_section);
+		temp.load_constant_data(&wasm.global_section);
 		temp
 	}
 
@@ -246,6 +268,24 @@ impl<'a> TypeInfo<'a> {
 			.map(|v| usize::try_from(v).unwrap());
 
 		self.func_list.extend(iter);
+
+		let iter = list
+			.iter()
+			.filter_map(|v| match v.ty {
+				TypeRef::Tag(v) => Some(v.func_type_idx),
+				_ => None,
+			})
+			.map(|v| usize::try_from(v).unwrap());
+
+		self.tag_list.extend(iter);
+
+		// Imported globals are never known ahead of time
+		let iter = list
+			.iter()
+			.filter(|v| matches!(v.ty, TypeRef::Global(_)))
+			.map(|_| None);
+
+		self.constant_data.extend(iter);
 	}
 
 	fn load_func_list(&mut self, list: &[u32]) {
@@ -254,6 +294,45 @@ impl<'a> TypeInfo<'a> {
 		self.func_list.extend(iter);
 	}
 
+	fn load_tag_list(&mut self, list: &[TagType]) {
+		let iter = list
+			.iter()
+			.map(|v| usize::try_from(v.func_type_idx).unwrap());
+
+		self.tag_list.extend(iter);
+	}
+
+	fn load_constant_data(&mut self, list: &[Global]) {
+		let iter = list.iter().map(|v| {
+			if v.ty.mutable {
+				return None;
+			}
+
+			let mut reader = v.init_expr.get_operators_reader();
+			let value = match reader.read().ok()? {
+				Operator::I32Const { value } => value.into(),
+				Operator::I64Const { value } => value.into(),
+				Operator::F32Const { value } => value.bits().into(),
+				Operator::F64Const { value } => value.bits().into(),
+				_ => return None,
+			};
+
+			matches!(reader.read(), Ok(Operator::End)).then_some(value)
+		});
+
+		self.constant_data.extend(iter);
+	}
+
+	/// Returns the signature of the function at `index` in the function space.
+	#[must_use]
+	pub fn func_type(&self, index: usize) -> &'a FuncType {
+		let Type::Func(ty) = &self.type_list[self.func_list[index]] else {
+			unreachable!("type at func index must be a func type");
+		};
+
+		ty
+	}
+
 	pub(crate) fn by_type_index(&self, index: usize) -> (usize, usize) {
 		let Type::Func(ty) = &self.type_list[index] else {
 			unreachable!("type at func index must be a func type");
@@ -268,6 +347,24 @@ impl<'a> TypeInfo<'a> {
 		self.by_type_index(adjusted)
 	}
 
+	pub(crate) fn by_func_index_params(&self, index: usize) -> &[ValType] {
+		let Type::Func(ty) = &self.type_list[self.func_list[index]] else {
+			unreachable!("type at func index must be a func type");
+		};
+
+		ty.params()
+	}
+
+	pub(crate) fn by_tag_index(&self, index: usize) -> usize {
+		let (num_param, _) = self.by_type_index(self.tag_list[index]);
+
+		num_param
+	}
+
+	pub(crate) fn by_global_index(&self, index: usize) -> Option<Value> {
+		self.constant_data.get(index).copied().flatten()
+	}
+
 	pub(crate) fn by_block_type(&self, ty: BlockType) -> (usize, usize) {
 		match ty {
 			BlockType::Empty => (0, 0),
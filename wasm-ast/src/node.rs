@@ -39,6 +39,26 @@ impl LoadType {
 			Self::I64_U32 => "i64_u32",
 		}
 	}
+
+	#[must_use]
+	pub const fn as_opcode(self) -> &'static str {
+		match self {
+			Self::I32 => "i32.load",
+			Self::I64 => "i64.load",
+			Self::F32 => "f32.load",
+			Self::F64 => "f64.load",
+			Self::I32_I8 => "i32.load8_s",
+			Self::I32_U8 => "i32.load8_u",
+			Self::I32_I16 => "i32.load16_s",
+			Self::I32_U16 => "i32.load16_u",
+			Self::I64_I8 => "i64.load8_s",
+			Self::I64_U8 => "i64.load8_u",
+			Self::I64_I16 => "i64.load16_s",
+			Self::I64_U16 => "i64.load16_u",
+			Self::I64_I32 => "i64.load32_s",
+			Self::I64_U32 => "i64.load32_u",
+		}
+	}
 }
 
 impl TryFrom<&Operator<'_>> for LoadType {
@@ -96,6 +116,21 @@ impl StoreType {
 			Self::I64_N32 => "i64_n32",
 		}
 	}
+
+	#[must_use]
+	pub const fn as_opcode(self) -> &'static str {
+		match self {
+			Self::I32 => "i32.store",
+			Self::I64 => "i64.store",
+			Self::F32 => "f32.store",
+			Self::F64 => "f64.store",
+			Self::I32_N8 => "i32.store8",
+			Self::I32_N16 => "i32.store16",
+			Self::I64_N8 => "i64.store8",
+			Self::I64_N16 => "i64.store16",
+			Self::I64_N32 => "i64.store32",
+		}
+	}
 }
 
 impl TryFrom<&Operator<'_>> for StoreType {
@@ -182,6 +217,9 @@ pub enum UnOpType {
 	Reinterpret_I64_F64,
 	Reinterpret_F32_I32,
 	Reinterpret_F64_I64,
+	Wrap_I31_I32,
+	Extend_I32_I31,
+	Extend_U32_I31,
 }
 
 impl UnOpType {
@@ -246,6 +284,76 @@ impl UnOpType {
 			Self::Reinterpret_I64_F64 => ("reinterpret", "i64_f64"),
 			Self::Reinterpret_F32_I32 => ("reinterpret", "f32_i32"),
 			Self::Reinterpret_F64_I64 => ("reinterpret", "f64_i64"),
+			Self::Wrap_I31_I32 => ("wrap", "i31_i32"),
+			Self::Extend_I32_I31 => ("extend", "i32_i31"),
+			Self::Extend_U32_I31 => ("extend", "u32_i31"),
+		}
+	}
+
+	#[must_use]
+	pub const fn as_opcode(self) -> &'static str {
+		match self {
+			Self::Clz_I32 => "i32.clz",
+			Self::Ctz_I32 => "i32.ctz",
+			Self::Popcnt_I32 => "i32.popcnt",
+			Self::Clz_I64 => "i64.clz",
+			Self::Ctz_I64 => "i64.ctz",
+			Self::Popcnt_I64 => "i64.popcnt",
+			Self::Abs_F32 => "f32.abs",
+			Self::Neg_F32 => "f32.neg",
+			Self::Ceil_F32 => "f32.ceil",
+			Self::Floor_F32 => "f32.floor",
+			Self::Truncate_F32 => "f32.trunc",
+			Self::Nearest_F32 => "f32.nearest",
+			Self::Sqrt_F32 => "f32.sqrt",
+			Self::Abs_F64 => "f64.abs",
+			Self::Neg_F64 => "f64.neg",
+			Self::Ceil_F64 => "f64.ceil",
+			Self::Floor_F64 => "f64.floor",
+			Self::Truncate_F64 => "f64.trunc",
+			Self::Nearest_F64 => "f64.nearest",
+			Self::Sqrt_F64 => "f64.sqrt",
+			Self::Wrap_I32_I64 => "i32.wrap_i64",
+			Self::Truncate_I32_F32 => "i32.trunc_f32_s",
+			Self::Truncate_I32_F64 => "i32.trunc_f64_s",
+			Self::Truncate_U32_F32 => "i32.trunc_f32_u",
+			Self::Truncate_U32_F64 => "i32.trunc_f64_u",
+			Self::Truncate_I64_F32 => "i64.trunc_f32_s",
+			Self::Truncate_I64_F64 => "i64.trunc_f64_s",
+			Self::Truncate_U64_F32 => "i64.trunc_f32_u",
+			Self::Truncate_U64_F64 => "i64.trunc_f64_u",
+			Self::Saturate_I32_F32 => "i32.trunc_sat_f32_s",
+			Self::Saturate_I32_F64 => "i32.trunc_sat_f64_s",
+			Self::Saturate_U32_F32 => "i32.trunc_sat_f32_u",
+			Self::Saturate_U32_F64 => "i32.trunc_sat_f64_u",
+			Self::Saturate_I64_F32 => "i64.trunc_sat_f32_s",
+			Self::Saturate_I64_F64 => "i64.trunc_sat_f64_s",
+			Self::Saturate_U64_F32 => "i64.trunc_sat_f32_u",
+			Self::Saturate_U64_F64 => "i64.trunc_sat_f64_u",
+			Self::Extend_I32_N8 => "i32.extend8_s",
+			Self::Extend_I32_N16 => "i32.extend16_s",
+			Self::Extend_I64_N8 => "i64.extend8_s",
+			Self::Extend_I64_N16 => "i64.extend16_s",
+			Self::Extend_I64_N32 => "i64.extend32_s",
+			Self::Extend_I64_I32 => "i64.extend_i32_s",
+			Self::Extend_I64_U32 => "i64.extend_i32_u",
+			Self::Convert_F32_I32 => "f32.convert_i32_s",
+			Self::Convert_F32_U32 => "f32.convert_i32_u",
+			Self::Convert_F32_I64 => "f32.convert_i64_s",
+			Self::Convert_F32_U64 => "f32.convert_i64_u",
+			Self::Demote_F32_F64 => "f32.demote_f64",
+			Self::Convert_F64_I32 => "f64.convert_i32_s",
+			Self::Convert_F64_U32 => "f64.convert_i32_u",
+			Self::Convert_F64_I64 => "f64.convert_i64_s",
+			Self::Convert_F64_U64 => "f64.convert_i64_u",
+			Self::Promote_F64_F32 => "f64.promote_f32",
+			Self::Reinterpret_I32_F32 => "i32.reinterpret_f32",
+			Self::Reinterpret_I64_F64 => "i64.reinterpret_f64",
+			Self::Reinterpret_F32_I32 => "f32.reinterpret_i32",
+			Self::Reinterpret_F64_I64 => "f64.reinterpret_i64",
+			Self::Wrap_I31_I32 => "i31.new",
+			Self::Extend_I32_I31 => "i31.get_s",
+			Self::Extend_U32_I31 => "i31.get_u",
 		}
 	}
 }
@@ -313,6 +421,9 @@ impl TryFrom<&Operator<'_>> for UnOpType {
 			Operator::I64ReinterpretF64 => Self::Reinterpret_I64_F64,
 			Operator::F32ReinterpretI32 => Self::Reinterpret_F32_I32,
 			Operator::F64ReinterpretI64 => Self::Reinterpret_F64_I64,
+			Operator::I31New => Self::Wrap_I31_I32,
+			Operator::I31GetS => Self::Extend_I32_I31,
+			Operator::I31GetU => Self::Extend_U32_I31,
 			_ => return Err(()),
 		};
 
@@ -419,6 +530,56 @@ impl BinOpType {
 			Self::Copysign_F64 => ("copysign", "f64"),
 		}
 	}
+
+	#[must_use]
+	pub const fn as_opcode(self) -> &'static str {
+		match self {
+			Self::Add_I32 => "i32.add",
+			Self::Sub_I32 => "i32.sub",
+			Self::Mul_I32 => "i32.mul",
+			Self::DivS_I32 => "i32.div_s",
+			Self::DivU_I32 => "i32.div_u",
+			Self::RemS_I32 => "i32.rem_s",
+			Self::RemU_I32 => "i32.rem_u",
+			Self::And_I32 => "i32.and",
+			Self::Or_I32 => "i32.or",
+			Self::Xor_I32 => "i32.xor",
+			Self::Shl_I32 => "i32.shl",
+			Self::ShrS_I32 => "i32.shr_s",
+			Self::ShrU_I32 => "i32.shr_u",
+			Self::Rotl_I32 => "i32.rotl",
+			Self::Rotr_I32 => "i32.rotr",
+			Self::Add_I64 => "i64.add",
+			Self::Sub_I64 => "i64.sub",
+			Self::Mul_I64 => "i64.mul",
+			Self::DivS_I64 => "i64.div_s",
+			Self::DivU_I64 => "i64.div_u",
+			Self::RemS_I64 => "i64.rem_s",
+			Self::RemU_I64 => "i64.rem_u",
+			Self::And_I64 => "i64.and",
+			Self::Or_I64 => "i64.or",
+			Self::Xor_I64 => "i64.xor",
+			Self::Shl_I64 => "i64.shl",
+			Self::ShrS_I64 => "i64.shr_s",
+			Self::ShrU_I64 => "i64.shr_u",
+			Self::Rotl_I64 => "i64.rotl",
+			Self::Rotr_I64 => "i64.rotr",
+			Self::Add_F32 => "f32.add",
+			Self::Sub_F32 => "f32.sub",
+			Self::Mul_F32 => "f32.mul",
+			Self::Div_F32 => "f32.div",
+			Self::Min_F32 => "f32.min",
+			Self::Max_F32 => "f32.max",
+			Self::Copysign_F32 => "f32.copysign",
+			Self::Add_F64 => "f64.add",
+			Self::Sub_F64 => "f64.sub",
+			Self::Mul_F64 => "f64.mul",
+			Self::Div_F64 => "f64.div",
+			Self::Min_F64 => "f64.min",
+			Self::Max_F64 => "f64.max",
+			Self::Copysign_F64 => "f64.copysign",
+		}
+	}
 }
 
 impl TryFrom<&Operator<'_>> for BinOpType {
@@ -554,6 +715,44 @@ impl CmpOpType {
 			Self::Ge_F64 => ("ge", "f64"),
 		}
 	}
+
+	#[must_use]
+	pub const fn as_opcode(self) -> &'static str {
+		match self {
+			Self::Eq_I32 => "i32.eq",
+			Self::Ne_I32 => "i32.ne",
+			Self::LtS_I32 => "i32.lt_s",
+			Self::LtU_I32 => "i32.lt_u",
+			Self::GtS_I32 => "i32.gt_s",
+			Self::GtU_I32 => "i32.gt_u",
+			Self::LeS_I32 => "i32.le_s",
+			Self::LeU_I32 => "i32.le_u",
+			Self::GeS_I32 => "i32.ge_s",
+			Self::GeU_I32 => "i32.ge_u",
+			Self::Eq_I64 => "i64.eq",
+			Self::Ne_I64 => "i64.ne",
+			Self::LtS_I64 => "i64.lt_s",
+			Self::LtU_I64 => "i64.lt_u",
+			Self::GtS_I64 => "i64.gt_s",
+			Self::GtU_I64 => "i64.gt_u",
+			Self::LeS_I64 => "i64.le_s",
+			Self::LeU_I64 => "i64.le_u",
+			Self::GeS_I64 => "i64.ge_s",
+			Self::GeU_I64 => "i64.ge_u",
+			Self::Eq_F32 => "f32.eq",
+			Self::Ne_F32 => "f32.ne",
+			Self::Lt_F32 => "f32.lt",
+			Self::Gt_F32 => "f32.gt",
+			Self::Le_F32 => "f32.le",
+			Self::Ge_F32 => "f32.ge",
+			Self::Eq_F64 => "f64.eq",
+			Self::Ne_F64 => "f64.ne",
+			Self::Lt_F64 => "f64.lt",
+			Self::Gt_F64 => "f64.gt",
+			Self::Le_F64 => "f64.le",
+			Self::Ge_F64 => "f64.ge",
+		}
+	}
 }
 
 impl TryFrom<&Operator<'_>> for CmpOpType {
@@ -609,6 +808,15 @@ pub struct Select {
 }
 
 impl Select {
+	#[must_use]
+	pub fn new(condition: Expression, on_true: Expression, on_false: Expression) -> Self {
+		Self {
+			condition: Box::new(condition),
+			on_true: Box::new(on_true),
+			on_false: Box::new(on_false),
+		}
+	}
+
 	#[must_use]
 	pub fn condition(&self) -> &Expression {
 		&self.condition
@@ -631,6 +839,11 @@ pub struct Temporary {
 }
 
 impl Temporary {
+	#[must_use]
+	pub const fn new(var: usize) -> Self {
+		Self { var }
+	}
+
 	#[must_use]
 	pub const fn var(self) -> usize {
 		self.var
@@ -643,6 +856,11 @@ pub struct Local {
 }
 
 impl Local {
+	#[must_use]
+	pub const fn new(var: usize) -> Self {
+		Self { var }
+	}
+
 	#[must_use]
 	pub const fn var(self) -> usize {
 		self.var
@@ -655,6 +873,11 @@ pub struct GetGlobal {
 }
 
 impl GetGlobal {
+	#[must_use]
+	pub const fn new(var: usize) -> Self {
+		Self { var }
+	}
+
 	#[must_use]
 	pub const fn var(self) -> usize {
 		self.var
@@ -669,6 +892,16 @@ pub struct LoadAt {
 }
 
 impl LoadAt {
+	#[must_use]
+	pub fn new(load_type: LoadType, memory: usize, offset: u32, pointer: Expression) -> Self {
+		Self {
+			load_type,
+			memory,
+			offset,
+			pointer: Box::new(pointer),
+		}
+	}
+
 	#[must_use]
 	pub const fn load_type(&self) -> LoadType {
 		self.load_type
@@ -696,6 +929,11 @@ pub struct MemorySize {
 }
 
 impl MemorySize {
+	#[must_use]
+	pub const fn new(memory: usize) -> Self {
+		Self { memory }
+	}
+
 	#[must_use]
 	pub const fn memory(&self) -> usize {
 		self.memory
@@ -740,6 +978,14 @@ pub struct UnOp {
 }
 
 impl UnOp {
+	#[must_use]
+	pub fn new(op_type: UnOpType, rhs: Expression) -> Self {
+		Self {
+			op_type,
+			rhs: Box::new(rhs),
+		}
+	}
+
 	#[must_use]
 	pub const fn op_type(&self) -> UnOpType {
 		self.op_type
@@ -758,6 +1004,15 @@ pub struct BinOp {
 }
 
 impl BinOp {
+	#[must_use]
+	pub fn new(op_type: BinOpType, lhs: Expression, rhs: Expression) -> Self {
+		Self {
+			op_type,
+			lhs: Box::new(lhs),
+			rhs: Box::new(rhs),
+		}
+	}
+
 	#[must_use]
 	pub const fn op_type(&self) -> BinOpType {
 		self.op_type
@@ -781,6 +1036,15 @@ pub struct CmpOp {
 }
 
 impl CmpOp {
+	#[must_use]
+	pub fn new(op_type: CmpOpType, lhs: Expression, rhs: Expression) -> Self {
+		Self {
+			op_type,
+			lhs: Box::new(lhs),
+			rhs: Box::new(rhs),
+		}
+	}
+
 	#[must_use]
 	pub const fn op_type(&self) -> CmpOpType {
 		self.op_type
@@ -840,6 +1104,11 @@ pub struct Align {
 }
 
 impl Align {
+	#[must_use]
+	pub const fn new(new: usize, old: usize, length: usize) -> Self {
+		Self { new, old, length }
+	}
+
 	#[must_use]
 	pub const fn is_aligned(self) -> bool {
 		self.length == 0 || self.new == self.old
@@ -863,6 +1132,11 @@ pub struct Br {
 }
 
 impl Br {
+	#[must_use]
+	pub const fn new(target: usize, align: Align) -> Self {
+		Self { target, align }
+	}
+
 	#[must_use]
 	pub const fn target(self) -> usize {
 		self.target
@@ -881,6 +1155,15 @@ pub struct BrTable {
 }
 
 impl BrTable {
+	#[must_use]
+	pub fn new(condition: Expression, data: Vec<Br>, default: Br) -> Self {
+		Self {
+			condition: Box::new(condition),
+			data,
+			default,
+		}
+	}
+
 	#[must_use]
 	pub fn condition(&self) -> &Expression {
 		&self.condition
@@ -903,10 +1186,71 @@ pub enum LabelType {
 	Backward,
 }
 
+/// An instruction the factory cannot lower, written as a trap in its place.
+pub struct Unsupported {
+	pub(crate) name: String,
+}
+
+impl Unsupported {
+	#[must_use]
+	pub const fn new(name: String) -> Self {
+		Self { name }
+	}
+
+	#[must_use]
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+}
+
+pub struct Throw {
+	pub(crate) tag: usize,
+	pub(crate) value_list: Vec<Expression>,
+}
+
+impl Throw {
+	#[must_use]
+	pub const fn new(tag: usize, value_list: Vec<Expression>) -> Self {
+		Self { tag, value_list }
+	}
+
+	#[must_use]
+	pub const fn tag(&self) -> usize {
+		self.tag
+	}
+
+	#[must_use]
+	pub fn value_list(&self) -> &[Expression] {
+		&self.value_list
+	}
+}
+
+/// Throws again the exception caught by the `catch` at `target`, counted
+/// in blocks out the same way as the target of a branch.
+#[derive(Clone, Copy)]
+pub struct Rethrow {
+	pub(crate) target: usize,
+}
+
+impl Rethrow {
+	#[must_use]
+	pub const fn new(target: usize) -> Self {
+		Self { target }
+	}
+
+	#[must_use]
+	pub const fn target(self) -> usize {
+		self.target
+	}
+}
+
 pub enum Terminator {
 	Unreachable,
+	Unsupported(Unsupported),
 	Br(Br),
 	BrTable(BrTable),
+	Throw(Throw),
+	Rethrow(Rethrow),
 }
 
 #[derive(Default)]
@@ -917,6 +1261,19 @@ pub struct Block {
 }
 
 impl Block {
+	#[must_use]
+	pub fn new(
+		label_type: Option<LabelType>,
+		code: Vec<Statement>,
+		last: Option<Terminator>,
+	) -> Self {
+		Self {
+			label_type,
+			code,
+			last: last.map(Box::new),
+		}
+	}
+
 	#[must_use]
 	pub const fn label_type(&self) -> Option<LabelType> {
 		self.label_type
@@ -927,6 +1284,13 @@ impl Block {
 		&self.code
 	}
 
+	/// Allows passes to rewrite the statements in place. Any temporaries
+	/// they introduce must stay below the function's `num_stack`.
+	#[must_use]
+	pub const fn code_mut(&mut self) -> &mut Vec<Statement> {
+		&mut self.code
+	}
+
 	#[must_use]
 	pub fn last(&self) -> Option<&Terminator> {
 		self.last.as_deref()
@@ -939,6 +1303,14 @@ pub struct BrIf {
 }
 
 impl BrIf {
+	#[must_use]
+	pub fn new(condition: Expression, target: Br) -> Self {
+		Self {
+			condition: Box::new(condition),
+			target,
+		}
+	}
+
 	#[must_use]
 	pub fn condition(&self) -> &Expression {
 		&self.condition
@@ -957,6 +1329,15 @@ pub struct If {
 }
 
 impl If {
+	#[must_use]
+	pub fn new(condition: Expression, on_true: Block, on_false: Option<Block>) -> Self {
+		Self {
+			condition: Box::new(condition),
+			on_true: Box::new(on_true),
+			on_false: on_false.map(Box::new),
+		}
+	}
+
 	#[must_use]
 	pub fn condition(&self) -> &Expression {
 		&self.condition
@@ -973,6 +1354,72 @@ impl If {
 	}
 }
 
+/// A handler of exceptions with its tag, which are given to it in the
+/// temporaries of `result_list`.
+pub struct Catch {
+	pub(crate) tag: usize,
+	pub(crate) result_list: ResultList,
+	pub(crate) block: Block,
+}
+
+impl Catch {
+	#[must_use]
+	pub const fn new(tag: usize, result_list: ResultList, block: Block) -> Self {
+		Self {
+			tag,
+			result_list,
+			block,
+		}
+	}
+
+	#[must_use]
+	pub const fn tag(&self) -> usize {
+		self.tag
+	}
+
+	#[must_use]
+	pub const fn result_list(&self) -> ResultList {
+		self.result_list
+	}
+
+	#[must_use]
+	pub const fn block(&self) -> &Block {
+		&self.block
+	}
+}
+
+pub struct Try {
+	pub(crate) body: Box<Block>,
+	pub(crate) catch_list: Vec<Catch>,
+	pub(crate) catch_all: Option<Box<Block>>,
+}
+
+impl Try {
+	#[must_use]
+	pub fn new(body: Block, catch_list: Vec<Catch>, catch_all: Option<Block>) -> Self {
+		Self {
+			body: Box::new(body),
+			catch_list,
+			catch_all: catch_all.map(Box::new),
+		}
+	}
+
+	#[must_use]
+	pub fn body(&self) -> &Block {
+		&self.body
+	}
+
+	#[must_use]
+	pub fn catch_list(&self) -> &[Catch] {
+		&self.catch_list
+	}
+
+	#[must_use]
+	pub fn catch_all(&self) -> Option<&Block> {
+		self.catch_all.as_deref()
+	}
+}
+
 pub struct Call {
 	pub(crate) function: usize,
 	pub(crate) param_list: Vec<Expression>,
@@ -980,6 +1427,19 @@ pub struct Call {
 }
 
 impl Call {
+	#[must_use]
+	pub const fn new(
+		function: usize,
+		param_list: Vec<Expression>,
+		result_list: ResultList,
+	) -> Self {
+		Self {
+			function,
+			param_list,
+			result_list,
+		}
+	}
+
 	#[must_use]
 	pub const fn function(&self) -> usize {
 		self.function
@@ -1004,6 +1464,21 @@ pub struct CallIndirect {
 }
 
 impl CallIndirect {
+	#[must_use]
+	pub fn new(
+		table: usize,
+		index: Expression,
+		param_list: Vec<Expression>,
+		result_list: ResultList,
+	) -> Self {
+		Self {
+			table,
+			index: Box::new(index),
+			param_list,
+			result_list,
+		}
+	}
+
 	#[must_use]
 	pub const fn table(&self) -> usize {
 		self.table
@@ -1031,6 +1506,14 @@ pub struct SetTemporary {
 }
 
 impl SetTemporary {
+	#[must_use]
+	pub fn new(var: Temporary, value: Expression) -> Self {
+		Self {
+			var,
+			value: Box::new(value),
+		}
+	}
+
 	#[must_use]
 	pub const fn var(&self) -> Temporary {
 		self.var
@@ -1048,6 +1531,14 @@ pub struct SetLocal {
 }
 
 impl SetLocal {
+	#[must_use]
+	pub fn new(var: Local, value: Expression) -> Self {
+		Self {
+			var,
+			value: Box::new(value),
+		}
+	}
+
 	#[must_use]
 	pub const fn var(&self) -> Local {
 		self.var
@@ -1065,6 +1556,14 @@ pub struct SetGlobal {
 }
 
 impl SetGlobal {
+	#[must_use]
+	pub fn new(var: usize, value: Expression) -> Self {
+		Self {
+			var,
+			value: Box::new(value),
+		}
+	}
+
 	#[must_use]
 	pub const fn var(&self) -> usize {
 		self.var
@@ -1085,6 +1584,23 @@ pub struct StoreAt {
 }
 
 impl StoreAt {
+	#[must_use]
+	pub fn new(
+		store_type: StoreType,
+		memory: usize,
+		offset: u32,
+		pointer: Expression,
+		value: Expression,
+	) -> Self {
+		Self {
+			store_type,
+			memory,
+			offset,
+			pointer: Box::new(pointer),
+			value: Box::new(value),
+		}
+	}
+
 	#[must_use]
 	pub const fn store_type(&self) -> StoreType {
 		self.store_type
@@ -1118,6 +1634,15 @@ pub struct MemoryGrow {
 }
 
 impl MemoryGrow {
+	#[must_use]
+	pub fn new(memory: usize, result: Temporary, size: Expression) -> Self {
+		Self {
+			memory,
+			result,
+			size: Box::new(size),
+		}
+	}
+
 	#[must_use]
 	pub const fn memory(&self) -> usize {
 		self.memory
@@ -1140,6 +1665,14 @@ pub struct MemoryArgument {
 }
 
 impl MemoryArgument {
+	#[must_use]
+	pub fn new(memory: usize, pointer: Expression) -> Self {
+		Self {
+			memory,
+			pointer: Box::new(pointer),
+		}
+	}
+
 	#[must_use]
 	pub const fn memory(&self) -> usize {
 		self.memory
@@ -1158,6 +1691,15 @@ pub struct MemoryCopy {
 }
 
 impl MemoryCopy {
+	#[must_use]
+	pub fn new(destination: MemoryArgument, source: MemoryArgument, size: Expression) -> Self {
+		Self {
+			destination,
+			source,
+			size: Box::new(size),
+		}
+	}
+
 	#[must_use]
 	pub const fn destination(&self) -> &MemoryArgument {
 		&self.destination
@@ -1181,6 +1723,15 @@ pub struct MemoryFill {
 }
 
 impl MemoryFill {
+	#[must_use]
+	pub fn new(destination: MemoryArgument, size: Expression, value: Expression) -> Self {
+		Self {
+			destination,
+			size: Box::new(size),
+			value: Box::new(value),
+		}
+	}
+
 	#[must_use]
 	pub const fn destination(&self) -> &MemoryArgument {
 		&self.destination
@@ -1197,10 +1748,75 @@ impl MemoryFill {
 	}
 }
 
+pub struct TableGet {
+	pub(crate) table: usize,
+	pub(crate) result: Temporary,
+	pub(crate) index: Box<Expression>,
+}
+
+impl TableGet {
+	#[must_use]
+	pub fn new(table: usize, result: Temporary, index: Expression) -> Self {
+		Self {
+			table,
+			result,
+			index: Box::new(index),
+		}
+	}
+
+	#[must_use]
+	pub const fn table(&self) -> usize {
+		self.table
+	}
+
+	#[must_use]
+	pub const fn result(&self) -> Temporary {
+		self.result
+	}
+
+	#[must_use]
+	pub fn index(&self) -> &Expression {
+		&self.index
+	}
+}
+
+pub struct TableSet {
+	pub(crate) table: usize,
+	pub(crate) index: Box<Expression>,
+	pub(crate) value: Box<Expression>,
+}
+
+impl TableSet {
+	#[must_use]
+	pub fn new(table: usize, index: Expression, value: Expression) -> Self {
+		Self {
+			table,
+			index: Box::new(index),
+			value: Box::new(value),
+		}
+	}
+
+	#[must_use]
+	pub const fn table(&self) -> usize {
+		self.table
+	}
+
+	#[must_use]
+	pub fn index(&self) -> &Expression {
+		&self.index
+	}
+
+	#[must_use]
+	pub fn value(&self) -> &Expression {
+		&self.value
+	}
+}
+
 pub enum Statement {
 	Block(Block),
 	BrIf(BrIf),
 	If(If),
+	Try(Try),
 	Call(Call),
 	CallIndirect(CallIndirect),
 	SetTemporary(SetTemporary),
@@ -1210,9 +1826,12 @@ pub enum Statement {
 	MemoryGrow(MemoryGrow),
 	MemoryCopy(MemoryCopy),
 	MemoryFill(MemoryFill),
+	TableGet(TableGet),
+	TableSet(TableSet),
 }
 
 pub struct FuncData {
+	pub(crate) param_data: Vec<ValType>,
 	pub(crate) local_data: Vec<ValType>,
 	pub(crate) num_result: usize,
 	pub(crate) num_param: usize,
@@ -1221,6 +1840,11 @@ pub struct FuncData {
 }
 
 impl FuncData {
+	#[must_use]
+	pub fn param_data(&self) -> &[ValType] {
+		&self.param_data
+	}
+
 	#[must_use]
 	pub fn local_data(&self) -> &[ValType] {
 		&self.local_data
@@ -1245,4 +1869,9 @@ impl FuncData {
 	pub const fn code(&self) -> &Block {
 		&self.code
 	}
+
+	#[must_use]
+	pub const fn code_mut(&mut self) -> &mut Block {
+		&mut self.code
+	}
 }
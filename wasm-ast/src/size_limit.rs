@@ -0,0 +1,42 @@
+use std::io::{Error, ErrorKind, Result, Write};
+
+/// A writer that fails once the output would grow past `limit` bytes.
+///
+/// Writes that would take the output past the limit fail before any of their
+/// bytes are passed on, so the output never grows beyond it.
+pub struct SizeLimit<'a> {
+	inner: &'a mut dyn Write,
+	limit: usize,
+	len: usize,
+}
+
+impl<'a> SizeLimit<'a> {
+	pub fn new(inner: &'a mut dyn Write, limit: usize) -> Self {
+		Self {
+			inner,
+			limit,
+			len: 0,
+		}
+	}
+}
+
+impl Write for SizeLimit<'_> {
+	fn write(&mut self, buf: &[u8]) -> Result<usize> {
+		if buf.len() > self.limit - self.len {
+			let limit = self.limit;
+			let error = format!("output is past the limit of {limit} bytes");
+
+			return Err(Error::new(ErrorKind::InvalidData, error));
+		}
+
+		let len = self.inner.write(buf)?;
+
+		self.len += len;
+
+		Ok(len)
+	}
+
+	fn flush(&mut self) -> Result<()> {
+		self.inner.flush()
+	}
+}
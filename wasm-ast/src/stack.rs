@@ -1,6 +1,7 @@
 use crate::{
 	node::{
-		Align, Expression, GetGlobal, LoadAt, Local, ResultList, SetTemporary, Statement, Temporary,
+		Align, BinOp, BinOpType, Expression, GetGlobal, LoadAt, Local, ResultList, SetTemporary,
+		Statement, Temporary, UnOp, UnOpType,
 	},
 	visit::{Driver, Visitor},
 };
@@ -51,6 +52,56 @@ where
 	}
 }
 
+// Loads out of bounds, division by zero, and truncation of values out of
+// range are the only ways evaluating an expression can trap
+pub struct MayTrap {
+	result: bool,
+}
+
+impl MayTrap {
+	pub fn run<D: Driver<Self>>(node: &D) -> bool {
+		let mut visitor = Self { result: false };
+
+		node.accept(&mut visitor);
+
+		visitor.result
+	}
+}
+
+impl Visitor for MayTrap {
+	fn visit_load_at(&mut self, _: &LoadAt) {
+		self.result = true;
+	}
+
+	fn visit_un_op(&mut self, un_op: &UnOp) {
+		self.result |= matches!(
+			un_op.op_type(),
+			UnOpType::Truncate_I32_F32
+				| UnOpType::Truncate_I32_F64
+				| UnOpType::Truncate_U32_F32
+				| UnOpType::Truncate_U32_F64
+				| UnOpType::Truncate_I64_F32
+				| UnOpType::Truncate_I64_F64
+				| UnOpType::Truncate_U64_F32
+				| UnOpType::Truncate_U64_F64
+		);
+	}
+
+	fn visit_bin_op(&mut self, bin_op: &BinOp) {
+		self.result |= matches!(
+			bin_op.op_type(),
+			BinOpType::DivS_I32
+				| BinOpType::DivU_I32
+				| BinOpType::RemS_I32
+				| BinOpType::RemU_I32
+				| BinOpType::DivS_I64
+				| BinOpType::DivU_I64
+				| BinOpType::RemS_I64
+				| BinOpType::RemU_I64
+		);
+	}
+}
+
 #[derive(Default)]
 pub struct Stack {
 	var_list: Vec<Expression>,
@@ -124,13 +175,33 @@ impl Stack {
 	pub fn leak_into<P>(&mut self, code: &mut Vec<Statement>, predicate: P)
 	where
 		P: Fn(&Expression) -> bool,
+	{
+		self.leak_where(code, |_, node| predicate(node));
+	}
+
+	// Leak all of the last `len` slots if any of their values matches, so
+	// that they are still evaluated in order
+	pub fn leak_last_into<P>(&mut self, code: &mut Vec<Statement>, len: usize, predicate: P)
+	where
+		P: Fn(&Expression) -> bool,
+	{
+		let start = self.len() - len;
+
+		if self.var_list[start..].iter().any(predicate) {
+			self.leak_where(code, |i, _| i >= start);
+		}
+	}
+
+	fn leak_where<P>(&mut self, code: &mut Vec<Statement>, predicate: P)
+	where
+		P: Fn(usize, &Expression) -> bool,
 	{
 		for (i, old) in self.var_list.iter_mut().enumerate() {
 			let var = self.previous + i;
 			let is_temporary =
 				matches!(old, Expression::GetTemporary(temporary) if temporary.var() == var);
 
-			if is_temporary || !predicate(old) {
+			if is_temporary || !predicate(i, old) {
 				continue;
 			}
 
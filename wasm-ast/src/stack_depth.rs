@@ -0,0 +1,219 @@
+use wasmparser::{BlockType, FunctionBody, Operator, Result};
+
+use crate::{
+	module::{read_checked, TypeInfo},
+	node::{BinOpType, CmpOpType, LoadType, StoreType, UnOpType},
+};
+
+struct Frame {
+	height: usize,
+	num_param: usize,
+	num_result: usize,
+}
+
+struct Visit<'a> {
+	type_info: &'a TypeInfo<'a>,
+	frame_list: Vec<Frame>,
+	height: usize,
+	max: usize,
+	nested_unreachable: usize,
+}
+
+impl Visit<'_> {
+	fn pop_push(&mut self, num_pop: usize, num_push: usize) {
+		self.height = self.height - num_pop + num_push;
+		self.max = self.max.max(self.height);
+	}
+
+	fn start_block(&mut self, ty: BlockType) {
+		let (num_param, num_result) = self.type_info.by_block_type(ty);
+
+		self.frame_list.push(Frame {
+			height: self.height - num_param,
+			num_param,
+			num_result,
+		});
+	}
+
+	fn start_else(&mut self) {
+		let frame = self.frame_list.last().unwrap();
+
+		self.height = frame.height + frame.num_param;
+	}
+
+	// Handlers start from the height of their `try` with the values of the
+	// exception pushed in place of its parameters
+	fn start_catch(&mut self, tag: Option<u32>) {
+		let num_value = tag.map_or(0, |tag| {
+			self.type_info.by_tag_index(tag.try_into().unwrap())
+		});
+		let height = self.frame_list.last().unwrap().height;
+
+		self.height = height;
+		self.pop_push(0, num_value);
+	}
+
+	fn end_block(&mut self) {
+		let frame = self.frame_list.pop().unwrap();
+
+		self.height = frame.height;
+		self.pop_push(0, frame.num_result);
+	}
+
+	// Mirrors the factory, which drops the rest of a block once it cannot be
+	// reached
+	fn drop_unreachable(&mut self, op: &Operator) {
+		match op {
+			Operator::Block { .. }
+			| Operator::Loop { .. }
+			| Operator::If { .. }
+			| Operator::Try { .. } => {
+				self.nested_unreachable += 1;
+			}
+			Operator::Else if self.nested_unreachable == 1 => {
+				self.nested_unreachable -= 1;
+
+				self.start_else();
+			}
+			Operator::Catch { tag_index } if self.nested_unreachable == 1 => {
+				self.nested_unreachable -= 1;
+
+				self.start_catch(Some(*tag_index));
+			}
+			Operator::CatchAll if self.nested_unreachable == 1 => {
+				self.nested_unreachable -= 1;
+
+				self.start_catch(None);
+			}
+			Operator::End | Operator::Delegate { .. } if self.nested_unreachable == 1 => {
+				self.nested_unreachable -= 1;
+
+				self.end_block();
+			}
+			Operator::End | Operator::Delegate { .. } => {
+				self.nested_unreachable -= 1;
+			}
+			_ => {}
+		}
+	}
+
+	fn try_add_operation(&mut self, op: &Operator) -> bool {
+		if UnOpType::try_from(op).is_ok() || LoadType::try_from(op).is_ok() {
+			self.pop_push(1, 1);
+		} else if BinOpType::try_from(op).is_ok() || CmpOpType::try_from(op).is_ok() {
+			self.pop_push(2, 1);
+		} else if StoreType::try_from(op).is_ok() {
+			self.pop_push(2, 0);
+		} else {
+			return false;
+		}
+
+		true
+	}
+
+	fn add_instruction(&mut self, op: &Operator) {
+		if self.try_add_operation(op) {
+			return;
+		}
+
+		match *op {
+			Operator::Unreachable
+			| Operator::Br { .. }
+			| Operator::Return
+			| Operator::Rethrow { .. } => {
+				self.nested_unreachable += 1;
+			}
+			Operator::Throw { tag_index } => {
+				let num_value = self.type_info.by_tag_index(tag_index.try_into().unwrap());
+
+				self.pop_push(num_value, 0);
+				self.nested_unreachable += 1;
+			}
+			Operator::BrTable { .. } => {
+				self.pop_push(1, 0);
+				self.nested_unreachable += 1;
+			}
+			Operator::Nop => {}
+			Operator::Block { blockty }
+			| Operator::Loop { blockty }
+			| Operator::Try { blockty } => {
+				self.start_block(blockty);
+			}
+			Operator::If { blockty } => {
+				self.pop_push(1, 0);
+				self.start_block(blockty);
+			}
+			Operator::Else => self.start_else(),
+			Operator::Catch { tag_index } => self.start_catch(Some(tag_index)),
+			Operator::CatchAll => self.start_catch(None),
+			Operator::End | Operator::Delegate { .. } => self.end_block(),
+			Operator::Call { function_index } => {
+				let index = function_index.try_into().unwrap();
+				let (num_param, num_result) = self.type_info.by_func_index(index);
+
+				self.pop_push(num_param, num_result);
+			}
+			Operator::CallIndirect { type_index, .. } => {
+				let index = type_index.try_into().unwrap();
+				let (num_param, num_result) = self.type_info.by_type_index(index);
+
+				self.pop_push(num_param + 1, num_result);
+			}
+			Operator::I32Eqz
+			| Operator::I64Eqz
+			| Operator::LocalTee { .. }
+			| Operator::MemoryGrow { .. }
+			| Operator::TableGet { .. } => self.pop_push(1, 1),
+			Operator::Drop
+			| Operator::BrIf { .. }
+			| Operator::LocalSet { .. }
+			| Operator::GlobalSet { .. } => self.pop_push(1, 0),
+			Operator::TableSet { .. } => self.pop_push(2, 0),
+			Operator::Select | Operator::TypedSelect { .. } => self.pop_push(3, 1),
+			Operator::MemoryCopy { .. } | Operator::MemoryFill { .. } => self.pop_push(3, 0),
+			Operator::LocalGet { .. }
+			| Operator::GlobalGet { .. }
+			| Operator::MemorySize { .. }
+			| Operator::I32Const { .. }
+			| Operator::I64Const { .. }
+			| Operator::F32Const { .. }
+			| Operator::F64Const { .. } => self.pop_push(0, 1),
+			_ => self.nested_unreachable += 1,
+		}
+	}
+}
+
+/// Returns the deepest the operand stack grows in the body of the function
+/// at `index`, found by simulating what each instruction pops and pushes.
+///
+/// Code that cannot be reached is skipped, as are instructions the factory
+/// does not support. Every temporary in the syntax tree of the function
+/// stands for a slot of this stack, so its `num_stack` never exceeds it.
+///
+/// # Errors
+/// Returns an error if the function is malformed.
+pub fn max_stack_depth(type_info: &TypeInfo, index: usize, func: &FunctionBody) -> Result<usize> {
+	let code = read_checked(func.get_operators_reader()?)?;
+	let (_, num_result) = type_info.by_func_index(index);
+	let mut visit = Visit {
+		type_info,
+		frame_list: vec![Frame {
+			height: 0,
+			num_param: 0,
+			num_result,
+		}],
+		height: 0,
+		max: 0,
+		nested_unreachable: 0,
+	};
+
+	for op in &code {
+		if visit.nested_unreachable == 0 {
+			visit.add_instruction(op);
+		} else {
+			visit.drop_unreachable(op);
+		}
+	}
+
+	Ok(visit.max)
+}
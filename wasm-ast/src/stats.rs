@@ -0,0 +1,15 @@
+/// Counts of operations emitted for a single function, useful for finding
+/// which functions dominate memory traffic or call overhead.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FunctionStats {
+	/// Index of the function in the module's function space.
+	pub index: usize,
+	/// Number of memory loads emitted.
+	pub loads: usize,
+	/// Number of memory stores emitted.
+	pub stores: usize,
+	/// Number of direct and indirect calls emitted.
+	pub calls: usize,
+	/// Number of branches emitted, counting each target of a jump table.
+	pub branches: usize,
+}
@@ -1,7 +1,8 @@
 use crate::node::{
-	BinOp, Block, Br, BrIf, BrTable, Call, CallIndirect, CmpOp, Expression, FuncData, GetGlobal,
-	If, LoadAt, Local, MemoryCopy, MemoryFill, MemoryGrow, MemorySize, Select, SetGlobal, SetLocal,
-	SetTemporary, Statement, StoreAt, Temporary, Terminator, UnOp, Value,
+	BinOp, Block, Br, BrIf, BrTable, Call, CallIndirect, Catch, CmpOp, Expression, FuncData,
+	GetGlobal, If, LoadAt, Local, MemoryCopy, MemoryFill, MemoryGrow, MemorySize, Select,
+	SetGlobal, SetLocal, SetTemporary, Statement, StoreAt, TableGet, TableSet, Temporary,
+	Terminator, Throw, Try, UnOp, Value,
 };
 
 pub trait Visitor {
@@ -33,6 +34,8 @@ pub trait Visitor {
 
 	fn visit_br_table(&mut self, _: &BrTable) {}
 
+	fn visit_throw(&mut self, _: &Throw) {}
+
 	fn visit_terminator(&mut self, _: &Terminator) {}
 
 	fn visit_block(&mut self, _: &Block) {}
@@ -41,6 +44,10 @@ pub trait Visitor {
 
 	fn visit_if(&mut self, _: &If) {}
 
+	fn visit_catch(&mut self, _: &Catch) {}
+
+	fn visit_try(&mut self, _: &Try) {}
+
 	fn visit_call(&mut self, _: &Call) {}
 
 	fn visit_call_indirect(&mut self, _: &CallIndirect) {}
@@ -59,6 +66,10 @@ pub trait Visitor {
 
 	fn visit_memory_fill(&mut self, _: &MemoryFill) {}
 
+	fn visit_table_get(&mut self, _: &TableGet) {}
+
+	fn visit_table_set(&mut self, _: &TableSet) {}
+
 	fn visit_statement(&mut self, _: &Statement) {}
 }
 
@@ -128,6 +139,23 @@ impl<T: Visitor> Driver<T> for MemoryFill {
 	}
 }
 
+impl<T: Visitor> Driver<T> for TableGet {
+	fn accept(&self, visitor: &mut T) {
+		self.index().accept(visitor);
+
+		visitor.visit_table_get(self);
+	}
+}
+
+impl<T: Visitor> Driver<T> for TableSet {
+	fn accept(&self, visitor: &mut T) {
+		self.index().accept(visitor);
+		self.value().accept(visitor);
+
+		visitor.visit_table_set(self);
+	}
+}
+
 impl<T: Visitor> Driver<T> for Value {
 	fn accept(&self, visitor: &mut T) {
 		visitor.visit_value(*self);
@@ -193,12 +221,24 @@ impl<T: Visitor> Driver<T> for BrTable {
 	}
 }
 
+impl<T: Visitor> Driver<T> for Throw {
+	fn accept(&self, visitor: &mut T) {
+		for v in self.value_list() {
+			v.accept(visitor);
+		}
+
+		visitor.visit_throw(self);
+	}
+}
+
 impl<T: Visitor> Driver<T> for Terminator {
 	fn accept(&self, visitor: &mut T) {
 		match self {
-			Self::Unreachable => visitor.visit_unreachable(),
+			Self::Unreachable | Self::Unsupported(_) => visitor.visit_unreachable(),
 			Self::Br(v) => v.accept(visitor),
 			Self::BrTable(v) => v.accept(visitor),
+			Self::Throw(v) => v.accept(visitor),
+			Self::Rethrow(_) => {}
 		}
 
 		visitor.visit_terminator(self);
@@ -240,6 +280,32 @@ impl<T: Visitor> Driver<T> for If {
 	}
 }
 
+// Handlers are visited before their code, as that is where they receive
+// the values of the exception
+impl<T: Visitor> Driver<T> for Catch {
+	fn accept(&self, visitor: &mut T) {
+		visitor.visit_catch(self);
+
+		self.block().accept(visitor);
+	}
+}
+
+impl<T: Visitor> Driver<T> for Try {
+	fn accept(&self, visitor: &mut T) {
+		self.body().accept(visitor);
+
+		for v in self.catch_list() {
+			v.accept(visitor);
+		}
+
+		if let Some(v) = self.catch_all() {
+			v.accept(visitor);
+		}
+
+		visitor.visit_try(self);
+	}
+}
+
 impl<T: Visitor> Driver<T> for Call {
 	fn accept(&self, visitor: &mut T) {
 		for v in self.param_list() {
@@ -309,6 +375,7 @@ impl<T: Visitor> Driver<T> for Statement {
 			Self::Block(v) => v.accept(visitor),
 			Self::BrIf(v) => v.accept(visitor),
 			Self::If(v) => v.accept(visitor),
+			Self::Try(v) => v.accept(visitor),
 			Self::Call(v) => v.accept(visitor),
 			Self::CallIndirect(v) => v.accept(visitor),
 			Self::SetTemporary(v) => v.accept(visitor),
@@ -318,6 +385,8 @@ impl<T: Visitor> Driver<T> for Statement {
 			Self::MemoryGrow(v) => v.accept(visitor),
 			Self::MemoryCopy(v) => v.accept(visitor),
 			Self::MemoryFill(v) => v.accept(visitor),
+			Self::TableGet(v) => v.accept(visitor),
+			Self::TableSet(v) => v.accept(visitor),
 		}
 
 		visitor.visit_statement(self);